@@ -0,0 +1,739 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// A `keybindings` section: each child names an action and lists the keys
+/// for it, replacing its default keys. An action with no keys is unbound.
+///
+/// ```kdl
+/// keybindings {
+///     next-service "n" "down"
+///     prev-service "e" "up"
+///     scroll-page-down "ctrl-d" "pagedown"
+/// }
+/// ```
+#[derive(knus::Decode, Debug, Default, PartialEq, Clone)]
+pub struct Keybindings {
+    #[knus(children)]
+    pub bindings: Vec<Binding>,
+}
+
+#[derive(knus::Decode, Debug, PartialEq, Clone)]
+pub struct Binding {
+    #[knus(node_name)]
+    pub action: Action,
+    #[knus(arguments, str)]
+    pub keys: Vec<KeyChord>,
+}
+
+impl Keybindings {
+    /// These bindings with `overrides` applied on top, per action.
+    pub fn merge(mut self, overrides: Keybindings) -> Self {
+        for binding in overrides.bindings {
+            self.bindings.retain(|b| b.action != binding.action);
+            self.bindings.push(binding);
+        }
+        self
+    }
+
+    /// The defaults with these bindings applied. Fails when a key ends up
+    /// bound to two actions of one mode, or `quit` to none.
+    pub fn keymap(&self) -> Result<Keymap, String> {
+        let mut bindings: Vec<(Action, Vec<KeyChord>)> = ACTIONS
+            .iter()
+            .map(|(action, ..)| (*action, action.default_keys()))
+            .collect();
+        for binding in &self.bindings {
+            if let Some((_, keys)) = bindings.iter_mut().find(|(a, _)| *a == binding.action) {
+                keys.clone_from(&binding.keys);
+            }
+        }
+
+        if bindings
+            .iter()
+            .any(|(action, keys)| *action == Action::Quit && keys.is_empty())
+        {
+            return Err(format!("{} must keep a key", Action::Quit));
+        }
+
+        let mut actions = HashMap::new();
+        for mode in Mode::ALL {
+            let active = bindings.iter().filter(|(action, _)| mode.handles(*action));
+            for (action, keys) in active {
+                for key in keys {
+                    if let Some(other) = actions.insert((mode, *key), *action)
+                        && other != *action
+                    {
+                        return Err(format!("`{key}` is bound to both {other} and {action}"));
+                    }
+                }
+            }
+        }
+
+        Ok(Keymap { bindings, actions })
+    }
+}
+
+/// The keys of every action, defaults included.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    /// Keys of every action, in help order.
+    bindings: Vec<(Action, Vec<KeyChord>)>,
+    actions: HashMap<(Mode, KeyChord), Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keybindings::default()
+            .keymap()
+            .expect("default keys do not conflict")
+    }
+}
+
+impl Keymap {
+    /// What `key` does in `mode`, see `Mode::handles`.
+    pub fn action(&self, mode: Mode, key: KeyChord) -> Option<Action> {
+        self.actions.get(&(mode, key)).copied()
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyChord] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map_or(&[], |(_, keys)| keys)
+    }
+
+    /// Every action with its keys, related actions together.
+    pub fn iter(&self) -> impl Iterator<Item = (Action, &[KeyChord])> {
+        self.bindings
+            .iter()
+            .map(|(action, keys)| (*action, keys.as_slice()))
+    }
+}
+
+/// Which keys the TUI listens to: those of normal mode, or of a panel or
+/// prompt with keys of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Normal,
+    Copy,
+    ProcessTree,
+    SignalPicker,
+    Export,
+}
+
+impl Mode {
+    const ALL: [Mode; 5] = [
+        Mode::Normal,
+        Mode::Copy,
+        Mode::ProcessTree,
+        Mode::SignalPicker,
+        Mode::Export,
+    ];
+
+    /// Whether the keys of `action` work in this mode. Copy mode moves
+    /// with a few normal keys, the process tree and signal picker leave
+    /// every normal key working, and the export prompt takes only its own.
+    pub fn handles(self, action: Action) -> bool {
+        let mode = action.mode();
+        mode == self
+            || mode == Mode::Normal
+                && match self {
+                    Mode::Normal | Mode::ProcessTree | Mode::SignalPicker => true,
+                    Mode::Copy => matches!(
+                        action,
+                        Action::NextService
+                            | Action::PrevService
+                            | Action::ScrollPageUp
+                            | Action::ScrollPageDown
+                            | Action::Follow
+                            | Action::CopyMode
+                            | Action::Quit
+                    ),
+                    Mode::Export => false,
+                }
+    }
+}
+
+/// What a key does in the TUI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    NextService,
+    PrevService,
+    ScrollPageUp,
+    ScrollPageDown,
+    Follow,
+    Search,
+    CycleLevel,
+    ToggleHidden,
+    Pretty,
+    Fold,
+    Timestamps,
+    OpenReference,
+    Errors,
+    ProcessTree,
+    Signal,
+    Restart,
+    Resources,
+    Export,
+    CopyMode,
+    SplitSideBySide,
+    SplitStacked,
+    ClosePane,
+    NextPane,
+    PrevPane,
+    ToggleMouse,
+    Help,
+    Quit,
+    CopyTop,
+    CopyHalfPageDown,
+    CopyHalfPageUp,
+    CopySelect,
+    CopyYank,
+    TreeTerm,
+    TreeKill,
+    TreeInt,
+    TreeHup,
+    SignalGroup,
+    ExportPlain,
+    ExportAnsi,
+    ExportNdjson,
+    ExportHtml,
+}
+
+/// Every action: action, name in the `keybindings` section, default keys,
+/// description.
+const ACTIONS: [(Action, &str, &[&str], &str); 41] = [
+    (
+        Action::NextService,
+        "next-service",
+        &["j", "down"],
+        "next service, or down in lists",
+    ),
+    (
+        Action::PrevService,
+        "prev-service",
+        &["k", "up"],
+        "previous service, or up in lists",
+    ),
+    (
+        Action::ScrollPageUp,
+        "scroll-page-up",
+        &["pageup"],
+        "scroll up a page",
+    ),
+    (
+        Action::ScrollPageDown,
+        "scroll-page-down",
+        &["pagedown"],
+        "scroll down a page",
+    ),
+    (
+        Action::Follow,
+        "follow",
+        &["G", "end"],
+        "follow the output, or go to the bottom",
+    ),
+    (Action::Search, "search", &["/"], "search the output"),
+    (
+        Action::CycleLevel,
+        "cycle-level",
+        &["f"],
+        "cycle the minimum level",
+    ),
+    (
+        Action::ToggleHidden,
+        "toggle-hidden",
+        &["space"],
+        "show or hide in the merged view",
+    ),
+    (Action::Pretty, "pretty", &["p"], "toggle pretty JSON lines"),
+    (Action::Fold, "fold", &["z"], "fold multi-line entries"),
+    (Action::Timestamps, "timestamps", &["t"], "cycle timestamps"),
+    (
+        Action::OpenReference,
+        "open-reference",
+        &["o"],
+        "open a file:line in the editor",
+    ),
+    (Action::Errors, "errors", &["e"], "errors panel"),
+    (
+        Action::ProcessTree,
+        "process-tree",
+        &["T"],
+        "process tree panel",
+    ),
+    (Action::Signal, "signal", &["s"], "send a signal"),
+    (Action::Restart, "restart", &["R"], "restart the service"),
+    (Action::Resources, "resources", &["r"], "resources panel"),
+    (
+        Action::Export,
+        "export",
+        &["S"],
+        "save the output to a file",
+    ),
+    (
+        Action::CopyMode,
+        "copy-mode",
+        &["v"],
+        "copy mode, or select in it",
+    ),
+    (
+        Action::SplitSideBySide,
+        "split-side-by-side",
+        &["|"],
+        "split the pane side by side",
+    ),
+    (
+        Action::SplitStacked,
+        "split-stacked",
+        &["-"],
+        "split the pane stacked",
+    ),
+    (Action::ClosePane, "close-pane", &["c"], "close the pane"),
+    (
+        Action::NextPane,
+        "next-pane",
+        &["tab"],
+        "focus the next pane",
+    ),
+    (
+        Action::PrevPane,
+        "prev-pane",
+        &["shift-tab"],
+        "focus the previous pane",
+    ),
+    (
+        Action::ToggleMouse,
+        "toggle-mouse",
+        &["m"],
+        "toggle mouse support",
+    ),
+    (Action::Help, "help", &["?"], "show the keys"),
+    (
+        Action::Quit,
+        "quit",
+        &["q", "esc", "ctrl-c"],
+        "quit, or close",
+    ),
+    (
+        Action::CopyTop,
+        "copy-top",
+        &["g", "home"],
+        "copy mode: go to the top",
+    ),
+    (
+        Action::CopyHalfPageDown,
+        "copy-half-page-down",
+        &["ctrl-d"],
+        "copy mode: down half a page",
+    ),
+    (
+        Action::CopyHalfPageUp,
+        "copy-half-page-up",
+        &["ctrl-u"],
+        "copy mode: up half a page",
+    ),
+    (
+        Action::CopySelect,
+        "copy-select",
+        &["V", "space"],
+        "copy mode: select lines",
+    ),
+    (
+        Action::CopyYank,
+        "copy-yank",
+        &["y", "enter"],
+        "copy mode: copy the selection",
+    ),
+    (
+        Action::TreeTerm,
+        "tree-term",
+        &["x"],
+        "process tree: send TERM",
+    ),
+    (
+        Action::TreeKill,
+        "tree-kill",
+        &["X"],
+        "process tree: send KILL",
+    ),
+    (
+        Action::TreeInt,
+        "tree-int",
+        &["i"],
+        "process tree: send INT",
+    ),
+    (
+        Action::TreeHup,
+        "tree-hup",
+        &["h"],
+        "process tree: send HUP",
+    ),
+    (
+        Action::SignalGroup,
+        "signal-group",
+        &["g"],
+        "signal picker: process or group",
+    ),
+    (
+        Action::ExportPlain,
+        "export-plain",
+        &["p"],
+        "export: plain text",
+    ),
+    (
+        Action::ExportAnsi,
+        "export-ansi",
+        &["a"],
+        "export: with colors",
+    ),
+    (
+        Action::ExportNdjson,
+        "export-ndjson",
+        &["j"],
+        "export: NDJSON",
+    ),
+    (Action::ExportHtml, "export-html", &["h"], "export: HTML"),
+];
+
+impl Action {
+    fn entry(&self) -> &'static (Action, &'static str, &'static [&'static str], &'static str) {
+        ACTIONS
+            .iter()
+            .find(|(action, ..)| action == self)
+            .expect("every action is listed")
+    }
+
+    /// Name in the `keybindings` section.
+    pub fn as_str(&self) -> &'static str {
+        self.entry().1
+    }
+
+    pub fn description(&self) -> &'static str {
+        self.entry().3
+    }
+
+    pub fn mode(&self) -> Mode {
+        match self {
+            Action::CopyTop
+            | Action::CopyHalfPageDown
+            | Action::CopyHalfPageUp
+            | Action::CopySelect
+            | Action::CopyYank => Mode::Copy,
+            Action::TreeTerm | Action::TreeKill | Action::TreeInt | Action::TreeHup => {
+                Mode::ProcessTree
+            }
+            Action::SignalGroup => Mode::SignalPicker,
+            Action::ExportPlain
+            | Action::ExportAnsi
+            | Action::ExportNdjson
+            | Action::ExportHtml => Mode::Export,
+            _ => Mode::Normal,
+        }
+    }
+
+    pub fn default_keys(&self) -> Vec<KeyChord> {
+        self.entry()
+            .2
+            .iter()
+            .map(|key| key.parse().expect("default keys parse"))
+            .collect()
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ACTIONS
+            .iter()
+            .find(|(_, name, ..)| *name == s)
+            .map(|(action, ..)| *action)
+            .ok_or_else(|| format!("unknown action '{s}'"))
+    }
+}
+
+/// A key with its modifiers, written like `j`, `G`, `ctrl-d`, `space` or
+/// `shift-tab`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub key: Key,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// A printable character, upper case for shifted letters.
+    Char(char),
+    Enter,
+    Esc,
+    Tab,
+    BackTab,
+    Backspace,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    F(u8),
+}
+
+const NAMED_KEYS: [(&str, Key); 16] = [
+    ("space", Key::Char(' ')),
+    ("enter", Key::Enter),
+    ("esc", Key::Esc),
+    ("tab", Key::Tab),
+    ("backtab", Key::BackTab),
+    ("backspace", Key::Backspace),
+    ("delete", Key::Delete),
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("minus", Key::Char('-')),
+];
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chord = KeyChord {
+            key: Key::Esc,
+            ctrl: false,
+            alt: false,
+        };
+        let mut shift = false;
+        let mut rest = s;
+        // `-` is a key too, so only strip while something follows.
+        while let Some((modifier, key)) = rest.split_once('-')
+            && !key.is_empty()
+        {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => chord.ctrl = true,
+                "alt" => chord.alt = true,
+                "shift" => shift = true,
+                _ => break,
+            }
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        chord.key = match (chars.next(), chars.next()) {
+            (Some(c), None) if chord.ctrl => Key::Char(c.to_ascii_lowercase()),
+            (Some(c), None) if shift => Key::Char(c.to_ascii_uppercase()),
+            (Some(c), None) => Key::Char(c),
+            _ => {
+                let name = rest.to_ascii_lowercase();
+                let key = NAMED_KEYS
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, key)| *key)
+                    .or_else(|| match name.strip_prefix('f')?.parse() {
+                        Ok(n @ 1..=24) => Some(Key::F(n)),
+                        _ => None,
+                    });
+                match key {
+                    Some(Key::Tab) if shift => Key::BackTab,
+                    Some(key) => key,
+                    None => return Err(format!("unknown key '{s}'")),
+                }
+            }
+        };
+
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            f.write_str("ctrl-")?;
+        }
+        if self.alt {
+            f.write_str("alt-")?;
+        }
+        match self.key {
+            Key::Char(' ') => f.write_str("space"),
+            Key::Char(c) => write!(f, "{c}"),
+            Key::BackTab => f.write_str("shift-tab"),
+            Key::F(n) => write!(f, "f{n}"),
+            key => {
+                let (name, _) = NAMED_KEYS
+                    .iter()
+                    .find(|(_, k)| *k == key)
+                    .expect("every other key is named");
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn chord(key: &str) -> KeyChord {
+        key.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_displays_chords() {
+        for key in [
+            "j",
+            "G",
+            "?",
+            "-",
+            "space",
+            "enter",
+            "esc",
+            "tab",
+            "shift-tab",
+            "pageup",
+            "f5",
+            "ctrl-d",
+            "alt-x",
+            "ctrl-alt-k",
+        ] {
+            assert_eq!(chord(key).to_string(), key);
+        }
+
+        // Shifted letters are upper case, ctrl takes the letter either way.
+        assert_eq!(chord("shift-g"), chord("G"));
+        assert_eq!(chord("ctrl-D"), chord("ctrl-d"));
+        assert_eq!(chord("CTRL-d").to_string(), "ctrl-d");
+        assert_eq!(chord("shift-tab"), chord("backtab"));
+        assert_eq!(chord("minus"), chord("-"));
+        assert_eq!(chord("ctrl--").key, Key::Char('-'));
+        assert_eq!(chord("PageDown").to_string(), "pagedown");
+
+        assert!("f25".parse::<KeyChord>().is_err());
+        assert!("hyper-x".parse::<KeyChord>().is_err());
+        assert!("".parse::<KeyChord>().is_err());
+    }
+
+    #[test]
+    fn overrides_replace_keys_per_action() {
+        let user = Keybindings {
+            bindings: vec![
+                Binding {
+                    action: Action::NextService,
+                    keys: vec![chord("n")],
+                },
+                Binding {
+                    action: Action::Fold,
+                    keys: vec![chord("Z")],
+                },
+            ],
+        };
+        let project = Keybindings {
+            bindings: vec![Binding {
+                action: Action::NextService,
+                keys: vec![chord("down")],
+            }],
+        };
+
+        let keymap = user.merge(project).keymap().unwrap();
+        assert_eq!(keymap.keys(Action::NextService), [chord("down")]);
+        assert_eq!(keymap.keys(Action::Fold), [chord("Z")]);
+        assert_eq!(keymap.action(Mode::Normal, chord("n")), None);
+        assert_eq!(keymap.action(Mode::Normal, chord("j")), None);
+        assert_eq!(keymap.keys(Action::Follow), Action::Follow.default_keys());
+    }
+
+    #[test]
+    fn scopes_keys_to_modes() {
+        let keymap = Keymap::default();
+        let action = |mode, key| keymap.action(mode, chord(key));
+
+        assert_eq!(action(Mode::Normal, "j"), Some(Action::NextService));
+        assert_eq!(action(Mode::Export, "j"), Some(Action::ExportNdjson));
+        assert_eq!(action(Mode::Normal, "space"), Some(Action::ToggleHidden));
+        assert_eq!(action(Mode::Copy, "space"), Some(Action::CopySelect));
+        assert_eq!(action(Mode::Copy, "j"), Some(Action::NextService));
+        assert_eq!(action(Mode::Copy, "f"), None);
+        assert_eq!(action(Mode::ProcessTree, "x"), Some(Action::TreeTerm));
+        assert_eq!(action(Mode::ProcessTree, "f"), Some(Action::CycleLevel));
+        assert_eq!(action(Mode::Normal, "x"), None);
+
+        // The process tree leaves normal keys working, so they clash.
+        let conflict = Keybindings {
+            bindings: vec![Binding {
+                action: Action::TreeTerm,
+                keys: vec![chord("f")],
+            }],
+        };
+        assert_eq!(
+            conflict.keymap().unwrap_err(),
+            "`f` is bound to both cycle-level and tree-term"
+        );
+
+        let unbound = Keybindings {
+            bindings: vec![Binding {
+                action: Action::Quit,
+                keys: Vec::new(),
+            }],
+        };
+        assert_eq!(unbound.keymap().unwrap_err(), "quit must keep a key");
+    }
+
+    #[test]
+    fn checks_conflicts_per_mode() {
+        let keymap = |action, key| {
+            Keybindings {
+                bindings: vec![Binding {
+                    action,
+                    keys: vec![chord(key)],
+                }],
+            }
+            .keymap()
+        };
+
+        // Copy mode shares the service and scroll keys only.
+        assert_eq!(
+            keymap(Action::CopyYank, "j").unwrap_err(),
+            "`j` is bound to both next-service and copy-yank"
+        );
+        let yank_f = keymap(Action::CopyYank, "f").unwrap();
+        assert_eq!(
+            yank_f.action(Mode::Copy, chord("f")),
+            Some(Action::CopyYank)
+        );
+        assert_eq!(
+            yank_f.action(Mode::Normal, chord("f")),
+            Some(Action::CycleLevel)
+        );
+        assert_eq!(yank_f.action(Mode::Copy, chord("y")), None);
+
+        assert_eq!(
+            keymap(Action::SignalGroup, "f").unwrap_err(),
+            "`f` is bound to both cycle-level and signal-group"
+        );
+
+        // The export prompt takes no other keys.
+        let export_q = keymap(Action::ExportPlain, "q").unwrap();
+        assert_eq!(
+            export_q.action(Mode::Export, chord("q")),
+            Some(Action::ExportPlain)
+        );
+        assert_eq!(
+            export_q.action(Mode::Normal, chord("q")),
+            Some(Action::Quit)
+        );
+        assert_eq!(export_q.action(Mode::Export, chord("n")), None);
+    }
+}
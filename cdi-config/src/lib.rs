@@ -1,12 +1,70 @@
-use std::{ffi::OsStr, path::Path};
+use std::{
+    env,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
-use knus;
 use miette::{Context, IntoDiagnostic};
 
-#[derive(knus::Decode, Debug, PartialEq)]
+mod keys;
+
+pub use keys::{Action, Binding, Key, KeyChord, Keybindings, Keymap, Mode};
+
+#[derive(knus::Decode, Debug, Default, PartialEq, Clone)]
 pub struct Config {
     #[knus(children(name = "service"))]
     pub services: Vec<Service>,
+    /// Command opening `{file}` at `{line}` and `{column}`, e.g.
+    /// `code -g {file}:{line}:{column}`. Defaults to `$VISUAL`/`$EDITOR`.
+    #[knus(child, unwrap(argument), default)]
+    pub editor: Option<String>,
+    /// Port of the HTTP API, served on 127.0.0.1 only. Off unless set.
+    #[knus(child, unwrap(argument), default)]
+    pub http: Option<u16>,
+    /// Port serving only Prometheus metrics at `/metrics`, on 127.0.0.1.
+    /// The HTTP API serves them too.
+    #[knus(child, unwrap(argument), default)]
+    pub metrics: Option<u16>,
+    /// Whether the TUI starts with mouse support, `m` toggles it. Off unless
+    /// set to `true`, so selecting text is left to the terminal.
+    #[knus(child, unwrap(argument), default)]
+    pub mouse: Option<bool>,
+    /// TUI keys, over those of the user-level config, see `UserConfig`.
+    #[knus(child, default)]
+    pub keybindings: Keybindings,
+}
+
+/// Settings of every project, from `$XDG_CONFIG_HOME/cdi/config.kdl` or
+/// `~/.config/cdi/config.kdl`.
+#[derive(knus::Decode, Debug, Default, PartialEq, Clone)]
+pub struct UserConfig {
+    #[knus(child, default)]
+    pub keybindings: Keybindings,
+}
+
+impl UserConfig {
+    pub fn path() -> Option<PathBuf> {
+        let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(config_dir.join("cdi").join("config.kdl"))
+    }
+
+    /// The user-level config, or the defaults when there is none.
+    pub fn load() -> miette::Result<Self> {
+        let Some(path) = Self::path().filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .into_diagnostic()
+            .with_context(|| format!("error reading {path:?}"))?;
+        knus::parse(path.to_string_lossy(), &contents)
+            .map_err(miette::Report::new)
+            .with_context(|| format!("error loading {path:?}"))
+    }
 }
 
 #[derive(knus::Decode, Debug, Default, PartialEq, Eq, Clone)]
@@ -17,11 +75,30 @@ pub struct Service {
     pub name: String,
     #[knus(child, unwrap(argument), default)]
     pub cwd: Option<String>,
+    /// Regex of lines that continue the previous log entry.
+    #[knus(child, unwrap(argument), default)]
+    pub multiline: Option<String>,
+    /// TCP ports the service listens on, one `port` node each. Checked
+    /// before it starts so a conflict fails fast.
+    #[knus(children(name = "port"), unwrap(argument))]
+    pub ports: Vec<u16>,
 }
 
 impl Config {
+    /// Loads the config at `path` with the keybindings of the user-level
+    /// config under its own, and checks they do not conflict.
     pub fn load(path: &Path) -> miette::Result<Self> {
-        Self::load_internal(path).context("error loading config")
+        let mut config = Self::load_internal(path).context("error loading config")?;
+
+        let user = UserConfig::load()?;
+        config.keybindings = user.keybindings.merge(config.keybindings);
+        config
+            .keybindings
+            .keymap()
+            .map_err(|e| miette::miette!("{e}"))
+            .context("invalid keybindings")?;
+
+        Ok(config)
     }
 
     fn load_internal(path: &Path) -> miette::Result<Self> {
@@ -61,6 +138,17 @@ mod tests {
     fn parse() {
         let parsed = do_parse(
             r##"
+            http 4444
+            metrics 9464
+            mouse true
+            keybindings {
+                next-service "n" "down"
+                prev-service "e" "up"
+                errors "E"
+                scroll-page-down "ctrl-D"
+                copy-half-page-down "ctrl-f"
+                help
+            }
             service {
                 name "api"
                 cmd "pnpm dev"
@@ -68,10 +156,34 @@ mod tests {
             service {
                 name "web"
                 cmd "pnpm dev"
+                port 3000
+                port 24678
             }
             "##,
         );
 
         println!("{:?}", parsed);
+        assert_eq!(parsed.services[1].ports, [3000, 24678]);
+        assert_eq!(parsed.http, Some(4444));
+        assert_eq!(parsed.metrics, Some(9464));
+        assert_eq!(parsed.mouse, Some(true));
+
+        let keymap = parsed.keybindings.keymap().unwrap();
+        let key = |key: &str| keymap.action(Mode::Normal, key.parse().unwrap());
+        assert_eq!(key("n"), Some(Action::NextService));
+        assert_eq!(key("j"), None);
+        assert_eq!(key("ctrl-d"), Some(Action::ScrollPageDown));
+        assert_eq!(key("pagedown"), None);
+        assert_eq!(key("?"), None);
+        assert_eq!(key("shift-tab"), Some(Action::PrevPane));
+        assert_eq!(keymap.keys(Action::Follow)[0].to_string(), "G");
+
+        // `e` still opens the errors panel unless it is rebound too.
+        let conflict = do_parse(r#"keybindings { prev-service "e"; }"#);
+        assert_eq!(
+            conflict.keybindings.keymap().unwrap_err(),
+            "`e` is bound to both prev-service and errors"
+        );
+        assert!(Config::parse("test.kdl", "keybindings { jump \"j\" }").is_err());
     }
 }
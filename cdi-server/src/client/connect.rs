@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        UnixStream,
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    sync::mpsc,
+};
+
+use cdi_shared::event::ui::TuiEvent;
+
+use super::protocol::{ClientRequest, ClientResponse};
+use crate::{
+    server::MAX_LINES,
+    session::check_runtime_dir,
+    store::{StoreHandle, StoreManager},
+};
+
+/// Responses to a request answered with a stream, such as `FollowLogs`.
+/// The request is cancelled by dropping it.
+pub struct Responses {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    _writer: OwnedWriteHalf,
+}
+
+impl Responses {
+    /// The next response, `None` once the server closed the connection.
+    pub async fn next(&mut self) -> Result<Option<ClientResponse>> {
+        match self.lines.next_line().await? {
+            Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Sends `request` to the server listening on `socket_path`.
+pub async fn open(socket_path: &Path, request: &ClientRequest) -> Result<Responses> {
+    if let Some(dir) = socket_path.parent()
+        && dir.exists()
+    {
+        check_runtime_dir(dir)?;
+    }
+
+    let stream = UnixStream::connect(socket_path)
+        .await
+        .context("no cdi server is running for this config")?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    Ok(Responses {
+        lines: BufReader::new(reader).lines(),
+        _writer: writer,
+    })
+}
+
+/// Sends one request to the server listening on `socket_path`.
+pub async fn request(socket_path: &Path, request: &ClientRequest) -> Result<ClientResponse> {
+    open(socket_path, request)
+        .await?
+        .next()
+        .await?
+        .context("server closed the connection")
+}
+
+/// Connects to the server listening on `socket_path` and mirrors its store
+/// into a local one, kept up to date for as long as the server runs. Once
+/// the connection ends, `TuiEvent::Quit` is emitted.
+pub async fn attach(socket_path: &Path) -> Result<StoreHandle> {
+    let mut responses = open(socket_path, &ClientRequest::Attach).await?;
+    let first = responses
+        .next()
+        .await?
+        .context("server closed the connection")?;
+    let (session_id, processes) = match first {
+        ClientResponse::Attached {
+            session_id,
+            processes,
+        } => (session_id, processes),
+        ClientResponse::Error { message } => bail!(message),
+        response => bail!("unexpected response: {response:?}"),
+    };
+
+    let (store_manager, store) = StoreManager::new(processes, session_id, MAX_LINES, None);
+    let (events, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(store_manager.run(receiver));
+
+    tokio::spawn(async move {
+        while let Ok(Some(response)) = responses.next().await {
+            match response {
+                ClientResponse::Event(event) => {
+                    let _ = events.send(event);
+                }
+                ClientResponse::Error { message } => {
+                    eprintln!("Detached from server: {message}");
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        TuiEvent::Quit.emit();
+    });
+
+    Ok(store)
+}
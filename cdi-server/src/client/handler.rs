@@ -0,0 +1,302 @@
+use anyhow::Result;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter, Lines},
+    net::UnixStream,
+    select,
+    sync::{broadcast::error::RecvError, mpsc::Sender},
+};
+
+use cdi_shared::{
+    event::ui::TuiEvent,
+    log::{ProcessInfo, Stream},
+};
+
+use super::protocol::{ClientRequest, ClientResponse};
+use crate::{
+    StoreHandle,
+    server::{Message, ServerCommand},
+    signal,
+    store::{Followed, LogQuery},
+};
+
+/// Answers requests from one client until it disconnects. `commands` goes
+/// to the supervisor.
+pub async fn handle_client(
+    stream: UnixStream,
+    store: StoreHandle,
+    commands: Sender<Message>,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str(&line) {
+            Ok(ClientRequest::Attach) => return feed_replica(writer, &store).await,
+            Ok(ClientRequest::FollowLogs {
+                process_id,
+                after,
+                seen_lines,
+                stream,
+            }) => {
+                return follow_logs(
+                    lines,
+                    writer,
+                    &store,
+                    &process_id,
+                    after,
+                    seen_lines,
+                    stream,
+                )
+                .await;
+            }
+            Ok(request) => handle_request(request, &store, &commands).await,
+            Err(e) => ClientResponse::error(format!("invalid request: {e}")),
+        };
+
+        write_response(&mut writer, &response).await?;
+    }
+
+    Ok(())
+}
+
+async fn write_response(
+    writer: &mut (impl AsyncWrite + Unpin),
+    response: &ClientResponse,
+) -> Result<()> {
+    let mut json = serde_json::to_string(response)?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await?;
+
+    Ok(())
+}
+
+/// Streams new lines of a process until the client hangs up.
+async fn follow_logs(
+    mut lines: Lines<impl AsyncBufRead + Unpin>,
+    mut writer: impl AsyncWrite + Unpin,
+    store: &StoreHandle,
+    process_id: &str,
+    after: Option<u64>,
+    seen_lines: Option<usize>,
+    stream: Option<Stream>,
+) -> Result<()> {
+    let info = match find_process(store, process_id) {
+        Ok(info) => info,
+        Err(response) => return write_response(&mut writer, &response).await,
+    };
+    let mut follower = store.follow(info.id, after, seen_lines, stream);
+
+    loop {
+        select! {
+            followed = follower.next() => {
+                let response = match followed {
+                    Some(Followed::Lines(lines)) => ClientResponse::Logs(lines),
+                    Some(Followed::Lagged { missed }) => ClientResponse::Lagged { missed },
+                    None => return Ok(()),
+                };
+                write_response(&mut writer, &response).await?;
+            }
+            line = lines.next_line() => {
+                if !matches!(line, Ok(Some(_))) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Streams the store to an attached client until either side goes away. A
+/// client too slow to keep up is cut off rather than shown a gap.
+async fn feed_replica(writer: impl AsyncWrite + Unpin, store: &StoreHandle) -> Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let (processes, history, mut events) = store.replicate();
+
+    let attached = ClientResponse::Attached {
+        session_id: store.session_id(),
+        processes,
+    };
+    write_response(&mut writer, &attached).await?;
+    for event in history {
+        write_response(&mut writer, &ClientResponse::Event(event)).await?;
+    }
+    writer.flush().await?;
+
+    loop {
+        match events.recv().await {
+            Ok(event) => write_response(&mut writer, &ClientResponse::Event(event)).await?,
+            Err(RecvError::Lagged(_)) => {
+                let error = ClientResponse::error("client fell too far behind");
+                write_response(&mut writer, &error).await?;
+                writer.flush().await?;
+                return Ok(());
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        }
+
+        // Flush once the burst is written. Lagging shows on the next `recv`.
+        while let Ok(event) = events.try_recv() {
+            write_response(&mut writer, &ClientResponse::Event(event)).await?;
+        }
+        writer.flush().await?;
+    }
+}
+
+async fn handle_request(
+    request: ClientRequest,
+    store: &StoreHandle,
+    commands: &Sender<Message>,
+) -> ClientResponse {
+    match request {
+        ClientRequest::ListProcesses => ClientResponse::Processes(store.processes()),
+        ClientRequest::GetProcessStatus { process_id } => match find_process(store, &process_id) {
+            Ok(info) => ClientResponse::Status(info.status),
+            Err(response) => response,
+        },
+        ClientRequest::GetLogs {
+            process_id,
+            limit,
+            stream,
+            search,
+            level,
+            since,
+        } => {
+            let info = match find_process(store, &process_id) {
+                Ok(info) => info,
+                Err(response) => return response,
+            };
+            let Some(snapshot) = store.snapshot(info.id) else {
+                return ClientResponse::Logs(Vec::new());
+            };
+
+            let mut view = snapshot.query();
+            if let Some(stream) = stream {
+                view = view.stream(stream);
+            }
+            if let Some(search) = search {
+                view = view.search(&search);
+            }
+            if let Some(level) = level {
+                view = view.min_level(level);
+            }
+            if let Some(since) = since {
+                view = view.since(since);
+            }
+
+            let session_id = store.session_id();
+            let logs = view
+                .tail(limit)
+                .into_iter()
+                .map(|(meta, content)| meta.to_log_line(info.id, session_id, content))
+                .collect();
+
+            ClientResponse::Logs(logs)
+        }
+        ClientRequest::ExportLogs {
+            process_id,
+            format,
+            stream,
+            search,
+            level,
+            since,
+        } => {
+            let process_ids = match process_id {
+                Some(name) => match find_process(store, &name) {
+                    Ok(info) => vec![info.id],
+                    Err(response) => return response,
+                },
+                None => store.processes().iter().map(|info| info.id).collect(),
+            };
+
+            let mut query = LogQuery::new();
+            if let Some(stream) = stream {
+                query = query.stream(stream);
+            }
+            if let Some(search) = search {
+                query = query.search(&search);
+            }
+            if let Some(level) = level {
+                query = query.min_level(level);
+            }
+            if let Some(since) = since {
+                query = query.since(since);
+            }
+            let mut content = Vec::new();
+            match store.export(&process_ids, query, format, &mut content) {
+                Ok(entries) => ClientResponse::Exported {
+                    entries,
+                    content: String::from_utf8_lossy(&content).into_owned(),
+                },
+                Err(e) => ClientResponse::error(format!("export failed: {e}")),
+            }
+        }
+        ClientRequest::GetErrors { process_id } => {
+            let process_id = match process_id.map(|name| find_process(store, &name)) {
+                None => None,
+                Some(Ok(info)) => Some(info.id),
+                Some(Err(response)) => return response,
+            };
+
+            ClientResponse::Errors(store.errors(process_id))
+        }
+        ClientRequest::Signal {
+            process_id,
+            signal,
+            group,
+        } => {
+            let info = match find_process(store, &process_id) {
+                Ok(info) => info,
+                Err(response) => return response,
+            };
+            let Some(number) = signal::parse_signal(&signal) else {
+                return ClientResponse::error(format!("unknown signal '{signal}'"));
+            };
+
+            match signal::signal_service(store, info.id, number, group) {
+                Ok(()) => ClientResponse::Ok,
+                Err(e) => ClientResponse::error(format!("{}: {e}", info.name)),
+            }
+        }
+        ClientRequest::RestartProcess { process_id } => {
+            let info = match find_process(store, &process_id) {
+                Ok(info) => info,
+                Err(response) => return response,
+            };
+            let command = ServerCommand::Restart {
+                process_id: info.id,
+            };
+            send_command(commands, command).await
+        }
+        ClientRequest::StopProcess { process_id } => {
+            let info = match find_process(store, &process_id) {
+                Ok(info) => info,
+                Err(response) => return response,
+            };
+            let command = ServerCommand::Stop {
+                process_id: info.id,
+            };
+            send_command(commands, command).await
+        }
+        ClientRequest::Shutdown => {
+            // The TUI, or the detached server's main loop, stops everything.
+            TuiEvent::Quit.emit();
+            ClientResponse::Ok
+        }
+        ClientRequest::Attach | ClientRequest::FollowLogs { .. } => {
+            unreachable!("handled by handle_client")
+        }
+    }
+}
+
+async fn send_command(commands: &Sender<Message>, command: ServerCommand) -> ClientResponse {
+    match commands.send(Message::Command(command)).await {
+        Ok(()) => ClientResponse::Ok,
+        Err(_) => ClientResponse::error("cdi is shutting down"),
+    }
+}
+
+/// Looks a process up by service name, falling back to its numeric id.
+fn find_process(store: &StoreHandle, name: &str) -> Result<ProcessInfo, ClientResponse> {
+    store
+        .find_process(name)
+        .ok_or_else(|| ClientResponse::error(format!("no process named '{name}'")))
+}
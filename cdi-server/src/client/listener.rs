@@ -0,0 +1,62 @@
+use std::{fs, os::unix::net::UnixStream as StdUnixStream, path::PathBuf};
+
+use anyhow::{Result, bail};
+use tokio::{net::UnixListener, sync::mpsc::Sender};
+
+use super::handler::handle_client;
+use crate::{StoreHandle, server::Message, session::create_runtime_dir};
+
+/// Serves the client protocol on a Unix socket. The socket file is removed
+/// when the listener is dropped.
+pub struct ClientListener {
+    socket_path: PathBuf,
+}
+
+impl ClientListener {
+    /// `commands` goes to the supervisor, see `Connection::sender`.
+    pub fn start(
+        socket_path: PathBuf,
+        store: StoreHandle,
+        commands: Sender<Message>,
+    ) -> Result<Self> {
+        create_runtime_dir(&socket_path)?;
+
+        if socket_path.exists() {
+            if StdUnixStream::connect(&socket_path).is_ok() {
+                bail!(
+                    "another session is already listening on {}",
+                    socket_path.display()
+                );
+            }
+            // Left behind by a session that didn't shut down cleanly.
+            fs::remove_file(&socket_path)?;
+        }
+
+        let listener = UnixListener::bind(&socket_path)?;
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(handle_client(stream, store.clone(), commands.clone()));
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to accept client: {e}");
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self { socket_path })
+    }
+
+    pub fn socket_path(&self) -> &PathBuf {
+        &self.socket_path
+    }
+}
+
+impl Drop for ClientListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
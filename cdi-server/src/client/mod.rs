@@ -0,0 +1,17 @@
+use std::path::{Path, PathBuf};
+
+mod connect;
+mod handler;
+mod listener;
+mod protocol;
+
+pub use connect::{Responses, attach, open, request};
+pub use listener::ClientListener;
+pub use protocol::{ClientRequest, ClientResponse};
+
+use crate::session::runtime_file;
+
+/// Socket of the session started from `project_dir`, see `runtime_file`.
+pub fn socket_path(project_dir: &Path) -> PathBuf {
+    runtime_file(project_dir, "sock")
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use cdi_shared::{
+    detect::DetectedError,
+    event::store::StoreEvent,
+    log::{Level, LogLine, ProcessInfo, ProcessStatus, Stream},
+};
+
+use crate::store::ExportFormat;
+
+/// Requests from external clients. Sent as one JSON object per line;
+/// `process_id` takes a service name or a numeric process id.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ClientRequest {
+    ListProcesses,
+    GetProcessStatus {
+        process_id: String,
+    },
+    /// The last `limit` entries matching every filter given.
+    GetLogs {
+        process_id: String,
+        limit: usize,
+        stream: Option<Stream>,
+        /// Case-insensitive text the entry must contain.
+        #[serde(default)]
+        search: Option<String>,
+        /// Lowest level of the entries.
+        #[serde(default)]
+        level: Option<Level>,
+        /// Unix time in milliseconds the entries were read at or after.
+        #[serde(default)]
+        since: Option<u128>,
+    },
+    /// Every retained entry matching the filters, as a file in `format`.
+    /// Without a `process_id`, the output of all processes interleaved.
+    ExportLogs {
+        #[serde(default)]
+        process_id: Option<String>,
+        format: ExportFormat,
+        #[serde(default)]
+        stream: Option<Stream>,
+        #[serde(default)]
+        search: Option<String>,
+        #[serde(default)]
+        level: Option<Level>,
+        #[serde(default)]
+        since: Option<u128>,
+    },
+    /// Streams the process's lines after entry `after`, or from now on
+    /// when it is omitted, as `Logs` responses until the client hangs up.
+    /// Lines entry `after` gains beyond the `seen_lines` the client has
+    /// are sent too. `Lagged` reports entries evicted before they could be
+    /// sent.
+    FollowLogs {
+        process_id: String,
+        #[serde(default)]
+        after: Option<u64>,
+        #[serde(default)]
+        seen_lines: Option<usize>,
+        #[serde(default)]
+        stream: Option<Stream>,
+    },
+    /// Detected errors of one process, or of every process when
+    /// `process_id` is omitted.
+    GetErrors {
+        #[serde(default)]
+        process_id: Option<String>,
+    },
+    /// Sends a signal to the process's whole process group, or only to the
+    /// process cdi started when `group` is false. `signal` is a name such
+    /// as `HUP` or `SIGUSR1`, or a number.
+    Signal {
+        process_id: String,
+        signal: String,
+        group: bool,
+    },
+    /// Stops the process if it is running, then starts it again. Answered
+    /// once the restart is under way.
+    RestartProcess {
+        process_id: String,
+    },
+    /// Stops the process, leaving the others running.
+    StopProcess {
+        process_id: String,
+    },
+    /// Stops every service and the server.
+    Shutdown,
+    /// Turns the connection into a feed for a replica of the store:
+    /// `Attached`, then one `Event` per line for as long as it stays open.
+    Attach,
+}
+
+/// Responses to external clients, one JSON object per line.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ClientResponse {
+    Processes(Vec<ProcessInfo>),
+    Status(ProcessStatus),
+    Logs(Vec<LogLine>),
+    Errors(Vec<DetectedError>),
+    Exported {
+        entries: usize,
+        content: String,
+    },
+    Attached {
+        session_id: u64,
+        processes: Vec<ProcessInfo>,
+    },
+    Event(StoreEvent),
+    Lagged {
+        missed: u64,
+    },
+    Ok,
+    Error {
+        message: String,
+    },
+}
+
+impl ClientResponse {
+    pub fn error(message: impl Into<String>) -> Self {
+        ClientResponse::Error {
+            message: message.into(),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Running the server detached from the terminal, so services outlive it.
+
+use std::{
+    env,
+    fs::OpenOptions,
+    io,
+    os::{
+        fd::AsRawFd,
+        unix::{net::UnixStream, process::CommandExt},
+    },
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    client::socket_path,
+    session::{SessionLock, create_runtime_dir, runtime_file},
+};
+
+/// How long a detached server gets to start listening.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where a detached server started from `project_dir` writes its own
+/// diagnostics. Service output goes to the store as usual.
+pub fn log_path(project_dir: &Path) -> PathBuf {
+    runtime_file(project_dir, "log")
+}
+
+/// Runs this executable with `args` in a new session, detached from the
+/// terminal, and waits until it listens on the client socket. Returns its
+/// pid.
+///
+/// The child is handed `lock` as `--session-fd`, so the session stays
+/// locked while it starts and no other start can slip in between.
+pub fn spawn(project_dir: &Path, args: &[&str], lock: &SessionLock) -> Result<u32> {
+    let log_path = log_path(project_dir);
+    create_runtime_dir(&log_path)?;
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("can't open {}", log_path.display()))?;
+
+    let fd = lock.as_raw_fd();
+    let mut command = Command::new(env::current_exe()?);
+    command
+        .args(args)
+        .args(["--session-fd", &fd.to_string()])
+        .current_dir(project_dir)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    unsafe {
+        command.pre_exec(move || {
+            libc::setsid();
+            // Only in the child, the lock stays close-on-exec here.
+            if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command.spawn()?;
+
+    let socket_path = socket_path(project_dir);
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    while Instant::now() < deadline {
+        if UnixStream::connect(&socket_path).is_ok() {
+            return Ok(child.id());
+        }
+        if let Some(status) = child.try_wait()? {
+            bail!(
+                "server exited with {status}, see {} for details",
+                log_path.display()
+            );
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    bail!(
+        "server didn't start listening in time, see {} for details",
+        log_path.display()
+    )
+}
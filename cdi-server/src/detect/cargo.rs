@@ -0,0 +1,146 @@
+use std::sync::LazyLock;
+
+use cdi_shared::detect::ErrorSeverity;
+use regex::Regex;
+
+use super::{Detection, Diagnostic, ErrorDetector, Update, location};
+
+static HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(error|warning)(?:\[\w+\])?: (.+)$").unwrap());
+static ARROW: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*--> (.+?):(\d+):(\d+)$").unwrap());
+/// `thread 'main' panicked at src/main.rs:2:5:`, message on the next line.
+static PANIC: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^thread '.*' panicked at (.+?):(\d+):(\d+):$").unwrap());
+/// Before Rust 1.73: `thread 'main' panicked at 'boom', src/main.rs:2:5`.
+static OLD_PANIC: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^thread '.*' panicked at '(.*)', (.+?):(\d+):(\d+)$").unwrap());
+/// Build summaries that repeat what the diagnostics already said.
+static SUMMARY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(could not compile|aborting due to|build failed|`.+` \(.+\) generated \d+ warnings?)",
+    )
+    .unwrap()
+});
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Compiler,
+    /// Panic header seen, message on the next line.
+    PanicMessage,
+    Panic,
+}
+
+/// rustc diagnostics, as printed by cargo, and runtime panics.
+#[derive(Default)]
+pub struct CargoErrorDetector {
+    state: State,
+    located: bool,
+}
+
+impl CargoErrorDetector {
+    fn start(&mut self, line: &str) -> Detection {
+        if let Some(caps) = HEADER.captures(line) {
+            if SUMMARY.is_match(&caps[2]) {
+                return Detection::Ignore;
+            }
+
+            let severity = match &caps[1] {
+                "error" => ErrorSeverity::Error,
+                _ => ErrorSeverity::Warning,
+            };
+            self.state = State::Compiler;
+            self.located = false;
+
+            return Detection::Start(Diagnostic {
+                severity,
+                message: caps[2].to_string(),
+                location: None,
+            });
+        }
+
+        if let Some(caps) = PANIC.captures(line) {
+            self.state = State::PanicMessage;
+            return Detection::Start(Diagnostic {
+                severity: ErrorSeverity::Error,
+                message: "panicked".to_string(),
+                location: location(&caps[1], &caps[2], Some(&caps[3])),
+            });
+        }
+
+        if let Some(caps) = OLD_PANIC.captures(line) {
+            self.state = State::Panic;
+            return Detection::Start(Diagnostic {
+                severity: ErrorSeverity::Error,
+                message: caps[1].to_string(),
+                location: location(&caps[2], &caps[3], Some(&caps[4])),
+            });
+        }
+
+        Detection::None
+    }
+
+    fn next(&mut self, line: &str) -> Detection {
+        match self.state {
+            State::Compiler => {
+                // rustc ends each diagnostic with a blank line.
+                if line.trim().is_empty() {
+                    return Detection::None;
+                }
+
+                if !self.located
+                    && let Some(caps) = ARROW.captures(line)
+                {
+                    self.located = true;
+                    return Detection::Continue(Update {
+                        location: location(&caps[1], &caps[2], Some(&caps[3])),
+                        ..Default::default()
+                    });
+                }
+
+                let continues = line.starts_with([' ', '|', '='])
+                    || line.starts_with("help:")
+                    || line.starts_with("note:")
+                    || line.split_once(" |").is_some_and(|(number, _)| {
+                        number.trim().bytes().all(|b| b.is_ascii_digit())
+                    });
+                if continues {
+                    Detection::Continue(Update::default())
+                } else {
+                    Detection::None
+                }
+            }
+            State::PanicMessage => {
+                self.state = State::Panic;
+                Detection::Continue(Update {
+                    message: Some(line.to_string()),
+                    ..Default::default()
+                })
+            }
+            State::Panic => {
+                let continues = line.starts_with(' ')
+                    || line.starts_with("note:")
+                    || line.starts_with("stack backtrace:");
+                if continues {
+                    Detection::Continue(Update::default())
+                } else {
+                    Detection::None
+                }
+            }
+        }
+    }
+}
+
+impl ErrorDetector for CargoErrorDetector {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn detect(&mut self, line: &str, active: bool) -> Detection {
+        match self.start(line) {
+            Detection::None if active => self.next(line),
+            detection => detection,
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::LazyLock;
+
+use cdi_shared::detect::{ErrorSeverity, Location};
+use regex::Regex;
+
+use super::{Detection, Diagnostic, ErrorDetector};
+
+static FILE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:[A-Za-z]:)?[\w./@~+\-\\]+\.(?:js|jsx|ts|tsx|mjs|cjs|vue|svelte|astro)$")
+        .unwrap()
+});
+static PROBLEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s+(\d+):(\d+)\s+(error|warning)\s+(.+?)(?:\s{2,}(\S+))?$").unwrap()
+});
+
+/// ESLint's default `stylish` output: a file path followed by one indented
+/// problem per line. Each problem is its own diagnostic.
+#[derive(Default)]
+pub struct EslintErrorDetector {
+    file: Option<String>,
+}
+
+impl ErrorDetector for EslintErrorDetector {
+    fn name(&self) -> &'static str {
+        "eslint"
+    }
+
+    fn detect(&mut self, line: &str, _active: bool) -> Detection {
+        if FILE.is_match(line) {
+            self.file = Some(line.to_string());
+            return Detection::None;
+        }
+
+        let Some(file) = &self.file else {
+            return Detection::None;
+        };
+        let Some(caps) = PROBLEM.captures(line) else {
+            if !line.trim().is_empty() {
+                self.file = None;
+            }
+            return Detection::None;
+        };
+
+        let severity = match &caps[3] {
+            "error" => ErrorSeverity::Error,
+            _ => ErrorSeverity::Warning,
+        };
+        let message = match caps.get(5) {
+            Some(rule) => format!("{} ({})", &caps[4], rule.as_str()),
+            None => caps[4].to_string(),
+        };
+
+        Detection::Start(Diagnostic {
+            severity,
+            message,
+            location: Some(Location {
+                file: file.clone(),
+                line: caps[1].parse().unwrap_or(0),
+                column: caps[2].parse().ok(),
+            }),
+        })
+    }
+}
@@ -0,0 +1,34 @@
+use cdi_shared::{
+    detect::ErrorSeverity,
+    log::{Level, Stream},
+};
+
+use super::{Detection, Diagnostic, ErrorDetector, find_locations};
+use crate::store::level;
+
+/// Any single line explicitly logged at error level, for output none of the
+/// tool-specific detectors know.
+pub struct GenericErrorDetector;
+
+impl ErrorDetector for GenericErrorDetector {
+    fn name(&self) -> &'static str {
+        "generic"
+    }
+
+    fn detect(&mut self, line: &str, _active: bool) -> Detection {
+        // Treated as stdout so only explicit markers count, not any stderr
+        // line that mentions "failed".
+        if level::detect(line, &Stream::Stdout) != Some(Level::Error) {
+            return Detection::None;
+        }
+
+        Detection::Start(Diagnostic {
+            severity: ErrorSeverity::Error,
+            message: line.trim().to_string(),
+            location: find_locations(line)
+                .into_iter()
+                .next()
+                .map(|(_, location)| location),
+        })
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::LazyLock;
+
+use cdi_shared::detect::ErrorSeverity;
+use regex::Regex;
+
+use super::{Detection, Diagnostic, ErrorDetector, Update, location};
+
+/// `./main.go:12:5: undefined: foo` from `go build` and `go vet`.
+static COMPILE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\S+\.go):(\d+):(\d+): (.+)$").unwrap());
+static PANIC: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:panic|fatal error): (.+)$").unwrap());
+/// Stack frame location: `\t/app/main.go:8 +0x1d`.
+static FRAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\t(\S+\.go):(\d+)(?: \+0x[0-9a-f]+)?$").unwrap());
+
+/// Go compiler errors and panics with their goroutine dumps.
+#[derive(Default)]
+pub struct GoErrorDetector {
+    panic: bool,
+    located: bool,
+}
+
+impl ErrorDetector for GoErrorDetector {
+    fn name(&self) -> &'static str {
+        "go"
+    }
+
+    fn detect(&mut self, line: &str, active: bool) -> Detection {
+        if let Some(caps) = COMPILE.captures(line) {
+            self.panic = false;
+            return Detection::Start(Diagnostic {
+                severity: ErrorSeverity::Error,
+                message: caps[4].to_string(),
+                location: location(&caps[1], &caps[2], Some(&caps[3])),
+            });
+        }
+
+        if let Some(caps) = PANIC.captures(line) {
+            self.panic = true;
+            self.located = false;
+            return Detection::Start(Diagnostic {
+                severity: ErrorSeverity::Error,
+                message: caps[1].to_string(),
+                location: None,
+            });
+        }
+
+        if !active || !self.panic {
+            return Detection::None;
+        }
+
+        // The innermost frame outside the runtime is where the panic happened.
+        if let Some(caps) = FRAME.captures(line) {
+            if self.located || caps[1].contains("/src/runtime/") {
+                return Detection::Continue(Update::default());
+            }
+
+            self.located = true;
+            return Detection::Continue(Update {
+                location: location(&caps[1], &caps[2], None),
+                ..Default::default()
+            });
+        }
+
+        let continues = line.trim().is_empty()
+            || line.starts_with("goroutine ")
+            || line.starts_with("[signal ")
+            || line.starts_with('\t')
+            || line.ends_with(')');
+        if continues {
+            Detection::Continue(Update::default())
+        } else {
+            Detection::None
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::sync::LazyLock;
+
+use cdi_shared::detect::{ErrorSeverity, Location};
+use regex::Regex;
+
+mod cargo;
+mod eslint;
+mod generic;
+mod go;
+mod node;
+mod python;
+mod tsc;
+
+pub use cargo::CargoErrorDetector;
+pub use eslint::EslintErrorDetector;
+pub use generic::GenericErrorDetector;
+pub use go::GoErrorDetector;
+pub use node::NodeErrorDetector;
+pub use python::PythonErrorDetector;
+pub use tsc::TscErrorDetector;
+
+/// Start of a diagnostic, before the store gives it ids.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: ErrorSeverity,
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+/// Changes a continuation line makes to the diagnostic it belongs to.
+#[derive(Debug, Default)]
+pub struct Update {
+    pub message: Option<String>,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug)]
+pub enum Detection {
+    None,
+    /// The detector's own output that isn't a diagnostic, such as a build
+    /// summary. Other detectors don't see the line.
+    Ignore,
+    /// The line starts a new diagnostic.
+    Start(Diagnostic),
+    /// The line belongs to the diagnostic this detector started last.
+    Continue(Update),
+}
+
+/// Recognises one tool's error output.
+///
+/// Detectors see ANSI-stripped lines one at a time. `active` is set while the
+/// previous line belonged to a diagnostic this detector started, which is the
+/// only time it may return `Continue`.
+pub trait ErrorDetector: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn detect(&mut self, line: &str, active: bool) -> Detection;
+}
+
+/// The detectors run over one output stream, with the one currently inside
+/// a multi-line diagnostic getting the first look at each line.
+pub struct DetectorSet {
+    detectors: Vec<Box<dyn ErrorDetector>>,
+    active: Option<usize>,
+}
+
+impl DetectorSet {
+    pub fn new() -> Self {
+        // Specific formats first, the level-based fallback last.
+        Self {
+            detectors: vec![
+                Box::new(CargoErrorDetector::default()),
+                Box::new(TscErrorDetector),
+                Box::new(EslintErrorDetector::default()),
+                Box::new(GoErrorDetector::default()),
+                Box::new(PythonErrorDetector::default()),
+                Box::new(NodeErrorDetector::default()),
+                Box::new(GenericErrorDetector),
+            ],
+            active: None,
+        }
+    }
+
+    /// Feeds one line, returning the detection and the detector's name.
+    pub fn feed(&mut self, line: &str) -> (Detection, &'static str) {
+        if let Some(idx) = self.active.take() {
+            let detector = &mut self.detectors[idx];
+            match detector.detect(line, true) {
+                Detection::None => {}
+                detection @ Detection::Ignore => return (detection, detector.name()),
+                detection => {
+                    self.active = Some(idx);
+                    return (detection, detector.name());
+                }
+            }
+        }
+
+        for (idx, detector) in self.detectors.iter_mut().enumerate() {
+            match detector.detect(line, false) {
+                detection @ Detection::Start(_) => {
+                    self.active = Some(idx);
+                    return (detection, detector.name());
+                }
+                detection @ Detection::Ignore => return (detection, detector.name()),
+                _ => {}
+            }
+        }
+
+        (Detection::None, "")
+    }
+}
+
+impl Default for DetectorSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Source extensions accepted in references without a `/`, so `main.rs:12`
+/// counts but `localhost:3000` or `v1.2:3` don't.
+const SOURCE_EXTENSIONS: [&str; 30] = [
+    "rs", "ts", "tsx", "js", "jsx", "mjs", "cjs", "go", "py", "rb", "java", "kt", "c", "h", "cc",
+    "cpp", "hpp", "cs", "swift", "php", "ex", "exs", "vue", "svelte", "json", "toml", "yaml",
+    "yml", "css", "scss",
+];
+
+static LOCATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:file://)?((?:[A-Za-z]:)?[\w./@~+\-\\]*\.([A-Za-z]{1,6}))(?::(\d+)(?::(\d+))?|\((\d+),(\d+)\))")
+        .unwrap()
+});
+
+/// `file:line[:col]` and `file(line,col)` references in a line, with their
+/// byte ranges.
+pub fn find_locations(text: &str) -> Vec<(std::ops::Range<usize>, Location)> {
+    LOCATION
+        .captures_iter(text)
+        .filter_map(|caps| {
+            let whole = caps.get(0)?;
+            let file = caps.get(1)?.as_str();
+            let extension = caps.get(2)?.as_str();
+            if !file.contains('/') && !SOURCE_EXTENSIONS.contains(&extension) {
+                return None;
+            }
+            // Part of a URL rather than a path.
+            if text[..whole.start()].ends_with("://") {
+                return None;
+            }
+
+            let line = caps.get(3).or(caps.get(5))?.as_str().parse().ok()?;
+            let column = caps
+                .get(4)
+                .or(caps.get(6))
+                .and_then(|column| column.as_str().parse().ok());
+
+            Some((
+                whole.range(),
+                Location {
+                    file: file.to_string(),
+                    line,
+                    column,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn location(file: &str, line: &str, column: Option<&str>) -> Option<Location> {
+    Some(Location {
+        file: file.to_string(),
+        line: line.parse().ok()?,
+        column: column.and_then(|column| column.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(output: &str) -> Vec<(&'static str, Diagnostic, usize)> {
+        let mut set = DetectorSet::new();
+        let mut found: Vec<(&'static str, Diagnostic, usize)> = Vec::new();
+
+        for line in output.lines() {
+            match set.feed(line) {
+                (Detection::Start(diagnostic), name) => found.push((name, diagnostic, 1)),
+                (Detection::Continue(update), _) => {
+                    let (_, diagnostic, lines) = found.last_mut().unwrap();
+                    *lines += 1;
+                    if let Some(message) = update.message {
+                        diagnostic.message = message;
+                    }
+                    if update.location.is_some() {
+                        diagnostic.location = update.location;
+                    }
+                }
+                (Detection::None | Detection::Ignore, _) => {}
+            }
+        }
+
+        found
+    }
+
+    fn at(diagnostic: &Diagnostic) -> String {
+        diagnostic.location.as_ref().unwrap().to_string()
+    }
+
+    #[test]
+    fn groups_multi_line_diagnostics() {
+        let found = run("   Compiling app v0.1.0
+error[E0308]: mismatched types
+ --> src/main.rs:4:18
+  |
+4 |     let x: u32 = \"a\";
+  |            ---   ^^^ expected `u32`, found `&str`
+  |
+error: could not compile `app` (bin \"app\") due to 1 previous error
+Traceback (most recent call last):
+  File \"/app/main.py\", line 10, in <module>
+    main()
+  File \"/app/main.py\", line 6, in main
+    raise ValueError(\"bad\")
+ValueError: bad
+/app/index.js:3
+TypeError: Cannot read properties of undefined (reading 'x')
+    at f (node:internal/modules/cjs/loader:1:1)
+    at Object.<anonymous> (/app/index.js:3:13)
+listening on :3000
+panic: runtime error: index out of range [5] with length 3
+
+goroutine 1 [running]:
+main.main()
+\t/app/main.go:8 +0x1d
+exit status 2
+src/index.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.
+/app/src/a.js
+  1:10  error  'foo' is defined but never used  no-unused-vars
+  2:1   warning  Unexpected console statement  no-console
+2024-01-01T00:00:00Z ERROR db: connection refused
+");
+
+        let summary: Vec<_> = found
+            .iter()
+            .map(|(name, diagnostic, lines)| (*name, diagnostic.message.as_str(), *lines))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("cargo", "mismatched types", 6),
+                ("python", "ValueError: bad", 6),
+                (
+                    "node",
+                    "TypeError: Cannot read properties of undefined (reading 'x')",
+                    3
+                ),
+                (
+                    "go",
+                    "runtime error: index out of range [5] with length 3",
+                    5
+                ),
+                (
+                    "tsc",
+                    "Type 'string' is not assignable to type 'number'.",
+                    1
+                ),
+                (
+                    "eslint",
+                    "'foo' is defined but never used (no-unused-vars)",
+                    1
+                ),
+                ("eslint", "Unexpected console statement (no-console)", 1),
+                (
+                    "generic",
+                    "2024-01-01T00:00:00Z ERROR db: connection refused",
+                    1
+                ),
+            ]
+        );
+
+        assert_eq!(at(&found[0].1), "src/main.rs:4:18");
+        assert_eq!(at(&found[1].1), "/app/main.py:6");
+        assert_eq!(at(&found[2].1), "/app/index.js:3:13");
+        assert_eq!(at(&found[3].1), "/app/main.go:8");
+        assert_eq!(at(&found[4].1), "src/index.ts:3:7");
+        assert_eq!(at(&found[5].1), "/app/src/a.js:1:10");
+        assert_eq!(found[6].1.severity, ErrorSeverity::Warning);
+    }
+
+    #[test]
+    fn finds_locations() {
+        let found: Vec<String> = find_locations(
+            "at src/app.ts:12:5, lib/a.py:3 and main.rs:7 not http://localhost:3000 or v1.2:3",
+        )
+        .into_iter()
+        .map(|(_, location)| location.to_string())
+        .collect();
+
+        assert_eq!(found, ["src/app.ts:12:5", "lib/a.py:3", "main.rs:7"]);
+    }
+}
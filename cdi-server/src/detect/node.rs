@@ -0,0 +1,59 @@
+use std::sync::LazyLock;
+
+use cdi_shared::detect::ErrorSeverity;
+use regex::Regex;
+
+use super::{Detection, Diagnostic, ErrorDetector, Update, location};
+
+/// `TypeError: ...`, `Error [ERR_MODULE_NOT_FOUND]: ...`, `Uncaught Error`.
+static HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:Uncaught )?[A-Z]?\w*(?:Error|Exception)(?: \[\w+\])?(?::.*)?$").unwrap()
+});
+/// `    at fn (/app/index.js:3:13)` or `    at /app/index.js:3:13`.
+static FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s+at (?:.*?\()?(?:file://)?([^\s()]+):(\d+):(\d+)\)?$").unwrap()
+});
+
+/// Node.js errors with their `at ...` stack frames.
+#[derive(Default)]
+pub struct NodeErrorDetector {
+    located: bool,
+}
+
+impl ErrorDetector for NodeErrorDetector {
+    fn name(&self) -> &'static str {
+        "node"
+    }
+
+    fn detect(&mut self, line: &str, active: bool) -> Detection {
+        if !active || !line.starts_with([' ', '\t', '{', '}']) {
+            if !HEADER.is_match(line) {
+                return Detection::None;
+            }
+
+            self.located = false;
+            return Detection::Start(Diagnostic {
+                severity: ErrorSeverity::Error,
+                message: line.trim_start_matches("Uncaught ").to_string(),
+                location: None,
+            });
+        }
+
+        // The first frame in the app's own code.
+        if !self.located
+            && let Some(caps) = FRAME.captures(line)
+        {
+            let file = &caps[1];
+            if !file.starts_with("node:") && !file.contains("node_modules") {
+                self.located = true;
+                return Detection::Continue(Update {
+                    location: location(file, &caps[2], Some(&caps[3])),
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Frames, and the `{ code: ... }` properties printed after them.
+        Detection::Continue(Update::default())
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::LazyLock;
+
+use cdi_shared::detect::ErrorSeverity;
+use regex::Regex;
+
+use super::{Detection, Diagnostic, ErrorDetector, Update, location};
+
+const HEADER: &str = "Traceback (most recent call last):";
+
+static FRAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^\s+File "(.+?)", line (\d+)"#).unwrap());
+/// The closing `ValueError: bad` line.
+static EXCEPTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^[A-Za-z_][\w.]*(?:Error|Exception|Warning|Exit|Interrupt|Iteration)(?::.*)?$")
+        .unwrap()
+});
+
+/// Python tracebacks, including chained ones.
+#[derive(Default)]
+pub struct PythonErrorDetector {
+    /// A frame outside site-packages has been seen.
+    in_project: bool,
+}
+
+impl ErrorDetector for PythonErrorDetector {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+
+    fn detect(&mut self, line: &str, active: bool) -> Detection {
+        if !active {
+            if line.trim_end() != HEADER {
+                return Detection::None;
+            }
+
+            self.in_project = false;
+            return Detection::Start(Diagnostic {
+                severity: ErrorSeverity::Error,
+                message: "Traceback".to_string(),
+                location: None,
+            });
+        }
+
+        // Frames run outermost first, so the last one in project code wins.
+        if let Some(caps) = FRAME.captures(line) {
+            let file = &caps[1];
+            let library = file.contains("site-packages") || file.contains("/lib/python");
+            if library && self.in_project {
+                return Detection::Continue(Update::default());
+            }
+
+            self.in_project |= !library;
+            return Detection::Continue(Update {
+                location: location(file, &caps[2], None),
+                ..Default::default()
+            });
+        }
+
+        if EXCEPTION.is_match(line) {
+            return Detection::Continue(Update {
+                message: Some(line.trim_end().to_string()),
+                ..Default::default()
+            });
+        }
+
+        let continues = line.trim().is_empty()
+            || line.starts_with(' ')
+            || line.trim_end() == HEADER
+            || line.starts_with("During handling of the above exception")
+            || line.starts_with("The above exception was the direct cause");
+        if continues {
+            Detection::Continue(Update::default())
+        } else {
+            Detection::None
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::LazyLock;
+
+use cdi_shared::detect::ErrorSeverity;
+use regex::Regex;
+
+use super::{Detection, Diagnostic, ErrorDetector, Update, location};
+
+/// `src/a.ts(3,7): error TS2322: ...` (plain) and
+/// `src/a.ts:3:7 - error TS2322: ...` (`--pretty`).
+static HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(.+?)(?:\((\d+),(\d+)\): |:(\d+):(\d+) - )(error|warning) TS\d+: (.+)$").unwrap()
+});
+
+/// TypeScript compiler diagnostics, with the code frame `--pretty` prints
+/// below them.
+pub struct TscErrorDetector;
+
+impl ErrorDetector for TscErrorDetector {
+    fn name(&self) -> &'static str {
+        "tsc"
+    }
+
+    fn detect(&mut self, line: &str, active: bool) -> Detection {
+        if let Some(caps) = HEADER.captures(line) {
+            let (line_no, column) = match (caps.get(2), caps.get(4)) {
+                (Some(line_no), _) => (line_no, caps.get(3)),
+                (None, Some(line_no)) => (line_no, caps.get(5)),
+                (None, None) => return Detection::None,
+            };
+            let severity = match &caps[6] {
+                "error" => ErrorSeverity::Error,
+                _ => ErrorSeverity::Warning,
+            };
+
+            return Detection::Start(Diagnostic {
+                severity,
+                message: caps[7].to_string(),
+                location: location(
+                    &caps[1],
+                    line_no.as_str(),
+                    column.map(|column| column.as_str()),
+                ),
+            });
+        }
+
+        // Code frame: blank lines, `3 const x: number = "a";`, `  ~~~`.
+        let frame = line.trim().is_empty()
+            || line.starts_with(' ')
+            || line
+                .split_once(' ')
+                .is_some_and(|(number, _)| number.bytes().all(|b| b.is_ascii_digit()));
+        if active && frame {
+            Detection::Continue(Update::default())
+        } else {
+            Detection::None
+        }
+    }
+}
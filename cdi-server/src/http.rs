@@ -0,0 +1,487 @@
+use std::{
+    collections::HashSet,
+    net::{Ipv4Addr, SocketAddr, TcpListener as StdTcpListener},
+};
+
+use anyhow::Result;
+use axum::{
+    Json, Router,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, HeaderName, Method, StatusCode, header},
+    middleware::{self, Next},
+    response::{
+        Html, IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{get, post},
+};
+use cdi_shared::{
+    ansi,
+    event::store::StoreEvent,
+    log::{LogLine, ProcessInfo, Stream},
+};
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast::error::RecvError, mpsc::Sender},
+    task::JoinHandle,
+};
+
+use crate::{
+    StoreHandle, metrics,
+    server::{Message, ServerCommand},
+    store::Followed,
+};
+
+/// Lines returned by `/processes/{name}/logs` without a `limit`.
+const DEFAULT_LIMIT: usize = 100;
+
+/// The HTTP API's socket, bound on 127.0.0.1 before the services start so
+/// a taken port fails fast. `start` serves it.
+pub struct HttpListener {
+    listener: StdTcpListener,
+}
+
+/// Serves the HTTP API until dropped:
+///
+/// - `GET /`: the web UI
+/// - `GET /processes`: every process, in config order
+/// - `GET /processes/{name}/logs?limit=&after_id=&stream=&search=`: the last
+///   `limit` lines, or the first `limit` after entry `after_id`
+/// - `GET /events?process=`: Server-Sent Events, a `log` event per new line
+///   and a `status` event with the process when it starts or exits, of one
+///   process or all of them. `lagged` reports lines evicted before they
+///   could be sent.
+/// - `POST /processes/{name}/restart` and `/stop`
+/// - `GET /metrics`: Prometheus metrics, see `metrics::render`
+///
+/// Requests must name the server as `127.0.0.1:<port>` or `localhost:<port>`
+/// and come from no other origin, and POSTs must carry `X-Cdi: 1`, see
+/// `check_request`.
+///
+/// `{name}` takes a service name or a numeric process id. With
+/// `format=html`, lines are sent as HTML with their ANSI colors converted,
+/// see `ansi::to_html`.
+pub struct HttpServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl HttpListener {
+    /// Port 0 picks a free port.
+    pub fn bind(port: u16) -> Result<Self> {
+        let listener = StdTcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self { listener })
+    }
+
+    /// `commands` goes to the supervisor, see `Connection::sender`.
+    pub fn start(self, store: StoreHandle, commands: Sender<Message>) -> Result<HttpServer> {
+        let port = self.listener.local_addr()?.port();
+        self.serve(router(Api { store, commands }, port))
+    }
+
+    /// Serves only `GET /metrics`, for scrapers that shouldn't get to
+    /// stop services.
+    pub fn start_metrics(self, store: StoreHandle) -> Result<HttpServer> {
+        let app = Router::new()
+            .route("/metrics", get(metrics))
+            .with_state(store);
+        self.serve(app)
+    }
+
+    fn serve(self, app: Router) -> Result<HttpServer> {
+        let listener = TcpListener::from_std(self.listener)?;
+        let addr = listener.local_addr()?;
+
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                eprintln!("HTTP server on {addr} stopped: {e}");
+            }
+        });
+
+        Ok(HttpServer { addr, task })
+    }
+}
+
+impl HttpServer {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Clone)]
+struct Api {
+    store: StoreHandle,
+    commands: Sender<Message>,
+}
+
+impl Api {
+    fn find_process(&self, name: &str) -> Result<ProcessInfo, ApiError> {
+        self.store
+            .find_process(name)
+            .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("no process named '{name}'")))
+    }
+
+    async fn send(&self, command: ServerCommand) -> Result<StatusCode, ApiError> {
+        self.commands
+            .send(Message::Command(command))
+            .await
+            .map(|_| StatusCode::ACCEPTED)
+            .map_err(|_| {
+                ApiError(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "cdi is shutting down".to_string(),
+                )
+            })
+    }
+}
+
+/// Sent as `{"error": message}`.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+fn router(api: Api, port: u16) -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/processes", get(processes))
+        .route("/processes/{name}/logs", get(logs))
+        .route("/processes/{name}/restart", post(restart))
+        .route("/processes/{name}/stop", post(stop))
+        .route("/events", get(events))
+        .route("/metrics", get(api_metrics))
+        .layer(middleware::from_fn_with_state(port, check_request))
+        .with_state(api)
+}
+
+/// Keeps web pages out of the API. A foreign `Host` means a name rebound
+/// to 127.0.0.1, a foreign `Origin` a cross-site request, and `X-Cdi` can't
+/// be set by a form or a plain cross-origin `fetch`, so a page that got
+/// past the other checks still can't stop services.
+async fn check_request(State(port): State<u16>, request: Request, next: Next) -> Response {
+    if let Err(message) = allowed(request.method(), request.headers(), port) {
+        return ApiError(StatusCode::FORBIDDEN, message.to_string()).into_response();
+    }
+
+    next.run(request).await
+}
+
+fn allowed(method: &Method, headers: &HeaderMap, port: u16) -> Result<(), &'static str> {
+    let local =
+        |host: &str| host == format!("127.0.0.1:{port}") || host == format!("localhost:{port}");
+    let header = |name| headers.get(name).map(|value| value.to_str().unwrap_or(""));
+
+    if !header(header::HOST).is_some_and(local) {
+        return Err("unexpected Host");
+    }
+    if let Some(origin) = header(header::ORIGIN)
+        && !origin.strip_prefix("http://").is_some_and(local)
+    {
+        return Err("cross-origin requests are not allowed");
+    }
+    if method == Method::POST && header(HeaderName::from_static("x-cdi")) != Some("1") {
+        return Err("missing X-Cdi header");
+    }
+
+    Ok(())
+}
+
+/// How line content is sent.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    /// As printed, escape sequences included.
+    #[default]
+    Text,
+    Html,
+}
+
+impl Format {
+    fn convert(self, mut line: LogLine) -> LogLine {
+        if self == Format::Html {
+            line.content = ansi::to_html(&line.content);
+        }
+        line
+    }
+}
+
+async fn index() -> Html<&'static str> {
+    Html(include_str!("web/index.html"))
+}
+
+async fn metrics(State(store): State<StoreHandle>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, metrics::CONTENT_TYPE)],
+        metrics::render(&store),
+    )
+}
+
+async fn api_metrics(State(api): State<Api>) -> impl IntoResponse {
+    metrics(State(api.store)).await
+}
+
+async fn processes(State(api): State<Api>) -> Json<Vec<ProcessInfo>> {
+    Json(api.store.processes())
+}
+
+#[derive(Deserialize)]
+struct LogParams {
+    limit: Option<usize>,
+    after_id: Option<u64>,
+    stream: Option<Stream>,
+    /// Case-insensitive text the lines must contain.
+    search: Option<String>,
+    #[serde(default)]
+    format: Format,
+}
+
+async fn logs(
+    State(api): State<Api>,
+    Path(name): Path<String>,
+    Query(params): Query<LogParams>,
+) -> Result<Json<Vec<LogLine>>, ApiError> {
+    let info = api.find_process(&name)?;
+    let Some(snapshot) = api.store.snapshot(info.id) else {
+        return Ok(Json(Vec::new()));
+    };
+
+    let mut view = snapshot.query();
+    if let Some(stream) = params.stream {
+        view = view.stream(stream);
+    }
+    if let Some(search) = params.search.filter(|search| !search.is_empty()) {
+        view = view.search(&search);
+    }
+
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    let lines = match params.after_id {
+        Some(after_id) => view.after(after_id).limit(limit).iter().collect(),
+        None => view.tail(limit),
+    };
+
+    let session_id = api.store.session_id();
+    let lines = lines
+        .into_iter()
+        .map(|(meta, content)| {
+            params
+                .format
+                .convert(meta.to_log_line(info.id, session_id, content))
+        })
+        .collect();
+
+    Ok(Json(lines))
+}
+
+async fn restart(State(api): State<Api>, Path(name): Path<String>) -> Result<StatusCode, ApiError> {
+    let info = api.find_process(&name)?;
+    api.send(ServerCommand::Restart {
+        process_id: info.id,
+    })
+    .await
+}
+
+async fn stop(State(api): State<Api>, Path(name): Path<String>) -> Result<StatusCode, ApiError> {
+    let info = api.find_process(&name)?;
+    api.send(ServerCommand::Stop {
+        process_id: info.id,
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct EventParams {
+    process: Option<String>,
+    #[serde(default)]
+    format: Format,
+}
+
+async fn events(
+    State(api): State<Api>,
+    Query(params): Query<EventParams>,
+) -> Result<Sse<impl futures::Stream<Item = Result<Event, axum::Error>>>, ApiError> {
+    let processes = match params.process {
+        Some(name) => vec![api.find_process(&name)?],
+        None => api.store.processes(),
+    };
+
+    let mut streams: Vec<BoxStream<'static, Result<Event, axum::Error>>> = processes
+        .iter()
+        .map(|info| log_events(&api.store, info.id, params.format).boxed())
+        .collect();
+    let ids = processes.iter().map(|info| info.id).collect();
+    streams.push(status_events(api.store.clone(), ids).boxed());
+
+    Ok(Sse::new(stream::select_all(streams)).keep_alive(KeepAlive::default()))
+}
+
+/// `log` events for each new line of the process.
+fn log_events(
+    store: &StoreHandle,
+    process_id: u64,
+    format: Format,
+) -> impl futures::Stream<Item = Result<Event, axum::Error>> + use<> {
+    let follower = store.follow(process_id, None, None, None);
+
+    stream::unfold(follower, |mut follower| async move {
+        let followed = follower.next().await?;
+        Some((followed, follower))
+    })
+    .flat_map(move |followed| {
+        let events: Vec<_> = match followed {
+            Followed::Lines(lines) => lines
+                .into_iter()
+                .map(|line| {
+                    Event::default()
+                        .event("log")
+                        .json_data(format.convert(line))
+                })
+                .collect(),
+            Followed::Lagged { missed } => vec![
+                Event::default()
+                    .event("lagged")
+                    .json_data(json!({ "process_id": process_id, "missed": missed })),
+            ],
+        };
+        stream::iter(events)
+    })
+}
+
+/// `status` events with the process whenever one of `ids` starts or exits.
+fn status_events(
+    store: StoreHandle,
+    ids: HashSet<u64>,
+) -> impl futures::Stream<Item = Result<Event, axum::Error>> {
+    let events = store.subscribe();
+
+    stream::unfold(events, move |mut events| {
+        let ids = ids.clone();
+        async move {
+            let changed = loop {
+                match events.recv().await {
+                    Ok(
+                        StoreEvent::ProcessStarted { process_id, .. }
+                        | StoreEvent::ProcessExited { process_id, .. },
+                    ) if ids.contains(&process_id) => break vec![process_id],
+                    Ok(_) => {}
+                    // A change may have been missed, resend them all.
+                    Err(RecvError::Lagged(_)) => break ids.iter().copied().collect(),
+                    Err(RecvError::Closed) => return None,
+                }
+            };
+            Some((changed, events))
+        }
+    })
+    .flat_map(move |changed| {
+        // Read after the event is applied: it is published with the
+        // store locked.
+        let events: Vec<_> = changed
+            .into_iter()
+            .filter_map(|process_id| store.process(process_id))
+            .map(|info| Event::default().event("status").json_data(info))
+            .collect();
+        stream::iter(events)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        sync::mpsc,
+    };
+
+    use super::*;
+    use crate::store::StoreManager;
+
+    async fn post(addr: SocketAddr, path: &str, headers: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST {path} HTTP/1.1\r\n{headers}Connection: close\r\nContent-Length: 0\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn sends_commands() {
+        let info = ProcessInfo::new("api".into(), "cargo run".into(), None);
+        let process_id = info.id;
+        let (_manager, store) = StoreManager::new(vec![info], 1, 512, None);
+        let (commands, mut received) = mpsc::channel(1);
+
+        let server = HttpListener::bind(0)
+            .unwrap()
+            .start(store, commands)
+            .unwrap();
+        assert!(server.local_addr().ip().is_loopback());
+
+        let addr = server.local_addr();
+        let web_ui = format!("Host: {addr}\r\nOrigin: http://{addr}\r\nX-Cdi: 1\r\n");
+        let response = post(addr, "/processes/api/restart", &web_ui).await;
+        assert!(response.starts_with("HTTP/1.1 202"), "{response}");
+        assert!(matches!(
+            received.recv().await,
+            Some(Message::Command(ServerCommand::Restart { process_id: id })) if id == process_id
+        ));
+
+        let response = post(addr, "/processes/web/stop", &web_ui).await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+        assert!(response.ends_with(r#"{"error":"no process named 'web'"}"#));
+    }
+
+    #[tokio::test]
+    async fn rejects_foreign_requests() {
+        let info = ProcessInfo::new("api".into(), "cargo run".into(), None);
+        let (_manager, store) = StoreManager::new(vec![info], 1, 512, None);
+        let (commands, mut received) = mpsc::channel(1);
+
+        let server = HttpListener::bind(0)
+            .unwrap()
+            .start(store, commands)
+            .unwrap();
+        let addr = server.local_addr();
+        let port = addr.port();
+
+        for headers in [
+            // A page on another site posting a form or `fetch`ing.
+            format!("Host: {addr}\r\nOrigin: http://evil.example\r\nX-Cdi: 1\r\n"),
+            format!("Host: localhost:{port}\r\nOrigin: http://evil.example\r\n"),
+            // A name rebound to 127.0.0.1.
+            format!("Host: evil.example:{port}\r\nX-Cdi: 1\r\n"),
+            format!("Host: {addr}\r\n"),
+        ] {
+            let response = post(addr, "/processes/api/stop", &headers).await;
+            assert!(
+                response.starts_with("HTTP/1.1 403"),
+                "{headers}: {response}"
+            );
+        }
+        assert!(received.try_recv().is_err());
+
+        let response = post(
+            addr,
+            "/processes/api/stop",
+            &format!("Host: localhost:{port}\r\nX-Cdi: 1\r\n"),
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 202"), "{response}");
+    }
+}
@@ -1,15 +1,29 @@
-use anyhow;
-
+pub mod client;
+pub mod daemon;
+pub mod detect;
+pub mod http;
+pub mod mcp;
+pub mod metrics;
 pub mod server;
 use cdi_shared::log::ProcessInfo;
 pub use server::Connection;
+use session::SessionLock;
+pub use store::StoreHandle;
 
+mod monitor;
 mod process;
-mod store;
+mod procfs;
+mod reader;
+pub mod session;
+pub mod signal;
+pub mod store;
 mod supervisor;
 mod utils;
 
 #[doc(hidden)]
-pub fn serve(processes: Vec<ProcessInfo>) -> anyhow::Result<Connection> {
-    server::serve(processes)
+pub fn serve(
+    processes: Vec<ProcessInfo>,
+    session: Option<SessionLock>,
+) -> anyhow::Result<(Connection, StoreHandle)> {
+    server::serve(processes, session)
 }
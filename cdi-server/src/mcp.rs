@@ -0,0 +1,629 @@
+use std::{
+    collections::HashMap,
+    future,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use cdi_shared::{
+    ansi::strip_ansi,
+    log::{Level, ProcessInfo, ProcessStatus, Stream},
+};
+use regex::Regex;
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    pin, select,
+    sync::mpsc,
+    time::{self, Instant},
+};
+
+use crate::client::{self, ClientRequest, ClientResponse, Responses};
+
+/// Protocol versions spoken, newest first.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Entries returned by `get_logs` by default, and at most.
+const DEFAULT_LINES: usize = 100;
+const MAX_LINES: usize = 1000;
+
+/// How long a restarted service may take to be running again.
+const RESTART_TIMEOUT: Duration = Duration::from_secs(15);
+const DEFAULT_READY_TIMEOUT: u64 = 60;
+/// How often the status and ports of a service are checked while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const PORT_TIMEOUT: Duration = Duration::from_millis(200);
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// A JSON-RPC request, or a notification when it has no `id`.
+#[derive(Deserialize)]
+struct Request {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Serves the Model Context Protocol on stdin and stdout, one JSON-RPC
+/// message per line, so coding agents can inspect and restart the services.
+/// Every tool is answered with requests to the server on `socket_path`.
+pub async fn serve_stdio(socket_path: PathBuf) -> Result<()> {
+    let (responses, mut outgoing) = mpsc::unbounded_channel::<Value>();
+    tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = outgoing.recv().await {
+            let mut line = message.to_string();
+            line.push('\n');
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let _ = responses.send(error(Value::Null, PARSE_ERROR, e.to_string()));
+                continue;
+            }
+        };
+
+        // `wait_until_ready` takes a while; other calls are answered meanwhile.
+        let socket_path = socket_path.clone();
+        let responses = responses.clone();
+        tokio::spawn(async move {
+            if let Some(response) = handle(&socket_path, request).await {
+                let _ = responses.send(response);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// The response to `request`, `None` for notifications.
+async fn handle(socket_path: &Path, request: Request) -> Option<Value> {
+    let id = request.id?;
+    let result = match request.method.as_str() {
+        "initialize" => Ok(initialize(&request.params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => call_tool(socket_path, request.params).await,
+        method => Err((METHOD_NOT_FOUND, format!("unknown method '{method}'"))),
+    };
+
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error(id, code, message),
+    })
+}
+
+fn initialize(params: &Value) -> Value {
+    let requested = params["protocolVersion"].as_str();
+    let version = PROTOCOL_VERSIONS
+        .into_iter()
+        .find(|version| Some(*version) == requested)
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "cdi", "version": env!("CARGO_PKG_VERSION") },
+        "instructions": "Inspects and restarts the development services cdi runs for this project. \
+            Start them with `cdi up -d` if no cdi server is running.",
+    })
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "list_services",
+            "description": "Lists the services with their status, pid, exit code and ports.",
+            "inputSchema": { "type": "object", "properties": {} },
+        },
+        {
+            "name": "get_logs",
+            "description": "Returns the latest output of a service, oldest first, with ANSI escapes removed. \
+                Multi-line entries such as stack traces count as one entry.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "service": { "type": "string", "description": "Service name" },
+                    "lines": { "type": "integer", "description": "Entries to return, 100 by default, at most 1000" },
+                    "grep": { "type": "string", "description": "Case-insensitive text the entries must contain" },
+                    "level": { "type": "string", "enum": ["trace", "debug", "info", "warn", "error"], "description": "Lowest log level" },
+                    "since": { "type": "string", "description": "Only entries from this long ago, e.g. 30s, 5m, 2h" },
+                    "stream": { "type": "string", "enum": ["stdout", "stderr"] },
+                },
+                "required": ["service"],
+            },
+        },
+        {
+            "name": "get_errors",
+            "description": "Returns the compiler diagnostics, stack traces and error lines detected in the output \
+                of one service or all of them, with file locations where known.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "service": { "type": "string", "description": "Service name, all services when omitted" },
+                },
+            },
+        },
+        {
+            "name": "restart_service",
+            "description": "Restarts a service, or starts it if it is stopped, and waits until it is running again.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "service": { "type": "string", "description": "Service name" },
+                },
+                "required": ["service"],
+            },
+        },
+        {
+            "name": "wait_until_ready",
+            "description": "Waits until a service is running and accepts connections on all of its ports. \
+                With log_pattern, also waits for a line matching it in the output of the current run. \
+                Fails if the service exits meanwhile.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "service": { "type": "string", "description": "Service name" },
+                    "log_pattern": { "type": "string", "description": "Regex of a line printed once the service is ready" },
+                    "timeout_seconds": { "type": "integer", "description": "60 by default" },
+                },
+                "required": ["service"],
+            },
+        },
+    ])
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+struct ServiceArgs {
+    service: String,
+}
+
+#[derive(Deserialize)]
+struct GetLogsArgs {
+    service: String,
+    lines: Option<usize>,
+    grep: Option<String>,
+    level: Option<String>,
+    since: Option<String>,
+    stream: Option<Stream>,
+}
+
+#[derive(Deserialize)]
+struct GetErrorsArgs {
+    service: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WaitArgs {
+    service: String,
+    log_pattern: Option<String>,
+    timeout_seconds: Option<u64>,
+}
+
+async fn call_tool(socket_path: &Path, params: Value) -> Result<Value, (i64, String)> {
+    let call: ToolCall =
+        serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+    let arguments = call.arguments;
+
+    let result = match call.name.as_str() {
+        "list_services" => list_services(socket_path).await,
+        "get_logs" => match parse_args(arguments) {
+            Ok(args) => get_logs(socket_path, args).await,
+            Err(e) => Err(e),
+        },
+        "get_errors" => match parse_args(arguments) {
+            Ok(args) => get_errors(socket_path, args).await,
+            Err(e) => Err(e),
+        },
+        "restart_service" => match parse_args(arguments) {
+            Ok(args) => restart_service(socket_path, args).await,
+            Err(e) => Err(e),
+        },
+        "wait_until_ready" => match parse_args(arguments) {
+            Ok(args) => wait_until_ready(socket_path, args).await,
+            Err(e) => Err(e),
+        },
+        name => return Err((INVALID_PARAMS, format!("unknown tool '{name}'"))),
+    };
+
+    // Failures are shown to the model, which can correct itself.
+    let (text, is_error) = match result {
+        Ok(text) => (text, false),
+        Err(e) => (format!("{e:#}"), true),
+    };
+
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    }))
+}
+
+fn parse_args<T: DeserializeOwned>(arguments: Value) -> Result<T> {
+    let arguments = if arguments.is_null() {
+        json!({})
+    } else {
+        arguments
+    };
+
+    serde_json::from_value(arguments).context("invalid arguments")
+}
+
+/// Sends `request`, turning an error response into an error.
+async fn call(socket_path: &Path, request: ClientRequest) -> Result<ClientResponse> {
+    match client::request(socket_path, &request).await? {
+        ClientResponse::Error { message } => bail!(message),
+        response => Ok(response),
+    }
+}
+
+async fn processes(socket_path: &Path) -> Result<Vec<ProcessInfo>> {
+    match call(socket_path, ClientRequest::ListProcesses).await? {
+        ClientResponse::Processes(processes) => Ok(processes),
+        response => bail!("unexpected response: {response:?}"),
+    }
+}
+
+async fn process(socket_path: &Path, name: &str) -> Result<ProcessInfo> {
+    let processes = processes(socket_path).await?;
+    if let Some(info) = processes.iter().find(|info| info.name == name) {
+        return Ok(info.clone());
+    }
+
+    let names: Vec<&str> = processes.iter().map(|info| info.name.as_str()).collect();
+    bail!("no service named '{name}', services: {}", names.join(", "))
+}
+
+async fn list_services(socket_path: &Path) -> Result<String> {
+    let services: Vec<Value> = processes(socket_path)
+        .await?
+        .into_iter()
+        .map(|info| {
+            json!({
+                "name": info.name,
+                "status": info.status,
+                "pid": info.pid,
+                "exit_code": info.exit_code,
+                "ports": info.ports,
+                "command": info.command,
+                "cwd": info.cwd,
+            })
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&services)?)
+}
+
+async fn get_logs(socket_path: &Path, args: GetLogsArgs) -> Result<String> {
+    let level = args
+        .level
+        .map(|level| Level::parse(&level).with_context(|| format!("unknown level '{level}'")))
+        .transpose()?;
+    let since = args
+        .since
+        .map(|since| {
+            let ago = parse_duration(&since).with_context(|| {
+                format!("invalid duration '{since}', expected e.g. 30s, 5m or 2h")
+            })?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+            Ok::<_, anyhow::Error>(now.saturating_sub(ago).as_millis())
+        })
+        .transpose()?;
+
+    let request = ClientRequest::GetLogs {
+        process_id: process(socket_path, &args.service).await?.name,
+        limit: args.lines.unwrap_or(DEFAULT_LINES).min(MAX_LINES),
+        stream: args.stream,
+        search: args.grep,
+        level,
+        since,
+    };
+    let lines = match call(socket_path, request).await? {
+        ClientResponse::Logs(lines) => lines,
+        response => bail!("unexpected response: {response:?}"),
+    };
+
+    if lines.is_empty() {
+        return Ok("No matching output.".to_string());
+    }
+
+    let lines: Vec<_> = lines
+        .iter()
+        .map(|line| strip_ansi(&line.content).into_owned())
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+async fn get_errors(socket_path: &Path, args: GetErrorsArgs) -> Result<String> {
+    let processes = processes(socket_path).await?;
+    let names: HashMap<u64, &str> = processes
+        .iter()
+        .map(|info| (info.id, info.name.as_str()))
+        .collect();
+
+    let process_id = match args.service {
+        Some(name) => Some(process(socket_path, &name).await?.name),
+        None => None,
+    };
+    let errors = match call(socket_path, ClientRequest::GetErrors { process_id }).await? {
+        ClientResponse::Errors(errors) => errors,
+        response => bail!("unexpected response: {response:?}"),
+    };
+
+    if errors.is_empty() {
+        return Ok("No errors detected.".to_string());
+    }
+
+    let errors: Vec<String> = errors
+        .iter()
+        .map(|error| {
+            let name = names.get(&error.process_id).copied().unwrap_or("?");
+            let location = error
+                .location
+                .as_ref()
+                .map(|location| format!(" {location}"))
+                .unwrap_or_default();
+            format!(
+                "[{name}] {}{location} ({}): {}",
+                error.severity.as_str(),
+                error.source,
+                error.message
+            )
+        })
+        .collect();
+    Ok(errors.join("\n"))
+}
+
+async fn restart_service(socket_path: &Path, args: ServiceArgs) -> Result<String> {
+    let before = process(socket_path, &args.service).await?;
+    let request = ClientRequest::RestartProcess {
+        process_id: before.name.clone(),
+    };
+    call(socket_path, request).await?;
+
+    // Running again once it has a new process group.
+    let deadline = Instant::now() + RESTART_TIMEOUT;
+    loop {
+        time::sleep(POLL_INTERVAL).await;
+        let info = process(socket_path, &before.name).await?;
+        if info.status == ProcessStatus::Running
+            && let Some(pid) = info.pid
+            && info.pid != before.pid
+        {
+            return Ok(format!("Restarted {} (pid {pid}).", info.name));
+        }
+
+        if Instant::now() >= deadline {
+            bail!(
+                "{} is not running again after {}s, its status is {:?}; see get_logs",
+                info.name,
+                RESTART_TIMEOUT.as_secs(),
+                info.status
+            );
+        }
+    }
+}
+
+async fn wait_until_ready(socket_path: &Path, args: WaitArgs) -> Result<String> {
+    let pattern = args
+        .log_pattern
+        .map(|pattern| Regex::new(&pattern))
+        .transpose()
+        .context("invalid log_pattern")?;
+    let timeout = args.timeout_seconds.unwrap_or(DEFAULT_READY_TIMEOUT);
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    let name = process(socket_path, &args.service).await?.name;
+
+    let mut matched = pattern.is_none();
+    // New output, followed once the run's earlier output was searched.
+    let mut follow = None;
+    let mut searched = false;
+    let mut was_running = false;
+    loop {
+        let info = process(socket_path, &name).await?;
+        let waiting_for = if info.status == ProcessStatus::Running {
+            was_running = true;
+            if let Some(pattern) = &pattern
+                && !searched
+            {
+                follow = search_run(socket_path, &info, pattern).await?;
+                matched = follow.is_none();
+                searched = true;
+            }
+
+            if !matched {
+                Some("a line matching log_pattern".to_string())
+            } else {
+                closed_port(&info.ports)
+                    .await
+                    .map(|port| format!("port {port} to accept connections"))
+            }
+        } else if was_running {
+            bail!(
+                "{name} exited while starting (exit code {}); see get_logs",
+                info.exit_code
+                    .map_or_else(|| "none".to_string(), |code| code.to_string())
+            );
+        } else {
+            Some(format!("it to run, its status is {:?}", info.status))
+        };
+
+        let Some(waiting_for) = waiting_for else {
+            return Ok(format!("{name} is ready."));
+        };
+        if Instant::now() >= deadline {
+            bail!("{name} is not ready after {timeout}s, still waiting for {waiting_for}");
+        }
+
+        // Reads output until the next check.
+        let tick = time::sleep(POLL_INTERVAL);
+        pin!(tick);
+        loop {
+            select! {
+                _ = &mut tick => break,
+                response = next_response(&mut follow), if !matched => {
+                    match response? {
+                        Some(ClientResponse::Logs(lines)) => {
+                            let pattern = pattern.as_ref().expect("followed with a pattern");
+                            matched = lines
+                                .iter()
+                                .any(|line| pattern.is_match(&strip_ansi(&line.content)));
+                        }
+                        Some(ClientResponse::Error { message }) => bail!(message),
+                        Some(_) => {}
+                        None => bail!("the cdi server stopped"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Searches the output of the service's current run for a line matching
+/// `pattern`. Without a match, follows the lines printed after it.
+async fn search_run(
+    socket_path: &Path,
+    info: &ProcessInfo,
+    pattern: &Regex,
+) -> Result<Option<Responses>> {
+    let request = ClientRequest::GetLogs {
+        process_id: info.name.clone(),
+        limit: MAX_LINES,
+        stream: None,
+        search: None,
+        level: None,
+        since: info.started_at,
+    };
+    let lines = match call(socket_path, request).await? {
+        ClientResponse::Logs(lines) => lines,
+        response => bail!("unexpected response: {response:?}"),
+    };
+    if lines
+        .iter()
+        .any(|line| pattern.is_match(&strip_ansi(&line.content)))
+    {
+        return Ok(None);
+    }
+
+    let request = ClientRequest::FollowLogs {
+        process_id: info.name.clone(),
+        after: lines.last().map(|line| line.id),
+        seen_lines: lines.last().map(|line| line.content.split('\n').count()),
+        stream: None,
+    };
+    Ok(Some(client::open(socket_path, &request).await?))
+}
+
+async fn next_response(responses: &mut Option<Responses>) -> Result<Option<ClientResponse>> {
+    match responses {
+        Some(responses) => responses.next().await,
+        None => future::pending().await,
+    }
+}
+
+/// The first of `ports` nothing accepts connections on, on localhost.
+async fn closed_port(ports: &[u16]) -> Option<u16> {
+    for &port in ports {
+        let connect = TcpStream::connect(("localhost", port));
+        if !matches!(time::timeout(PORT_TIMEOUT, connect).await, Ok(Ok(_))) {
+            return Some(port);
+        }
+    }
+
+    None
+}
+
+/// `30s`, `5m`, `2h`, `1d`, or a number of seconds.
+fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().ok()?;
+
+    let seconds = match unit.trim() {
+        "" | "s" | "sec" | "secs" => 1,
+        "m" | "min" | "mins" => 60,
+        "h" | "hr" | "hrs" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn answers_requests() {
+        let socket_path = Path::new("/nonexistent/cdi.sock");
+        let request = |text: &str| serde_json::from_str::<Request>(text).unwrap();
+
+        let response = handle(
+            socket_path,
+            request(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05"}}"#),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+
+        let notification = request(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#);
+        assert!(handle(socket_path, notification).await.is_none());
+
+        let response = handle(
+            socket_path,
+            request(r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response["result"]["tools"].as_array().unwrap().len(), 5);
+
+        // Tool failures go to the model rather than failing the request.
+        let response = handle(
+            socket_path,
+            request(r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"list_services"}}"#),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response["result"]["isError"], true);
+
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2 h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("soon"), None);
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::HashSet,
+    fmt::{Display, Write as _},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use cdi_shared::log::{LogStats, ProcessInfo, ProcessStatus};
+
+use crate::{StoreHandle, procfs};
+
+/// `Content-Type` of `render`'s output.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Per-service and store metrics in the Prometheus text format.
+///
+/// A service is ready once it is running and something listens on each of
+/// its declared ports. CPU and memory are those of the service's process
+/// tree at the latest resource sample.
+pub fn render(store: &StoreHandle) -> String {
+    let services: Vec<(ProcessInfo, LogStats)> = store
+        .processes()
+        .into_iter()
+        .map(|info| {
+            let stats = store.stats(info.id).unwrap_or_default();
+            (info, stats)
+        })
+        .collect();
+    let listening: HashSet<u16> = procfs::listening_ports().into_values().collect();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis());
+
+    let mut out = Metrics::default();
+
+    out.family("cdi_service_up", "gauge", "Whether the service is running.");
+    for (info, _) in &services {
+        out.sample("cdi_service_up", info, &[], running(info) as u8);
+    }
+
+    out.family(
+        "cdi_service_ready",
+        "gauge",
+        "Whether the service is running and listening on all of its ports.",
+    );
+    for (info, _) in &services {
+        let ready = running(info) && info.ports.iter().all(|port| listening.contains(port));
+        out.sample("cdi_service_ready", info, &[], ready as u8);
+    }
+
+    out.family(
+        "cdi_service_restarts_total",
+        "counter",
+        "Times the service was started again after its first start.",
+    );
+    for (info, stats) in &services {
+        let restarts = stats.starts.saturating_sub(1);
+        out.sample("cdi_service_restarts_total", info, &[], restarts);
+    }
+
+    out.family(
+        "cdi_service_uptime_seconds",
+        "gauge",
+        "Seconds since the service started, 0 when it is not running.",
+    );
+    for (info, _) in &services {
+        let uptime = match info.started_at {
+            Some(started_at) if running(info) => now.saturating_sub(started_at) as f64 / 1000.0,
+            _ => 0.0,
+        };
+        out.sample("cdi_service_uptime_seconds", info, &[], uptime);
+    }
+
+    out.family(
+        "cdi_service_log_lines_total",
+        "counter",
+        "Lines the service wrote.",
+    );
+    for (info, stats) in &services {
+        let name = "cdi_service_log_lines_total";
+        out.sample(name, info, &[("stream", "stdout")], stats.stdout_lines);
+        out.sample(name, info, &[("stream", "stderr")], stats.stderr_lines);
+    }
+
+    out.family(
+        "cdi_service_log_bytes_total",
+        "counter",
+        "Bytes the service wrote, line endings excluded.",
+    );
+    for (info, stats) in &services {
+        let name = "cdi_service_log_bytes_total";
+        out.sample(name, info, &[("stream", "stdout")], stats.stdout_bytes);
+        out.sample(name, info, &[("stream", "stderr")], stats.stderr_bytes);
+    }
+
+    out.family(
+        "cdi_service_log_level_entries",
+        "gauge",
+        "Log entries detected at a level. An entry growing into a stack trace may move up a level.",
+    );
+    for (info, stats) in &services {
+        let name = "cdi_service_log_level_entries";
+        out.sample(name, info, &[("level", "error")], stats.errors);
+        out.sample(name, info, &[("level", "warn")], stats.warnings);
+    }
+
+    out.family(
+        "cdi_service_cpu_percent",
+        "gauge",
+        "CPU use of the service's process tree, in percent of one core.",
+    );
+    for (info, _) in &services {
+        let cpu = latest(store, info).map_or(0.0, |(cpu, _)| cpu);
+        out.sample("cdi_service_cpu_percent", info, &[], cpu);
+    }
+
+    out.family(
+        "cdi_service_resident_memory_bytes",
+        "gauge",
+        "Resident memory of the service's process tree.",
+    );
+    for (info, _) in &services {
+        let memory = latest(store, info).map_or(0, |(_, memory)| memory);
+        out.sample("cdi_service_resident_memory_bytes", info, &[], memory);
+    }
+
+    out.family(
+        "cdi_store_memory_bytes",
+        "gauge",
+        "Approximate memory held by the retained output of all services.",
+    );
+    out.value("cdi_store_memory_bytes", store.heap_size());
+
+    out.family(
+        "cdi_store_event_backlog",
+        "gauge",
+        "Events waiting to be applied to the store.",
+    );
+    out.value("cdi_store_event_backlog", store.backlog());
+
+    out.family(
+        "cdi_replica_event_backlog",
+        "gauge",
+        "Events the slowest attached client has yet to receive.",
+    );
+    out.value("cdi_replica_event_backlog", store.replica_backlog());
+
+    if let Some(stat) = procfs::stat(std::process::id()) {
+        out.family(
+            "cdi_resident_memory_bytes",
+            "gauge",
+            "Resident memory of cdi itself.",
+        );
+        out.value("cdi_resident_memory_bytes", stat.memory());
+    }
+
+    out.0
+}
+
+fn running(info: &ProcessInfo) -> bool {
+    info.status == ProcessStatus::Running
+}
+
+/// CPU and memory at the latest resource sample, while the service runs.
+fn latest(store: &StoreHandle, info: &ProcessInfo) -> Option<(f32, u64)> {
+    if !running(info) {
+        return None;
+    }
+
+    store
+        .resources(info.id)
+        .last()
+        .map(|sample| (sample.cpu, sample.memory))
+}
+
+#[derive(Default)]
+struct Metrics(String);
+
+impl Metrics {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {name} {help}");
+        let _ = writeln!(self.0, "# TYPE {name} {kind}");
+    }
+
+    fn value(&mut self, name: &str, value: impl Display) {
+        let _ = writeln!(self.0, "{name} {value}");
+    }
+
+    /// A sample labelled with the service and `labels`.
+    fn sample(
+        &mut self,
+        name: &str,
+        info: &ProcessInfo,
+        labels: &[(&str, &str)],
+        value: impl Display,
+    ) {
+        let _ = write!(self.0, "{name}{{service=\"{}\"", escape(&info.name));
+        for (label, label_value) in labels {
+            let _ = write!(self.0, ",{label}=\"{}\"", escape(label_value));
+        }
+        let _ = writeln!(self.0, "}} {value}");
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use cdi_shared::{
+        event::store::StoreEvent,
+        log::{ReadTime, Stream},
+    };
+
+    use super::*;
+    use crate::store::StoreManager;
+
+    #[tokio::test]
+    async fn renders_service_metrics() {
+        let info = ProcessInfo::new("api \"v2\"".into(), "cargo run".into(), None);
+        let process_id = info.id;
+        let (manager, store) = StoreManager::new(vec![info], 1, 512, None);
+
+        let (events, receiver) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(manager.run(receiver));
+        for pid in [100, 200] {
+            events
+                .send(StoreEvent::ProcessStarted {
+                    process_id,
+                    pid,
+                    time: ReadTime::now(),
+                })
+                .unwrap();
+        }
+        for content in ["listening", "error: boom"] {
+            events
+                .send(StoreEvent::AppendLog {
+                    process_id,
+                    stream: Stream::Stderr,
+                    content: content.to_string(),
+                    time: cdi_shared::log::ReadTime::now(),
+                })
+                .unwrap();
+        }
+        drop(events);
+        while store.stats(process_id).unwrap().stderr_lines < 2 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let metrics = render(&store);
+        for expected in [
+            r#"cdi_service_up{service="api \"v2\""} 1"#,
+            r#"cdi_service_ready{service="api \"v2\""} 1"#,
+            r#"cdi_service_restarts_total{service="api \"v2\""} 1"#,
+            r#"cdi_service_log_lines_total{service="api \"v2\"",stream="stderr"} 2"#,
+            r#"cdi_service_log_bytes_total{service="api \"v2\"",stream="stderr"} 20"#,
+            r#"cdi_service_log_level_entries{service="api \"v2\"",level="error"} 1"#,
+        ] {
+            assert!(
+                metrics.lines().any(|line| line == expected),
+                "{expected}\n{metrics}"
+            );
+        }
+    }
+}
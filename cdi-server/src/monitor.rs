@@ -0,0 +1,193 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use cdi_shared::{
+    event::store::StoreEvent,
+    resource::{ProcessNode, ResourceSample},
+};
+use tokio::time;
+
+use crate::{
+    procfs::{self, ProcStat},
+    store::StoreHandle,
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Samples the resource usage of every running service's process tree from
+/// `/proc` and sends it to the store.
+pub(crate) struct Monitor {
+    store: StoreHandle,
+    /// CPU ticks of every process at the previous sample.
+    previous: HashMap<u32, u64>,
+    sampled_at: Option<Instant>,
+}
+
+impl Monitor {
+    pub fn start(store: StoreHandle) {
+        let monitor = Self {
+            store,
+            previous: HashMap::new(),
+            sampled_at: None,
+        };
+        tokio::spawn(monitor.run());
+    }
+
+    async fn run(mut self) {
+        let mut interval = time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            self.sample();
+        }
+    }
+
+    fn sample(&mut self) {
+        let all = procfs::processes();
+        let now = Instant::now();
+        let elapsed = self.sampled_at.map(|at| now.duration_since(at));
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        let services: Vec<_> = self
+            .store
+            .processes()
+            .into_iter()
+            .filter_map(|info| Some((info.id, info.pid? as u32)))
+            .collect();
+        let ports = if services.is_empty() {
+            HashMap::new()
+        } else {
+            procfs::listening_ports()
+        };
+
+        // CPU usage needs two samples.
+        if let Some(elapsed) = elapsed {
+            for (process_id, pid) in services {
+                let procs = procfs::tree(pid, &all);
+                if procs.is_empty() {
+                    continue;
+                }
+                let tree: Vec<ProcessNode> = procs
+                    .iter()
+                    .map(|(proc, depth)| self.node(proc, *depth, elapsed, &ports))
+                    .collect();
+
+                let sample = ResourceSample {
+                    timestamp,
+                    cpu: tree.iter().map(|node| node.cpu).sum(),
+                    memory: tree.iter().map(|node| node.memory).sum(),
+                    threads: procs.iter().map(|(proc, _)| proc.threads).sum(),
+                    fds: tree
+                        .iter()
+                        .filter_map(|node| procfs::fd_count(node.pid))
+                        .sum(),
+                    processes: tree.len() as u32,
+                    stopped: procs.iter().all(|(proc, _)| proc.stopped()),
+                };
+
+                StoreEvent::ResourceSample {
+                    process_id,
+                    sample,
+                    tree,
+                }
+                .emit();
+            }
+        }
+
+        self.previous = all.iter().map(|proc| (proc.pid, proc.cpu_ticks)).collect();
+        self.sampled_at = Some(now);
+    }
+
+    fn node(
+        &self,
+        proc: &ProcStat,
+        depth: u32,
+        elapsed: Duration,
+        ports: &HashMap<u64, u16>,
+    ) -> ProcessNode {
+        // A process missing from the previous sample was started since, so
+        // all of its time falls into this interval.
+        let ticks = proc
+            .cpu_ticks
+            .saturating_sub(self.previous.get(&proc.pid).copied().unwrap_or(0));
+        let cpu = ticks as f64 / procfs::clock_ticks() as f64 / elapsed.as_secs_f64();
+
+        let command = procfs::cmdline(proc.pid)
+            .filter(|command| !command.is_empty())
+            .unwrap_or_else(|| format!("[{}]", proc.name));
+        let mut ports: Vec<u16> = procfs::socket_inodes(proc.pid)
+            .iter()
+            .filter_map(|inode| ports.get(inode).copied())
+            .collect();
+        ports.sort_unstable();
+        ports.dedup();
+
+        ProcessNode {
+            pid: proc.pid,
+            ppid: proc.ppid,
+            depth,
+            command,
+            cpu: (cpu * 100.0) as f32,
+            memory: proc.memory(),
+            ports,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::StoreManager;
+
+    #[test]
+    fn samples_cpu_since_previous() {
+        let own = procfs::stat(std::process::id()).unwrap();
+        let monitor = Monitor {
+            store: StoreManager::new(Vec::new(), 0, 128, None).1,
+            previous: HashMap::from([(own.pid, own.cpu_ticks)]),
+            sampled_at: None,
+        };
+        let ports = HashMap::new();
+
+        // Half a second of CPU time over a second.
+        let busy = ProcStat {
+            cpu_ticks: own.cpu_ticks + procfs::clock_ticks() / 2,
+            ..own.clone()
+        };
+        let node = monitor.node(&busy, 0, Duration::from_secs(1), &ports);
+        assert_eq!(node.cpu, 50.0);
+        assert_eq!(node.memory, own.memory());
+
+        // Started since the previous sample, so all its time counts.
+        let started = ProcStat {
+            pid: u32::MAX,
+            cpu_ticks: procfs::clock_ticks(),
+            ..own
+        };
+        let node = monitor.node(&started, 1, Duration::from_secs(2), &ports);
+        assert_eq!(node.cpu, 50.0);
+        assert_eq!(node.command, format!("[{}]", started.name));
+    }
+
+    #[test]
+    fn lists_listening_ports() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let own = procfs::stat(std::process::id()).unwrap();
+        let monitor = Monitor {
+            store: StoreManager::new(Vec::new(), 0, 128, None).1,
+            previous: HashMap::new(),
+            sampled_at: None,
+        };
+        let node = monitor.node(&own, 0, Duration::from_secs(1), &procfs::listening_ports());
+        assert!(node.ports.contains(&port));
+        assert!(!node.command.is_empty());
+    }
+}
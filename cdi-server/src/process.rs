@@ -1,25 +1,36 @@
 use std::process::Stdio;
+use std::time::Duration;
 
 use anyhow::Result;
 use cdi_shared::{
-    event::{store::StoreEvent, ui::TuiEvent},
-    log::{ProcessInfo, ProcessStatus},
+    event::store::StoreEvent,
+    log::{ProcessInfo, ProcessStatus, ReadTime, Stream},
 };
 use libc::pid_t;
 use tokio::{
-    io::{AsyncBufReadExt, BufReader},
+    io::AsyncRead,
     process::{Child, Command},
     select,
-    sync::mpsc,
     task::JoinHandle,
+    time::{self, Instant},
 };
 
 use crate::{
-    Connection,
+    Connection, procfs,
+    reader::{Chunk, LineReader},
     server::{Message, ServerCommand},
-    utils,
+    session, utils,
 };
 
+/// How often an adopted process group is checked for having exited.
+const ADOPTED_POLL: Duration = Duration::from_secs(1);
+
+/// How long an exited process's output may take to be read to the end.
+const READER_DRAIN: Duration = Duration::from_millis(500);
+
+/// How long a stopped service gets to exit after `SIGTERM` before `SIGKILL`.
+pub(crate) const STOP_GRACE: Duration = Duration::from_secs(5);
+
 pub(super) struct Process {
     info: ProcessInfo,
     conn: Connection,
@@ -37,6 +48,28 @@ impl Process {
     }
 
     async fn run(mut self) {
+        if let Some(pgid) = self.info.pid {
+            return self.adopt(pgid as u32).await;
+        }
+
+        if let Some(conflict) = self.port_conflict() {
+            StoreEvent::AppendLog {
+                process_id: self.info.id,
+                stream: Stream::Stderr,
+                content: conflict,
+                time: ReadTime::now(),
+            }
+            .emit();
+            StoreEvent::ProcessExited {
+                process_id: self.info.id,
+                status: ProcessStatus::Crashed,
+                exit_code: None,
+            }
+            .emit();
+
+            return;
+        }
+
         if let Some((cmd, args)) = utils::split_command_into_parts(&self.info.command) {
             let mut command = Command::new(cmd.clone());
 
@@ -52,72 +85,79 @@ impl Process {
                 command.current_dir(canonical);
             }
 
+            let time = ReadTime::now();
             let mut child = match command.spawn() {
                 Ok(c) => c,
                 Err(e) => {
-                    eprint!("Failed to spawn {}: {}", "", e);
+                    eprint!("Failed to spawn {}: {}", cmd, e);
                     return;
                 }
             };
 
+            if let Some(pid) = child.id() {
+                StoreEvent::ProcessStarted {
+                    process_id: self.info.id,
+                    pid,
+                    time,
+                }
+                .emit();
+            }
+
             let stdout = child.stdout.take().expect("Failed to capture stdout");
             let stderr = child.stderr.take().expect("Failed to capture stderr");
 
-            let mut stdout_reader = BufReader::new(stdout).lines();
-            let mut stderr_reader = BufReader::new(stderr).lines();
-
-            tokio::spawn(async move {
-                while let Ok(Some(line)) = stdout_reader.next_line().await {
-                    StoreEvent::AppendLog {
-                        process_id: self.info.id,
-                        content: line,
-                        stream: cdi_shared::log::Stream::Stdout,
-                    }
-                    .emit();
-                }
-            });
-
-            tokio::spawn(async move {
-                while let Ok(Some(line)) = stderr_reader.next_line().await {
-                    StoreEvent::AppendLog {
-                        process_id: self.info.id,
-                        content: line,
-                        stream: cdi_shared::log::Stream::Stderr,
-                    }
-                    .emit();
-                }
-            });
+            let mut readers = [
+                tokio::spawn(Self::read_output(self.info.id, Stream::Stdout, stdout)),
+                tokio::spawn(Self::read_output(self.info.id, Stream::Stderr, stderr)),
+            ];
 
+            // Set once asked to stop; the exit is reported like any other.
+            let mut kill_deadline = None;
             loop {
                 select! {
                     biased;
 
-                    Some(msg) = self.conn.receiver.recv() => {
+                    Some(msg) = self.conn.receiver.recv(), if kill_deadline.is_none() => {
                         match msg {
-                            Message::Command(c) => match c {
-                                ServerCommand::Shutdown => match Self::kill_gracefully(&child).await {
-                                    Ok(_) => {
-                                        let exit_code = child.wait().await.unwrap().code();
-                                        eprintln!("Process {} killed with status {:?}", cmd, exit_code);
-                                        return;
-                                    },
-                                    Err(_) => todo!()
-                                }
-
+                            Message::Command(ServerCommand::Shutdown) => {
+                                let _ = Self::kill_gracefully(&child).await;
+                                kill_deadline = Some(Instant::now() + STOP_GRACE);
                             }
-                            _ => todo!()
+                            // Stop and restart are handled by the supervisor,
+                            // which only ever sends `Shutdown`.
+                            Message::Command(ServerCommand::Stop { .. } | ServerCommand::Restart { .. })
+                            | Message::ProcessOutput { .. }
+                            | Message::Stopped => {}
                         }
 
                     }
 
+                    _ = time::sleep_until(kill_deadline.unwrap_or_else(Instant::now)), if kill_deadline.is_some() => {
+                        if let Some(pid) = child.id() {
+                            unsafe { libc::kill(-(pid as pid_t), libc::SIGKILL) };
+                        }
+                        kill_deadline = None;
+                    }
+
                     result = child.wait() => {
                         match result {
                             Ok(status) => {
+                                // Let the readers drain the pipes so the exit
+                                // message comes after the last output. Children
+                                // holding them open don't block it for long.
+                                let drain = async {
+                                    for reader in &mut readers {
+                                        let _ = reader.await;
+                                    }
+                                };
+                                let _ = time::timeout(READER_DRAIN, drain).await;
+
                                 let exit_msg = format!("Process {} exited with status: {}", cmd, status);
                                 StoreEvent::AppendLog {
                                     process_id: self.info.id,
                                     content: exit_msg.to_string(),
-                                    stream: cdi_shared::log::Stream::Stdout
+                                    stream: cdi_shared::log::Stream::Stdout,
+                                    time: ReadTime::now(),
                                 }.emit();
 
                                 StoreEvent::ProcessExited {
@@ -136,6 +176,113 @@ impl Process {
         }
     }
 
+    /// Stands in for a process group left running by a previous session:
+    /// its output can't be recovered, but it is monitored, and stopped with
+    /// the rest on shutdown.
+    async fn adopt(mut self, pgid: u32) {
+        StoreEvent::ProcessStarted {
+            process_id: self.info.id,
+            pid: pgid,
+            time: ReadTime::now(),
+        }
+        .emit();
+        StoreEvent::AppendLog {
+            process_id: self.info.id,
+            stream: Stream::Stderr,
+            content: format!(
+                "cdi: adopted process group {pgid} from a previous session, its output is not captured"
+            ),
+            time: ReadTime::now(),
+        }
+        .emit();
+
+        let mut interval = time::interval(ADOPTED_POLL);
+        let mut kill_deadline = None;
+        loop {
+            select! {
+                Some(Message::Command(ServerCommand::Shutdown)) = self.conn.receiver.recv(), if kill_deadline.is_none() => {
+                    unsafe { libc::kill(-(pgid as pid_t), libc::SIGTERM) };
+                    kill_deadline = Some(Instant::now() + STOP_GRACE);
+                }
+                _ = time::sleep_until(kill_deadline.unwrap_or_else(Instant::now)), if kill_deadline.is_some() => {
+                    unsafe { libc::kill(-(pgid as pid_t), libc::SIGKILL) };
+                    kill_deadline = None;
+                }
+                _ = interval.tick() => {
+                    if !session::group_alive(pgid) {
+                        StoreEvent::AppendLog {
+                            process_id: self.info.id,
+                            stream: Stream::Stdout,
+                            content: format!("Process group {pgid} exited"),
+                            time: ReadTime::now(),
+                        }
+                        .emit();
+                        StoreEvent::ProcessExited {
+                            process_id: self.info.id,
+                            status: ProcessStatus::Stopped,
+                            exit_code: None,
+                        }
+                        .emit();
+
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Why the service can't start: one of its ports is already taken. The
+    /// owner is only named if it is visible to us in `/proc`.
+    fn port_conflict(&self) -> Option<String> {
+        let listening = procfs::listening_ports();
+
+        self.info.ports.iter().find_map(|port| {
+            let inodes: Vec<u64> = listening
+                .iter()
+                .filter(|(_, listening)| *listening == port)
+                .map(|(inode, _)| *inode)
+                .collect();
+            if inodes.is_empty() {
+                return None;
+            }
+
+            let owner = procfs::socket_owner(&inodes).map(|pid| {
+                let command = procfs::cmdline(pid)
+                    .filter(|command| !command.is_empty())
+                    .or_else(|| procfs::stat(pid).map(|stat| stat.name))
+                    .unwrap_or_default();
+                format!("pid {pid} ({command})")
+            });
+
+            Some(format!(
+                "cdi: port {port} is already in use by {}, not starting",
+                owner.as_deref().unwrap_or("another process")
+            ))
+        })
+    }
+
+    async fn read_output(process_id: u64, stream: Stream, output: impl AsyncRead + Unpin) {
+        let mut reader = LineReader::new(output);
+
+        while let Some(chunk) = reader.next().await {
+            let stream = stream.clone();
+            match chunk {
+                Chunk::Line(content, time) => StoreEvent::AppendLog {
+                    process_id,
+                    stream,
+                    content,
+                    time,
+                },
+                Chunk::Partial(content) => StoreEvent::PartialLog {
+                    process_id,
+                    stream,
+                    content,
+                },
+            }
+            .emit();
+        }
+    }
+
     async fn kill_gracefully(child: &Child) -> std::io::Result<()> {
         unsafe {
             let rc = libc::kill(-(child.id().unwrap() as pid_t), libc::SIGTERM);
@@ -0,0 +1,282 @@
+//! Readers for the bits of `/proc` the resource monitor needs. Linux only;
+//! elsewhere every read comes back empty.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    sync::LazyLock,
+};
+
+static CLOCK_TICKS: LazyLock<u64> =
+    LazyLock::new(|| unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64);
+static PAGE_SIZE: LazyLock<u64> =
+    LazyLock::new(|| unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64);
+
+/// The fields of `/proc/<pid>/stat` that are of interest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ProcStat {
+    pub pid: u32,
+    pub ppid: u32,
+    pub pgrp: u32,
+    /// Executable name, truncated by the kernel to 15 bytes.
+    pub name: String,
+    /// `R` running, `S` sleeping, `T` stopped by a signal, and so on.
+    pub state: char,
+    /// User plus system time, in clock ticks.
+    pub cpu_ticks: u64,
+    pub threads: u32,
+    pub rss_pages: u64,
+    /// Clock ticks after boot the process started at. Together with the pid
+    /// it identifies a process, as pids are reused.
+    pub start_time: u64,
+}
+
+impl ProcStat {
+    #[inline]
+    pub fn memory(&self) -> u64 {
+        self.rss_pages * *PAGE_SIZE
+    }
+
+    #[inline]
+    pub fn stopped(&self) -> bool {
+        self.state == 'T'
+    }
+}
+
+/// Clock ticks per second, the unit of `ProcStat::cpu_ticks`.
+#[inline]
+pub(crate) fn clock_ticks() -> u64 {
+    *CLOCK_TICKS
+}
+
+/// Every process currently visible in `/proc`.
+pub(crate) fn processes() -> Vec<ProcStat> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .filter_map(stat)
+        .collect()
+}
+
+pub(crate) fn stat(pid: u32) -> Option<ProcStat> {
+    parse_stat(pid, &fs::read_to_string(format!("/proc/{pid}/stat")).ok()?)
+}
+
+fn parse_stat(pid: u32, text: &str) -> Option<ProcStat> {
+    // The name is in parentheses and may itself contain spaces and
+    // parentheses, so split at the last `)`.
+    let (head, rest) = text.rsplit_once(')')?;
+    let name = head.split_once('(')?.1.to_string();
+    // `rest` starts at field 3 (state).
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+
+    Some(ProcStat {
+        pid,
+        ppid: field(4)? as u32,
+        pgrp: field(5)? as u32,
+        name,
+        state: fields.first()?.chars().next()?,
+        cpu_ticks: field(14)? + field(15)?,
+        threads: field(20)? as u32,
+        rss_pages: field(24)?,
+        start_time: field(22)?,
+    })
+}
+
+pub(crate) fn fd_count(pid: u32) -> Option<u32> {
+    Some(fs::read_dir(format!("/proc/{pid}/fd")).ok()?.count() as u32)
+}
+
+/// Arguments joined with spaces; empty for kernel threads and zombies.
+pub(crate) fn cmdline(pid: u32) -> Option<String> {
+    let raw = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let args: Vec<String> = raw
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+
+    Some(args.join(" "))
+}
+
+/// Inodes of the sockets `pid` has open.
+pub(crate) fn socket_inodes(pid: u32) -> Vec<u64> {
+    let Ok(entries) = fs::read_dir(format!("/proc/{pid}/fd")) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| {
+            let target = fs::read_link(entry.ok()?.path()).ok()?;
+            let target = target.to_str()?;
+            target
+                .strip_prefix("socket:[")?
+                .strip_suffix(']')?
+                .parse()
+                .ok()
+        })
+        .collect()
+}
+
+/// The process holding one of the sockets `inodes`, if it is visible to us.
+pub(crate) fn socket_owner(inodes: &[u64]) -> Option<u32> {
+    let entries = fs::read_dir("/proc").ok()?;
+
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .find(|pid| {
+            socket_inodes(*pid)
+                .iter()
+                .any(|inode| inodes.contains(inode))
+        })
+}
+
+/// Socket inode to port of every listening TCP socket, IPv4 and IPv6.
+pub(crate) fn listening_ports() -> HashMap<u64, u16> {
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .into_iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|table| parse_tcp_table(&table))
+        .collect()
+}
+
+fn parse_tcp_table(table: &str) -> Vec<(u64, u16)> {
+    const LISTEN: &str = "0A";
+
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.get(3) != Some(&LISTEN) {
+                return None;
+            }
+
+            let port = fields.get(1)?.rsplit_once(':')?.1;
+            let port = u16::from_str_radix(port, 16).ok()?;
+            let inode = fields.get(9)?.parse().ok()?;
+
+            Some((inode, port))
+        })
+        .collect()
+}
+
+/// `root` and everything started under it, with each process's depth below
+/// `root`: descendants by parent link, which catches children that moved to
+/// a group of their own, plus members of its process group (services are
+/// spawned as group leaders) whose parent already exited. Depth-first, so
+/// children follow their parent.
+pub(crate) fn tree(root: u32, all: &[ProcStat]) -> Vec<(&ProcStat, u32)> {
+    let mut children: HashMap<u32, Vec<&ProcStat>> = HashMap::new();
+    for proc in all {
+        children.entry(proc.ppid).or_default().push(proc);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by_key(|proc| proc.pid);
+    }
+
+    // Group members reparented to init are only tied to `root` by their
+    // process group, so they go right under it.
+    let mut orphans: Vec<&ProcStat> = all.iter().filter(|proc| proc.pgrp == root).collect();
+    orphans.sort_by_key(|proc| proc.pid);
+    let starts = all
+        .iter()
+        .filter(|proc| proc.pid == root)
+        .map(|proc| (proc, 0))
+        .chain(orphans.into_iter().map(|proc| (proc, 1)));
+
+    let mut tree = Vec::new();
+    let mut visited = HashSet::new();
+    for start in starts {
+        let mut stack = vec![start];
+        while let Some((proc, depth)) = stack.pop() {
+            if !visited.insert(proc.pid) {
+                continue;
+            }
+            tree.push((proc, depth));
+            if let Some(siblings) = children.get(&proc.pid) {
+                stack.extend(siblings.iter().rev().map(|child| (*child, depth + 1)));
+            }
+        }
+    }
+
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_own_process() {
+        let pid = std::process::id();
+        let stat = stat(pid).unwrap();
+        assert_eq!(stat.pid, pid);
+        assert!(stat.threads >= 1);
+        assert!(stat.memory() > 0);
+        assert!(!stat.stopped());
+
+        assert!(fd_count(pid).unwrap() >= 3);
+        assert!(!cmdline(pid).unwrap().is_empty());
+        assert!(processes().iter().any(|proc| proc.pid == pid));
+    }
+
+    #[test]
+    fn parses_proc_files() {
+        let stat = parse_stat(
+            42,
+            "42 (node (worker) 1) S 1 42 42 0 -1 4194560 100 0 0 0 30 12 0 0 20 0 11 0 500 \
+             1000 2500 18446744073709551615",
+        )
+        .unwrap();
+        assert_eq!(stat.name, "node (worker) 1");
+        assert_eq!(stat.state, 'S');
+        assert_eq!((stat.ppid, stat.pgrp), (1, 42));
+        assert_eq!(
+            (stat.cpu_ticks, stat.threads, stat.rss_pages),
+            (42, 11, 2500)
+        );
+        assert_eq!(stat.start_time, 500);
+
+        let proc = |pid, ppid, pgrp| ProcStat {
+            pid,
+            ppid,
+            pgrp,
+            name: String::new(),
+            state: 'S',
+            cpu_ticks: 0,
+            threads: 1,
+            rss_pages: 0,
+            start_time: 0,
+        };
+        let all = [
+            proc(1, 0, 1),
+            proc(10, 1, 10),
+            proc(11, 10, 10),
+            // Started its own group under the service.
+            proc(12, 11, 12),
+            // Parent exited, still in the service's group.
+            proc(13, 1, 10),
+            proc(20, 1, 20),
+        ];
+
+        let tree: Vec<(u32, u32)> = tree(10, &all)
+            .iter()
+            .map(|(proc, depth)| (proc.pid, *depth))
+            .collect();
+        assert_eq!(tree, [(10, 0), (11, 1), (12, 2), (13, 1)]);
+
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0BB8 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:E9AE 0100007F:0BB8 01 00000000:00000000 02:00000B85 00000000  1000        0 4243 2 0000000000000000 20 4 0 16 8";
+        assert_eq!(parse_tcp_table(table), [(4242, 3000)]);
+
+        let table6 = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000000000000:1F90 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 5151 1 0000000000000000 100 0 0 10 0";
+        assert_eq!(parse_tcp_table(table6), [(5151, 8080)]);
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::{Duration, Instant};
+
+use cdi_shared::log::ReadTime;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    time::timeout,
+};
+
+const READ_BUF_SIZE: usize = 8 * 1024;
+/// Longer lines are split, so a service dumping a huge blob on one line
+/// can't stall the reader or bloat a single entry.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+/// How long an unterminated line may sit unseen, e.g. a prompt.
+const IDLE_FLUSH: Duration = Duration::from_millis(100);
+/// Progress bars redraw far more often than anyone can read.
+const PARTIAL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+pub(crate) enum Chunk {
+    /// A finished line, with the time its first byte was read.
+    Line(String, ReadTime),
+    /// The line being written so far. Replaces any earlier partial of the
+    /// same line; the eventual `Line` replaces it too.
+    Partial(String),
+}
+
+/// Splits a process's output into lines at the byte level.
+///
+/// Unlike `BufReader::lines`, invalid UTF-8 is decoded lossily instead of
+/// ending the stream, and a `\r` not followed by `\n` starts overwriting the
+/// line the way a terminal would, so progress bars collapse into their
+/// latest state.
+pub(crate) struct LineReader<R> {
+    reader: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    line: Vec<u8>,
+    /// When the line's first byte was read.
+    started: Option<ReadTime>,
+    /// A `\r` was read: the next byte other than `\n` clears the line.
+    overwrite: bool,
+    /// `line` changed since it was last emitted.
+    dirty: bool,
+    last_partial: Option<Instant>,
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> LineReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: vec![0; READ_BUF_SIZE].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            line: Vec::new(),
+            started: None,
+            overwrite: false,
+            dirty: false,
+            last_partial: None,
+            eof: false,
+        }
+    }
+
+    /// The next line or partial line, `None` once the stream is closed and
+    /// everything has been returned. Read errors close the stream.
+    pub(crate) async fn next(&mut self) -> Option<Chunk> {
+        loop {
+            while self.pos < self.len {
+                let byte = self.buf[self.pos];
+                self.pos += 1;
+
+                match byte {
+                    b'\n' => return Some(self.take_line(self.line.len())),
+                    b'\r' => self.overwrite = true,
+                    _ => {
+                        // Show the state being overwritten, a progress bar's
+                        // last frame, unless one was shown just now.
+                        let partial = if self.overwrite {
+                            self.overwrite = false;
+                            let partial = self.partial_due().then(|| self.partial());
+                            self.line.clear();
+                            partial
+                        } else {
+                            None
+                        };
+
+                        self.line.push(byte);
+                        self.dirty = true;
+                        self.started.get_or_insert_with(ReadTime::now);
+
+                        if self.line.len() >= MAX_LINE_BYTES {
+                            return Some(self.take_line(split_point(&self.line)));
+                        }
+                        if let Some(partial) = partial {
+                            return Some(partial);
+                        }
+                    }
+                }
+            }
+
+            if self.eof {
+                return (!self.line.is_empty()).then(|| self.take_line(self.line.len()));
+            }
+
+            let read = if self.dirty && !self.line.is_empty() {
+                match timeout(IDLE_FLUSH, self.reader.read(&mut self.buf)).await {
+                    Ok(read) => read,
+                    Err(_) => return Some(self.partial()),
+                }
+            } else {
+                self.reader.read(&mut self.buf).await
+            };
+
+            match read {
+                Ok(0) | Err(_) => self.eof = true,
+                Ok(len) => {
+                    self.pos = 0;
+                    self.len = len;
+                }
+            }
+        }
+    }
+
+    fn partial_due(&self) -> bool {
+        self.last_partial
+            .is_none_or(|last| last.elapsed() >= PARTIAL_INTERVAL)
+    }
+
+    fn partial(&mut self) -> Chunk {
+        self.dirty = false;
+        self.last_partial = Some(Instant::now());
+        Chunk::Partial(String::from_utf8_lossy(&self.line).into_owned())
+    }
+
+    /// Removes and decodes the first `len` bytes of the line.
+    fn take_line(&mut self, len: usize) -> Chunk {
+        self.overwrite = false;
+        self.dirty = len < self.line.len();
+        let line = String::from_utf8_lossy(&self.line[..len]).into_owned();
+        self.line.drain(..len);
+
+        // The rest of a split line starts now.
+        let time = if self.dirty {
+            self.started.replace(ReadTime::now())
+        } else {
+            self.started.take()
+        };
+
+        Chunk::Line(line, time.unwrap_or_else(ReadTime::now))
+    }
+}
+
+/// Where to split an over-long line without cutting a character in half.
+fn split_point(line: &[u8]) -> usize {
+    match std::str::from_utf8(line) {
+        Err(e) if e.error_len().is_none() && e.valid_up_to() > 0 => e.valid_up_to(),
+        _ => line.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn splits_lines() {
+        let output: &[u8] = b"ok\r\nbad \xff byte\n10%\r55%\r100%\ndone\nPassword: ";
+        let mut reader = LineReader::new(output);
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = reader.next().await {
+            chunks.push(chunk);
+        }
+
+        // Lines are timestamped when they start, in read order.
+        let seqs: Vec<u64> = chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                Chunk::Line(_, time) => Some(time.seq),
+                Chunk::Partial(_) => None,
+            })
+            .collect();
+        assert!(seqs.is_sorted());
+
+        let lines: Vec<_> = chunks
+            .iter()
+            .map(|chunk| match chunk {
+                Chunk::Line(line, _) => format!("line {line}"),
+                Chunk::Partial(line) => format!("partial {line}"),
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "line ok",
+                "line bad \u{fffd} byte",
+                "partial 10%",
+                "line 100%",
+                "line done",
+                "line Password: ",
+            ]
+        );
+    }
+}
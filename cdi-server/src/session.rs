@@ -0,0 +1,367 @@
+use std::{
+    env,
+    fs::{self, DirBuilder, File, OpenOptions},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, RawFd},
+        unix::fs::{DirBuilderExt, MetadataExt},
+    },
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use cdi_shared::log::ProcessInfo;
+use serde::{Deserialize, Serialize};
+
+use crate::procfs;
+
+/// How long leftover groups get to exit after `SIGTERM` before `SIGKILL`.
+const KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// `$XDG_RUNTIME_DIR/cdi/<hash>.<extension>` for the session started from
+/// `project_dir`, or under `/tmp/cdi-<uid>` when no runtime dir is set. The
+/// directory is created by `create_runtime_dir`.
+pub(crate) fn runtime_file(project_dir: &Path, extension: &str) -> PathBuf {
+    let runtime_dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("cdi"),
+        None => env::temp_dir().join(format!("cdi-{}", unsafe { libc::getuid() })),
+    };
+
+    runtime_dir.join(format!("{}.{extension}", project_hash(project_dir)))
+}
+
+/// Creates the directory of `file`, a `runtime_file`, accessible only to
+/// this user. Fails if it exists but isn't, since whoever can write there
+/// can plant a socket or lockfile for the session.
+pub(crate) fn create_runtime_dir(file: &Path) -> io::Result<()> {
+    let Some(dir) = file.parent() else {
+        return Ok(());
+    };
+
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    check_runtime_dir(dir)
+}
+
+/// Fails unless `dir` is a directory owned by this user and closed to
+/// group and others.
+pub(crate) fn check_runtime_dir(dir: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(dir)?;
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{} must be a directory owned by uid {uid} with mode 0700",
+                dir.display()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// `$XDG_STATE_HOME/cdi/<hash>.<extension>`, or under `~/.local/state`, for
+/// state of `project_dir` kept across sessions and reboots.
+pub fn state_file(project_dir: &Path, extension: &str) -> PathBuf {
+    let state_dir = match (env::var_os("XDG_STATE_HOME"), env::var_os("HOME")) {
+        (Some(dir), _) => PathBuf::from(dir).join("cdi"),
+        (None, Some(home)) => PathBuf::from(home).join(".local/state/cdi"),
+        (None, None) => env::temp_dir().join(format!("cdi-{}", unsafe { libc::getuid() })),
+    };
+
+    state_dir.join(format!("{}.{extension}", project_hash(project_dir)))
+}
+
+fn project_hash(project_dir: &Path) -> String {
+    let project_dir = project_dir
+        .canonicalize()
+        .unwrap_or_else(|_| project_dir.to_path_buf());
+    let mut hasher = DefaultHasher::new();
+    project_dir.hash(&mut hasher);
+
+    format!("{:016x}", hasher.finish())
+}
+
+/// A service's process group as recorded in the lockfile.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupRecord {
+    pub name: String,
+    pub command: String,
+    pub pgid: u32,
+    /// `ProcStat::start_time` of the group leader.
+    #[serde(default)]
+    pub start_time: u64,
+    /// Command line of the group leader, as it was running. Empty while
+    /// the leader is still being `exec`ed.
+    #[serde(default)]
+    pub cmdline: String,
+}
+
+impl GroupRecord {
+    /// Record of the group led by `pgid`, `None` if its leader is gone.
+    fn new(name: &str, command: &str, pgid: u32) -> Option<Self> {
+        Some(Self {
+            name: name.to_string(),
+            command: command.to_string(),
+            pgid,
+            start_time: procfs::stat(pgid)?.start_time,
+            cmdline: procfs::cmdline(pgid)?,
+        })
+    }
+
+    /// Whether the recorded leader still runs. Pids are reused, so after a
+    /// reboot or a long time `pgid` may well lead an unrelated group; the
+    /// leader's start time and command line tell them apart.
+    pub fn is_running(&self) -> bool {
+        procfs::stat(self.pgid)
+            .is_some_and(|stat| stat.pgrp == self.pgid && stat.start_time == self.start_time)
+            && procfs::cmdline(self.pgid).is_some_and(|cmdline| cmdline == self.cmdline)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionRecord {
+    /// The cdi process holding the lock.
+    pid: u32,
+    groups: Vec<GroupRecord>,
+}
+
+/// Exclusive lock on the session of one `.cdi.kdl`, held with `flock` on
+/// `<hash>.lock` next to the client socket. The kernel drops the lock when
+/// cdi exits, however it exits, so a locked file always means a live
+/// session. The file records the process group of every running service
+/// and is never removed, so groups orphaned by a crashed session are found
+/// even when a later start gives up on them.
+pub struct SessionLock {
+    file: File,
+    /// Live groups recorded by a previous session that didn't clean up.
+    leftovers: Vec<GroupRecord>,
+    /// What the file records.
+    groups: Vec<GroupRecord>,
+}
+
+impl SessionLock {
+    /// Fails if another session holds the lock for `project_dir`.
+    pub fn acquire(project_dir: &Path) -> Result<Self> {
+        let path = runtime_file(project_dir, "lock");
+        create_runtime_dir(&path)?;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        Self::lock(file)
+    }
+
+    /// Takes over the lock a parent acquired and passed down as `fd`, see
+    /// `daemon::spawn`. Locking the same open file again succeeds.
+    pub fn inherit(fd: RawFd) -> Result<Self> {
+        let file = unsafe { File::from_raw_fd(fd) };
+        // Kept from the services, which would hold the lock otherwise.
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Self::lock(file)
+    }
+
+    fn lock(mut file: File) -> Result<Self> {
+        let mut contents = String::new();
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == -1 {
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::WouldBlock {
+                return Err(error.into());
+            }
+
+            file.read_to_string(&mut contents)?;
+            match serde_json::from_str::<SessionRecord>(&contents) {
+                Ok(record) => bail!(
+                    "another cdi session (pid {}) is already running for this config",
+                    record.pid
+                ),
+                Err(_) => bail!("another cdi session is already running for this config"),
+            }
+        }
+
+        // Shared with the parent when inherited, which left it at the end.
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut contents)?;
+        let leftovers = serde_json::from_str::<SessionRecord>(&contents)
+            .map(|record| record.groups)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(GroupRecord::is_running)
+            .collect();
+
+        let mut lock = Self {
+            file,
+            leftovers,
+            groups: Vec::new(),
+        };
+        // Leftovers stay on record until they are killed or adopted.
+        let leftovers = lock.leftovers.clone();
+        lock.write(&leftovers)?;
+
+        Ok(lock)
+    }
+
+    /// Process groups of a crashed session that are still running.
+    pub fn leftovers(&self) -> &[GroupRecord] {
+        &self.leftovers
+    }
+
+    /// Records the process groups of the running services, replacing what
+    /// was recorded before. Groups whose leader already exited can't be
+    /// told apart from a reused pgid later, so they are left out.
+    pub fn record<'a>(
+        &mut self,
+        processes: impl IntoIterator<Item = &'a ProcessInfo>,
+    ) -> io::Result<()> {
+        let groups: Vec<GroupRecord> = processes
+            .into_iter()
+            .filter_map(|info| GroupRecord::new(&info.name, &info.command, info.pid? as u32))
+            .collect();
+
+        self.write(&groups)
+    }
+
+    /// Whether a group was recorded before its leader finished `exec`, so
+    /// `record` should run again to fill in the command line.
+    pub fn needs_record(&self) -> bool {
+        self.groups.iter().any(|group| group.cmdline.is_empty())
+    }
+
+    fn write(&mut self, groups: &[GroupRecord]) -> io::Result<()> {
+        self.groups = groups.to_vec();
+        let record = SessionRecord {
+            pid: std::process::id(),
+            groups: groups.to_vec(),
+        };
+
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        serde_json::to_writer(&mut self.file, &record)?;
+        self.file.flush()
+    }
+}
+
+impl AsRawFd for SessionLock {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// Whether any process of group `pgid` is still running.
+pub fn group_alive(pgid: u32) -> bool {
+    pgid > 1 && unsafe { libc::kill(-(pgid as libc::pid_t), 0) } == 0
+}
+
+/// Terminates `groups`, escalating to `SIGKILL` for those still running
+/// after a grace period. Resolves once they are gone or were killed.
+pub async fn kill_groups(groups: &[GroupRecord]) {
+    let signal = |signal| {
+        for group in groups.iter().filter(|group| group_alive(group.pgid)) {
+            unsafe { libc::kill(-(group.pgid as libc::pid_t), signal) };
+        }
+    };
+
+    signal(libc::SIGTERM);
+    let deadline = Instant::now() + KILL_GRACE;
+    while Instant::now() < deadline {
+        if !groups.iter().any(|group| group_alive(group.pgid)) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    signal(libc::SIGKILL);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::{fs::PermissionsExt, process::CommandExt},
+        process::Command,
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn refuses_second_session() {
+        let project_dir = env::temp_dir().join(format!("cdi-session-test-{}", std::process::id()));
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let lock = SessionLock::acquire(&project_dir).unwrap();
+        assert!(lock.leftovers().is_empty());
+        assert!(SessionLock::acquire(&project_dir).is_err());
+
+        drop(lock);
+        assert!(SessionLock::acquire(&project_dir).is_ok());
+        let _ = fs::remove_file(runtime_file(&project_dir, "lock"));
+        let _ = fs::remove_dir(&project_dir);
+    }
+
+    #[test]
+    fn skips_reused_process_groups() {
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        // The command line only shows once `exec` is done.
+        while procfs::cmdline(child.id()).is_some_and(|cmdline| cmdline.is_empty()) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let group = GroupRecord::new("sleeper", "sleep 30", child.id()).unwrap();
+        assert_eq!(group.cmdline, "sleep 30");
+        assert!(group.is_running());
+
+        // Same pgid, but led by a process started at another time or
+        // running something else: the pgid was reused.
+        let restarted = GroupRecord {
+            start_time: group.start_time + 1,
+            ..group.clone()
+        };
+        let other = GroupRecord {
+            name: "api".into(),
+            cmdline: "node server.js".into(),
+            ..group.clone()
+        };
+        assert!(!restarted.is_running());
+        assert!(!other.is_running());
+
+        let project_dir = env::temp_dir().join(format!("cdi-leftover-test-{}", std::process::id()));
+        fs::create_dir_all(&project_dir).unwrap();
+        let mut lock = SessionLock::acquire(&project_dir).unwrap();
+        lock.write(&[restarted, group.clone(), other]).unwrap();
+        drop(lock);
+
+        let lock = SessionLock::acquire(&project_dir).unwrap();
+        assert_eq!(lock.leftovers(), [group]);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        drop(lock);
+        let _ = fs::remove_file(runtime_file(&project_dir, "lock"));
+        let _ = fs::remove_dir(&project_dir);
+    }
+
+    #[test]
+    fn refuses_shared_runtime_dir() {
+        let dir = env::temp_dir().join(format!("cdi-runtime-test-{}", std::process::id()));
+        let file = dir.join("x.sock");
+
+        create_runtime_dir(&file).unwrap();
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(create_runtime_dir(&file).is_err());
+
+        let _ = fs::remove_dir(&dir);
+    }
+}
@@ -0,0 +1,122 @@
+use std::io;
+
+use libc::c_int;
+
+use crate::{procfs, store::StoreHandle};
+
+/// Signals known by name: name without the `SIG` prefix, number, and what
+/// services commonly do on receiving it.
+pub const SIGNALS: [(&str, c_int, &str); 9] = [
+    ("HUP", libc::SIGHUP, "reload configuration"),
+    ("INT", libc::SIGINT, "interrupt, like Ctrl-C"),
+    ("QUIT", libc::SIGQUIT, "quit and dump core"),
+    ("USR1", libc::SIGUSR1, "user defined, often dump state"),
+    ("USR2", libc::SIGUSR2, "user defined"),
+    ("TERM", libc::SIGTERM, "terminate"),
+    ("KILL", libc::SIGKILL, "kill, can't be handled"),
+    ("STOP", libc::SIGSTOP, "pause"),
+    ("CONT", libc::SIGCONT, "resume after STOP"),
+];
+
+/// Parses a signal name, with or without `SIG` and in any case, or number.
+pub fn parse_signal(name: &str) -> Option<c_int> {
+    if let Ok(number) = name.parse::<c_int>() {
+        return (1..=libc::SIGRTMAX()).contains(&number).then_some(number);
+    }
+
+    let name = name.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS
+        .iter()
+        .find(|(known, ..)| *known == name)
+        .map(|(_, signal, _)| *signal)
+}
+
+/// Sends `signal` to the service `process_id`: to its whole process group
+/// like stopping it does, or with `group` false only to the process cdi
+/// started.
+pub fn signal_service(
+    store: &StoreHandle,
+    process_id: u64,
+    signal: c_int,
+    group: bool,
+) -> io::Result<()> {
+    let pid = store
+        .process(process_id)
+        .and_then(|info| info.pid)
+        .ok_or_else(|| io::Error::other("service is not running"))?;
+
+    if group {
+        kill_group(pid as u32, signal)
+    } else {
+        kill(pid as u32, signal)
+    }
+}
+
+/// Sends `signal` to `pid` if it is still part of the process tree of the
+/// service `process_id`. Pids are reused, so one seen at the last sample
+/// may belong to something else entirely by now.
+pub fn signal_descendant(
+    store: &StoreHandle,
+    process_id: u64,
+    pid: u32,
+    signal: c_int,
+) -> io::Result<()> {
+    let root = store
+        .process(process_id)
+        .and_then(|info| info.pid)
+        .ok_or_else(|| io::Error::other("service is not running"))?;
+
+    let all = procfs::processes();
+    if !procfs::tree(root as u32, &all)
+        .iter()
+        .any(|(proc, _)| proc.pid == pid)
+    {
+        return Err(io::Error::other(format!(
+            "{pid} is no longer part of the service"
+        )));
+    }
+
+    kill(pid, signal)
+}
+
+fn kill(pid: u32, signal: c_int) -> io::Result<()> {
+    let rc = unsafe { libc::kill(pid as libc::pid_t, signal) };
+    if rc == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn kill_group(pgid: u32, signal: c_int) -> io::Result<()> {
+    let rc = unsafe { libc::kill(-(pgid as libc::pid_t), signal) };
+    if rc == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_signal_names() {
+        assert_eq!(parse_signal("HUP"), Some(libc::SIGHUP));
+        assert_eq!(parse_signal("sigusr1"), Some(libc::SIGUSR1));
+        assert_eq!(parse_signal("SigTerm"), Some(libc::SIGTERM));
+        assert_eq!(parse_signal("9"), Some(libc::SIGKILL));
+        assert_eq!(
+            parse_signal(&libc::SIGRTMAX().to_string()),
+            Some(libc::SIGRTMAX())
+        );
+
+        assert_eq!(parse_signal("0"), None);
+        assert_eq!(parse_signal(&(libc::SIGRTMAX() + 1).to_string()), None);
+        assert_eq!(parse_signal("-15"), None);
+        assert_eq!(parse_signal("SIG"), None);
+        assert_eq!(parse_signal("WINCH"), None);
+    }
+}
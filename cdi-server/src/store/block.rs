@@ -1,44 +1,100 @@
-// #### File: `block.rs` (NEW)
-// - [ ] `LogLineMeta` struct (per-line, ~40 bytes)
-//     - `id: u64`
-//     - `timestamp: u128`
-//     - `stream: Stream`
-//     - `content_start: u32` (offset into string_data)
-//     - `content_len: u32`
-// - [ ] `Block` struct
-//     - `process_id: u64` (block header - shared by all lines)
-//     - `session_id: u64` (block header - shared by all lines)
-//     - `string_data: String` (concatenated log content)
-//     - `lines: Vec<LogLineMeta>`
-// - [ ] `Block::new()` with pre-allocated capacity
-//     - `BLOCK_LINE_CAP = 256`
-//     - `BLOCK_STRING_CAP = 64KB`
-// - [ ] `Block::push(meta, content)` - append line
-// - [ ] `Block::is_full()` - check if block should be sealed
-// - [ ] `Block::get_content(&LogLineMeta) -> &str`
-// - [ ] `Block::iter() -> impl Iterator<Item = (&LogLineMeta, &str)>`
-// - [ ] Derive `Clone` for copy-on-write support
-
-use cdi_shared::log::Stream;
-
-const BLOCK_LINE_CAP: usize = 128;
-const BLOCK_STRING_INIT_SIZE: usize = 32 * 1024; // 64KB
+use std::io;
+use std::sync::Arc;
 
-#[derive(Clone)]
-struct LogLineData {
-    id: u64,
-    timestamp: u128,
-    stream: Stream,
+use cdi_shared::log::{Level, LogLine, ReadTime, Stream};
+
+use super::fields::FieldIndex;
+
+pub const BLOCK_CAP: usize = 128;
+const BLOCK_STRING_INIT_SIZE: usize = 32 * 1024; // 32KB
+
+/// Per-line metadata. Content lives in the owning block's string data.
+#[derive(Clone, Debug)]
+pub struct LogLineMeta {
+    pub id: u64,
+    /// Wall clock, milliseconds since the Unix epoch.
+    pub timestamp: u128,
+    /// Monotonic microseconds since the session started.
+    pub elapsed: u64,
+    /// Read order across all processes, see `ReadTime`.
+    pub seq: u64,
+    pub stream: Stream,
+    /// Detected severity, `None` if the line carries no level marker.
+    pub level: Option<Level>,
+    /// Flattened fields if the content is a JSON object, as
+    /// `fields::flatten` produced them when the line was stored.
+    pub fields: Option<Arc<[(String, String)]>>,
     content_start: u32,
     content_len: u32,
 }
 
+impl LogLineMeta {
+    pub fn new(id: u64, time: ReadTime, stream: Stream, level: Option<Level>) -> Self {
+        Self {
+            id,
+            timestamp: time.timestamp,
+            elapsed: time.elapsed,
+            seq: time.seq,
+            stream,
+            level,
+            fields: None,
+            content_start: 0,
+            content_len: 0,
+        }
+    }
+
+    /// The line as handed to clients.
+    pub fn to_log_line(&self, process_id: u64, session_id: u64, content: &str) -> LogLine {
+        LogLine {
+            id: self.id,
+            process_id,
+            session_id,
+            timestamp: self.timestamp,
+            seq: self.seq,
+            stream: self.stream.clone(),
+            level: self.level,
+            content: content.to_string(),
+        }
+    }
+
+    /// Whether the content is a JSON object.
+    #[inline]
+    pub fn is_structured(&self) -> bool {
+        self.fields.is_some()
+    }
+
+    /// Severity used for filtering. Unclassified lines count as info.
+    #[inline]
+    pub fn severity(&self) -> Level {
+        self.level.unwrap_or(Level::Info)
+    }
+
+    #[inline]
+    fn range(&self) -> std::ops::Range<usize> {
+        let start = self.content_start as usize;
+        start..start + self.content_len as usize
+    }
+}
+
+#[derive(Clone)]
+enum BlockData {
+    /// Still accepting lines.
+    Open(String),
+    /// Full and immutable. The string data is LZ4 compressed and only
+    /// decompressed when a query needs it (see `BlockCache`).
+    Sealed(Arc<[u8]>),
+}
+
 #[derive(Clone)]
 pub struct Block {
-    process_id: u64,
-    session_id: u64,
-    data: String,
-    lines: Vec<LogLineData>,
+    pub process_id: u64,
+    pub session_id: u64,
+    data: BlockData,
+    lines: Vec<LogLineMeta>,
+    /// Highest `LogLineMeta::severity` in the block.
+    max_level: Level,
+    /// Only allocated once a structured line is pushed.
+    fields: Option<FieldIndex>,
 }
 
 impl Block {
@@ -46,15 +102,218 @@ impl Block {
         Self {
             process_id,
             session_id,
-            data: String::with_capacity(BLOCK_STRING_INIT_SIZE), // 64KB
-            lines: Vec::with_capacity(BLOCK_LINE_CAP),
+            data: BlockData::Open(String::with_capacity(BLOCK_STRING_INIT_SIZE)),
+            lines: Vec::with_capacity(BLOCK_CAP),
+            max_level: Level::Trace,
+            fields: None,
         }
     }
 
-    pub fn push() {}
-    pub fn is_full() {}
-    pub fn get_content() {}
-    // pub fn iter() -> impl Iterator<Item = (&LogLineData, &str)> {}
+    /// Appends a line. Panics if the block has already been sealed.
+    pub fn push(&mut self, mut meta: LogLineMeta, content: &str) {
+        let BlockData::Open(data) = &mut self.data else {
+            panic!("push into sealed block");
+        };
+
+        meta.content_start = data.len() as u32;
+        meta.content_len = content.len() as u32;
+        data.push_str(content);
+        self.max_level = self.max_level.max(meta.severity());
+        self.lines.push(meta);
+    }
+
+    /// Appends `content` as another line of the last entry, which must be on
+    /// `stream`. Returns the entry's id and its level before the change, or
+    /// `None` if the block is sealed or ends with another stream's entry.
+    pub fn extend_last(
+        &mut self,
+        stream: &Stream,
+        content: &str,
+        level: Option<Level>,
+    ) -> Option<(u64, Option<Level>)> {
+        let BlockData::Open(data) = &mut self.data else {
+            return None;
+        };
+        let meta = self
+            .lines
+            .last_mut()
+            .filter(|meta| meta.stream == *stream)?;
+
+        data.push('\n');
+        data.push_str(content);
+        meta.content_len += 1 + content.len() as u32;
+
+        let previous = meta.level;
+        meta.level = meta.level.max(level);
+        self.max_level = self.max_level.max(meta.severity());
+
+        Some((meta.id, previous))
+    }
+
+    /// Adds the flattened fields of the last pushed line to the index.
+    pub fn index_fields(&mut self, fields: &[(String, String)]) {
+        let index = self.fields.get_or_insert_with(FieldIndex::new);
+        for (key, value) in fields {
+            index.insert(key, value);
+        }
+    }
+
+    /// `false` if no line in the block can have `key` = `value`.
+    pub fn may_contain_field(&self, key: &str, value: &str) -> bool {
+        self.fields
+            .as_ref()
+            .is_some_and(|index| index.may_contain(key, value))
+    }
+
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.lines.len() >= BLOCK_CAP
+    }
+
+    #[inline]
+    pub fn is_sealed(&self) -> bool {
+        matches!(self.data, BlockData::Sealed(_))
+    }
+
+    /// Compresses the string data. The block is immutable afterwards.
+    pub fn seal(&mut self) {
+        if let BlockData::Open(data) = &self.data {
+            let compressed = lz4_flex::compress_prepend_size(data.as_bytes());
+            self.data = BlockData::Sealed(compressed.into());
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    #[inline]
+    pub fn lines(&self) -> &[LogLineMeta] {
+        &self.lines
+    }
+
+    #[inline]
+    pub fn max_level(&self) -> Level {
+        self.max_level
+    }
+
+    #[inline]
+    pub fn first_id(&self) -> Option<u64> {
+        self.lines.first().map(|meta| meta.id)
+    }
+
+    #[inline]
+    pub fn last_id(&self) -> Option<u64> {
+        self.lines.last().map(|meta| meta.id)
+    }
+
+    /// Approximate heap bytes held by the block.
+    pub fn heap_size(&self) -> usize {
+        let data = match &self.data {
+            BlockData::Open(data) => data.capacity(),
+            BlockData::Sealed(compressed) => compressed.len(),
+        };
+        let index = self.fields.as_ref().map_or(0, FieldIndex::heap_size);
+        let fields: usize = self
+            .lines
+            .iter()
+            .filter_map(|meta| meta.fields.as_deref())
+            .flatten()
+            .map(|(key, value)| size_of::<(String, String)>() + key.len() + value.len())
+            .sum();
+
+        data + self.lines.capacity() * size_of::<LogLineMeta>() + index + fields
+    }
+
+    /// Uncompressed string data, if the block is still open.
+    #[inline]
+    pub fn text(&self) -> Option<&str> {
+        match &self.data {
+            BlockData::Open(data) => Some(data),
+            BlockData::Sealed(_) => None,
+        }
+    }
+
+    /// Decompresses the string data of a sealed block. Open blocks are
+    /// copied as-is. Fails with `InvalidData` if the data is corrupt.
+    pub fn decompress(&self) -> io::Result<Arc<str>> {
+        match &self.data {
+            BlockData::Open(data) => Ok(data.as_str().into()),
+            BlockData::Sealed(compressed) => {
+                let bytes = lz4_flex::decompress_size_prepended(compressed)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let text = String::from_utf8(bytes)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Ok(text.into())
+            }
+        }
+    }
+
+    /// Content of `meta` within `text`, where `text` is either `Block::text`
+    /// or the result of `Block::decompress`. Empty if `text` doesn't hold
+    /// it, e.g. in place of corrupt data.
+    #[inline]
+    pub fn get_content<'a>(&self, text: &'a str, meta: &LogLineMeta) -> &'a str {
+        text.get(meta.range()).unwrap_or_default()
+    }
+
+    pub fn iter<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (&'a LogLineMeta, &'a str)> {
+        self.lines
+            .iter()
+            .map(move |meta| (meta, text.get(meta.range()).unwrap_or_default()))
+    }
 }
 
-// struct LogLineContext<'a> {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_roundtrip() {
+        let mut block = Block::new(1, 1);
+        for id in 0..BLOCK_CAP as u64 {
+            block.push(
+                LogLineMeta::new(id, ReadTime::now(), Stream::Stdout, None),
+                &format!("DEBUG request {id} handled"),
+            );
+        }
+        assert!(block.is_full());
+
+        let before: Vec<String> = {
+            let text = block.text().unwrap();
+            block.iter(text).map(|(_, line)| line.to_string()).collect()
+        };
+
+        block.seal();
+        assert!(block.is_sealed());
+        assert!(block.text().is_none());
+
+        let text = block.decompress().unwrap();
+        let after: Vec<String> = block
+            .iter(&text)
+            .map(|(_, line)| line.to_string())
+            .collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn corrupt_block_reads_empty() {
+        let mut block = Block::new(1, 1);
+        block.push(
+            LogLineMeta::new(0, ReadTime::now(), Stream::Stdout, None),
+            "listening",
+        );
+        block.data = BlockData::Sealed(lz4_flex::compress_prepend_size(&[0xff; 9]).into());
+
+        let error = block.decompress().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let contents: Vec<&str> = block.iter("").map(|(_, line)| line).collect();
+        assert_eq!(contents, [""]);
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use super::block::Block;
+
+pub const DEFAULT_CACHE_BLOCKS: usize = 16;
+
+/// (process_id, first line id) uniquely identifies a sealed block.
+type BlockKey = (u64, u64);
+
+/// Small LRU of decompressed sealed blocks, shared by every snapshot taken
+/// from the store. Keeps the tail and recently scrolled regions from being
+/// decompressed on every render.
+pub struct BlockCache {
+    capacity: usize,
+    entries: Mutex<VecDeque<(BlockKey, Arc<str>)>>,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Decompressed text of a sealed block, from cache if possible.
+    pub fn get(&self, block: &Block) -> Arc<str> {
+        let key = (block.process_id, block.first_id().unwrap_or(0));
+        let mut entries = self.entries.lock().unwrap();
+
+        if let Some(pos) = entries.iter().position(|(k, _)| *k == key) {
+            let entry = entries.remove(pos).unwrap();
+            let text = entry.1.clone();
+            entries.push_front(entry);
+            return text;
+        }
+
+        // Decompress without holding the lock so other readers aren't stalled.
+        drop(entries);
+        // Corrupt data can't be recovered; its entries read as empty.
+        let text = block.decompress().unwrap_or_else(|_| Arc::from(""));
+
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.iter().any(|(k, _)| *k == key) {
+            entries.push_front((key, text.clone()));
+            entries.truncate(self.capacity);
+        }
+
+        text
+    }
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_BLOCKS)
+    }
+}
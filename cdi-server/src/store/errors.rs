@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+
+use cdi_shared::{ansi::strip_ansi, detect::DetectedError, log::Stream};
+
+use crate::detect::{Detection, DetectorSet};
+
+/// Diagnostics kept per process; the oldest are dropped first.
+const MAX_ERRORS: usize = 1000;
+
+/// Runs the detectors over a process's output. Each stream has its own
+/// detector state, so interleaved stdout and stderr don't split a
+/// multi-line diagnostic.
+pub struct ProcessErrors {
+    stdout: StreamDetectors,
+    stderr: StreamDetectors,
+    errors: VecDeque<DetectedError>,
+}
+
+#[derive(Default)]
+struct StreamDetectors {
+    detectors: DetectorSet,
+    /// Diagnostic continuation lines are added to.
+    current: Option<u64>,
+}
+
+impl ProcessErrors {
+    pub fn new() -> Self {
+        Self {
+            stdout: StreamDetectors::default(),
+            stderr: StreamDetectors::default(),
+            errors: VecDeque::new(),
+        }
+    }
+
+    /// Feeds one stored line. `next_id` is the store-wide error counter.
+    pub fn feed(
+        &mut self,
+        next_id: &mut u64,
+        process_id: u64,
+        line_id: u64,
+        timestamp: u128,
+        stream: &Stream,
+        content: &str,
+    ) {
+        let state = match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        };
+
+        let text = strip_ansi(content);
+        match state.detectors.feed(text.trim_end()) {
+            (Detection::Start(diagnostic), source) => {
+                let id = *next_id;
+                *next_id += 1;
+                state.current = Some(id);
+
+                self.errors.push_back(DetectedError {
+                    id,
+                    process_id,
+                    source: source.to_string(),
+                    severity: diagnostic.severity,
+                    message: diagnostic.message,
+                    location: diagnostic.location,
+                    first_line_id: line_id,
+                    last_line_id: line_id,
+                    timestamp,
+                });
+                if self.errors.len() > MAX_ERRORS {
+                    self.errors.pop_front();
+                }
+            }
+            (Detection::Continue(update), _) => {
+                let Some(error) = state
+                    .current
+                    .and_then(|id| self.errors.iter_mut().rev().find(|error| error.id == id))
+                else {
+                    return;
+                };
+
+                error.last_line_id = line_id;
+                if let Some(message) = update.message {
+                    error.message = message;
+                }
+                if update.location.is_some() {
+                    error.location = update.location;
+                }
+            }
+            (Detection::None | Detection::Ignore, _) => state.current = None,
+        }
+    }
+
+    /// Oldest first.
+    pub fn errors(&self) -> &VecDeque<DetectedError> {
+        &self.errors
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use cdi_shared::{
+    ansi::{strip_ansi, to_html},
+    log::{LogLine, ProcessInfo, Stream},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{block::LogLineMeta, query::MergedView};
+
+/// File formats logs can be exported in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Text with the escape sequences stripped.
+    Plain,
+    /// Text as printed, colors included.
+    Ansi,
+    /// One JSON object per entry, with its metadata.
+    Ndjson,
+    /// A standalone page with the colors converted.
+    Html,
+}
+
+impl ExportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Plain => "plain",
+            ExportFormat::Ansi => "ansi",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    /// File extension, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Plain => "txt",
+            ExportFormat::Ansi => "log",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "plain" | "text" | "txt" => Ok(ExportFormat::Plain),
+            "ansi" => Ok(ExportFormat::Ansi),
+            "ndjson" | "json" | "jsonl" => Ok(ExportFormat::Ndjson),
+            "html" => Ok(ExportFormat::Html),
+            _ => Err(format!(
+                "unknown format '{s}', expected plain, ansi, ndjson or html"
+            )),
+        }
+    }
+}
+
+/// An NDJSON record: the line with the name of its service.
+#[derive(Serialize)]
+struct Record<'a> {
+    service: &'a str,
+    #[serde(flatten)]
+    line: LogLine,
+}
+
+/// Writes the entries of `view` to `out` in `format`, oldest first, and
+/// returns how many were written. `processes` are the services in the view;
+/// with more than one, text lines are prefixed with the service name.
+pub fn export(
+    view: &MergedView,
+    processes: &[ProcessInfo],
+    session_id: u64,
+    format: ExportFormat,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let name = |process_id: u64| {
+        processes
+            .iter()
+            .find(|info| info.id == process_id)
+            .map_or("", |info| info.name.as_str())
+    };
+    // Width of the service column, 0 without one.
+    let width = match processes {
+        [_, _, ..] => processes
+            .iter()
+            .map(|info| info.name.len())
+            .max()
+            .unwrap_or(0),
+        _ => 0,
+    };
+
+    if format == ExportFormat::Html {
+        let title: Vec<&str> = processes.iter().map(|info| info.name.as_str()).collect();
+        write!(
+            out,
+            "<!doctype html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>cdi: {}</title>\n<style>\n{HTML_STYLE}</style>\n</head>\n<body>\n<main>\n",
+            to_html(&title.join(", "))
+        )?;
+    }
+
+    let mut count = 0;
+    for (process_id, meta, content) in view.iter() {
+        count += 1;
+        let service = name(process_id);
+
+        match format {
+            ExportFormat::Plain | ExportFormat::Ansi => {
+                let content = match format {
+                    ExportFormat::Plain => strip_ansi(content),
+                    _ => content.into(),
+                };
+                for (idx, row) in content.split('\n').enumerate() {
+                    if width > 0 {
+                        // Only the first row of an entry carries the name.
+                        let label = if idx == 0 { service } else { "" };
+                        write!(out, "{label:<width$} | ")?;
+                    }
+                    writeln!(out, "{row}")?;
+                }
+            }
+            ExportFormat::Ndjson => {
+                let record = Record {
+                    service,
+                    line: meta.to_log_line(process_id, session_id, content),
+                };
+                serde_json::to_writer(&mut *out, &record)?;
+                writeln!(out)?;
+            }
+            ExportFormat::Html => write_html(out, meta, content, service, width)?,
+        }
+    }
+
+    if format == ExportFormat::Html {
+        writeln!(out, "</main>\n</body>\n</html>")?;
+    }
+
+    Ok(count)
+}
+
+fn write_html(
+    out: &mut impl Write,
+    meta: &LogLineMeta,
+    content: &str,
+    service: &str,
+    width: usize,
+) -> io::Result<()> {
+    let stream = match meta.stream {
+        Stream::Stdout => "stdout",
+        Stream::Stderr => "stderr",
+    };
+    match meta.level {
+        Some(level) => write!(out, "<div class=\"entry {stream} {}\">", level.as_str())?,
+        None => write!(out, "<div class=\"entry {stream}\">")?,
+    }
+    if width > 0 {
+        write!(
+            out,
+            "<span class=\"service\">{:<width$}</span> ",
+            to_html(service)
+        )?;
+    }
+    writeln!(out, "{}</div>", to_html(content))
+}
+
+/// Styles of exported pages, with the web UI's palette.
+const HTML_STYLE: &str = "\
+body { margin: 0; background: #1d1f21; color: #d0d0d0; }
+main { padding: 8px 0; font: 13px ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; }
+.entry { white-space: pre-wrap; word-break: break-all; padding: 0 10px; border-left: 2px solid transparent; }
+.entry.stderr { border-left-color: #cc6666; }
+.service { color: #81a2be; }
+.ansi-fg-0 { color: #1d1f21; } .ansi-fg-1 { color: #cc6666; } .ansi-fg-2 { color: #b5bd68; } .ansi-fg-3 { color: #f0c674; }
+.ansi-fg-4 { color: #81a2be; } .ansi-fg-5 { color: #b294bb; } .ansi-fg-6 { color: #8abeb7; } .ansi-fg-7 { color: #c5c8c6; }
+.ansi-fg-8 { color: #666666; } .ansi-fg-9 { color: #d54e53; } .ansi-fg-10 { color: #b9ca4a; } .ansi-fg-11 { color: #e7c547; }
+.ansi-fg-12 { color: #7aa6da; } .ansi-fg-13 { color: #c397d8; } .ansi-fg-14 { color: #70c0b1; } .ansi-fg-15 { color: #eaeaea; }
+.ansi-bg-0 { background: #1d1f21; } .ansi-bg-1 { background: #cc6666; } .ansi-bg-2 { background: #b5bd68; } .ansi-bg-3 { background: #f0c674; }
+.ansi-bg-4 { background: #81a2be; } .ansi-bg-5 { background: #b294bb; } .ansi-bg-6 { background: #8abeb7; } .ansi-bg-7 { background: #c5c8c6; }
+.ansi-bg-8 { background: #666666; } .ansi-bg-9 { background: #d54e53; } .ansi-bg-10 { background: #b9ca4a; } .ansi-bg-11 { background: #e7c547; }
+.ansi-bg-12 { background: #7aa6da; } .ansi-bg-13 { background: #c397d8; } .ansi-bg-14 { background: #70c0b1; } .ansi-bg-15 { background: #eaeaea; }
+";
+
+#[cfg(test)]
+mod tests {
+    use cdi_shared::log::ReadTime;
+
+    use super::*;
+    use crate::store::{LogQuery, MemoryStore, block::BLOCK_CAP};
+
+    #[test]
+    fn exports_merged_view() {
+        let api = ProcessInfo::new("api".into(), "cargo run".into(), None);
+        let web = ProcessInfo::new("web".into(), "pnpm dev".into(), None);
+        let processes = vec![api.clone(), web.clone()];
+        let mut store = MemoryStore::new(processes.clone(), 7, 4 * BLOCK_CAP);
+
+        store.append(
+            api.id,
+            Stream::Stdout,
+            "\x1b[32mready\x1b[0m",
+            ReadTime::now(),
+        );
+        store.append(web.id, Stream::Stderr, "error: <boom>", ReadTime::now());
+        store.append(api.id, Stream::Stdout, "debug: skipped", ReadTime::now());
+
+        let snapshots = [
+            store.snapshot(api.id).unwrap(),
+            store.snapshot(web.id).unwrap(),
+        ];
+        let view = MergedView::new(&snapshots).filter(LogQuery::new().search("R"));
+        let export = |format| {
+            let mut out = Vec::new();
+            let count = super::export(&view, &processes, 7, format, &mut out).unwrap();
+            (count, String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(
+            export(ExportFormat::Plain),
+            (2, "api | ready\nweb | error: <boom>\n".to_string())
+        );
+
+        let (_, ndjson) = export(ExportFormat::Ndjson);
+        let record: serde_json::Value =
+            serde_json::from_str(ndjson.lines().last().unwrap()).unwrap();
+        assert_eq!(record["service"], "web");
+        assert_eq!(record["session_id"], 7);
+        assert_eq!(record["stream"], "stderr");
+        assert_eq!(record["level"], "error");
+
+        let (_, html) = export(ExportFormat::Html);
+        assert!(html.contains("<span class=\"ansi-fg-2\">ready</span>"));
+        assert!(html.contains("error: &lt;boom&gt;"));
+    }
+
+    #[test]
+    fn exports_one_service() {
+        let api = ProcessInfo::new("api".into(), "cargo run".into(), None);
+        let processes = vec![api.clone()];
+        let mut store = MemoryStore::new(processes.clone(), 7, 4 * BLOCK_CAP);
+
+        for (stream, line) in [
+            (
+                Stream::Stderr,
+                "\x1b[31mpanicked at src/main.rs:3:5:\x1b[0m",
+            ),
+            (Stream::Stderr, "  boom"),
+            (Stream::Stdout, "ready"),
+        ] {
+            store.append(api.id, stream, line, ReadTime::now());
+        }
+
+        let snapshots = [store.snapshot(api.id).unwrap()];
+        let view = MergedView::new(&snapshots);
+        let export = |format| {
+            let mut out = Vec::new();
+            let count = super::export(&view, &processes, 7, format, &mut out).unwrap();
+            (count, String::from_utf8(out).unwrap())
+        };
+
+        // No service column, and entries keep their continuation lines.
+        assert_eq!(
+            export(ExportFormat::Plain),
+            (
+                2,
+                "panicked at src/main.rs:3:5:\n  boom\nready\n".to_string()
+            )
+        );
+        assert_eq!(
+            export(ExportFormat::Ansi).1,
+            "\x1b[31mpanicked at src/main.rs:3:5:\x1b[0m\n  boom\nready\n"
+        );
+
+        let (count, ndjson) = export(ExportFormat::Ndjson);
+        assert_eq!(count, ndjson.lines().count());
+        let record: serde_json::Value =
+            serde_json::from_str(ndjson.lines().next().unwrap()).unwrap();
+        assert_eq!(record["service"], "api");
+        assert_eq!(record["stream"], "stderr");
+
+        let (_, html) = export(ExportFormat::Html);
+        assert!(html.starts_with("<!doctype html>"));
+        assert!(html.contains("<title>cdi: api</title>"));
+        assert!(html.ends_with("</html>\n"));
+        assert!(!html.contains("class=\"service\""));
+        assert_eq!(html.matches("<div class=\"entry stderr").count(), 1);
+    }
+
+    #[test]
+    fn parses_format_names() {
+        assert_eq!("txt".parse(), Ok(ExportFormat::Plain));
+        assert_eq!("ansi".parse(), Ok(ExportFormat::Ansi));
+        assert_eq!("jsonl".parse(), Ok(ExportFormat::Ndjson));
+        assert_eq!("html".parse(), Ok(ExportFormat::Html));
+        assert!("xml".parse::<ExportFormat>().is_err());
+
+        for format in [ExportFormat::Plain, ExportFormat::Ndjson] {
+            assert_eq!(format.to_string().parse(), Ok(format));
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use cdi_shared::log::Level;
+use serde_json::{Map, Value};
+
+use super::level;
+
+const TIMESTAMP_KEYS: [&str; 4] = ["timestamp", "time", "ts", "@timestamp"];
+const MESSAGE_KEYS: [&str; 3] = ["msg", "message", "fields.message"];
+
+/// Top-level and nested scalar fields of a JSON log line, flattened to
+/// dotted keys (`fields.req_id`). `None` if the line isn't a JSON object.
+pub fn flatten(content: &str) -> Option<Vec<(String, String)>> {
+    let trimmed = content.trim();
+    if !trimmed.starts_with('{') || !trimmed.ends_with('}') {
+        return None;
+    }
+
+    let object: Map<String, Value> = serde_json::from_str(trimmed).ok()?;
+    let mut fields = Vec::with_capacity(object.len());
+    flatten_into(&mut fields, "", object);
+
+    Some(fields)
+}
+
+fn flatten_into(fields: &mut Vec<(String, String)>, prefix: &str, object: Map<String, Value>) {
+    for (key, value) in object {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match value {
+            Value::Object(object) => flatten_into(fields, &key, object),
+            Value::String(s) => fields.push((key, s)),
+            other => fields.push((key, other.to_string())),
+        }
+    }
+}
+
+/// Whether flattened `key` is addressed by a query for `name`: either the
+/// full dotted path or its last segment, so `req_id` finds `fields.req_id`.
+#[inline]
+pub fn key_matches(key: &str, name: &str) -> bool {
+    key == name || key.rsplit('.').next() == Some(name)
+}
+
+/// A JSON log line split into the parts the TUI renders as
+/// `timestamp level message key=value...`.
+pub struct StructuredLine {
+    pub timestamp: Option<String>,
+    pub level: Option<Level>,
+    pub message: Option<String>,
+    pub fields: Vec<(String, String)>,
+}
+
+impl StructuredLine {
+    /// Splits the fields `flatten` produced for a line.
+    pub fn new(fields: &[(String, String)]) -> Self {
+        let mut line = StructuredLine {
+            timestamp: None,
+            level: None,
+            message: None,
+            fields: Vec::new(),
+        };
+
+        for (key, value) in fields {
+            if line.timestamp.is_none() && TIMESTAMP_KEYS.contains(&key.as_str()) {
+                line.timestamp = Some(format_timestamp(value));
+            } else if line.level.is_none()
+                && let Some(level) = level::field_level(key, value)
+            {
+                line.level = Some(level);
+            } else if line.message.is_none() && MESSAGE_KEYS.contains(&key.as_str()) {
+                line.message = Some(value.clone());
+            } else {
+                line.fields.push((key.clone(), value.clone()));
+            }
+        }
+
+        line
+    }
+}
+
+/// Epoch timestamps (seconds, as zap writes them, or milliseconds, as pino
+/// does) become `HH:MM:SS.mmm` UTC. Anything else is shown as-is.
+fn format_timestamp(value: &str) -> String {
+    let Ok(number) = value.parse::<f64>() else {
+        return value.to_string();
+    };
+
+    let millis = if number > 1e11 {
+        number as u64
+    } else {
+        (number * 1000.0) as u64
+    };
+    let secs = millis / 1000;
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        millis % 1000
+    )
+}
+
+const INDEX_BITS: usize = 8 * 1024;
+const INDEX_HASHES: u64 = 3;
+
+/// Bloom filter over the `key=value` pairs of a block's JSON lines. Lets
+/// field queries skip blocks without looking at their lines. Each pair is indexed under both its dotted path and
+/// its last segment, mirroring `key_matches`.
+#[derive(Clone)]
+pub struct FieldIndex {
+    bits: Box<[u64; INDEX_BITS / 64]>,
+}
+
+impl FieldIndex {
+    #[inline]
+    pub fn heap_size(&self) -> usize {
+        size_of_val(&*self.bits)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            bits: Box::new([0; INDEX_BITS / 64]),
+        }
+    }
+
+    pub fn insert(&mut self, key: &str, value: &str) {
+        for name in [Some(key), key.rsplit_once('.').map(|(_, last)| last)]
+            .into_iter()
+            .flatten()
+        {
+            for bit in Self::bits(name, value) {
+                self.bits[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+    }
+
+    /// `false` means no line in the block has the pair.
+    pub fn may_contain(&self, key: &str, value: &str) -> bool {
+        Self::bits(key, value).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    fn bits(key: &str, value: &str) -> impl Iterator<Item = usize> {
+        let mut hasher = DefaultHasher::new();
+        (key, value).hash(&mut hasher);
+        let hash = hasher.finish();
+        let (h1, h2) = (hash & 0xffff_ffff, hash >> 32);
+
+        (0..INDEX_HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) as usize) % INDEX_BITS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tracing_json() {
+        let fields = flatten(
+            r#"{"timestamp":"2024-05-01T10:00:00Z","level":"WARN","fields":{"message":"slow","req_id":"abc"},"target":"api"}"#,
+        )
+        .unwrap();
+        let line = StructuredLine::new(&fields);
+
+        assert_eq!(line.timestamp.as_deref(), Some("2024-05-01T10:00:00Z"));
+        assert_eq!(line.level, Some(Level::Warn));
+        assert_eq!(line.message.as_deref(), Some("slow"));
+        assert_eq!(
+            line.fields,
+            [
+                ("fields.req_id".to_string(), "abc".to_string()),
+                ("target".to_string(), "api".to_string())
+            ]
+        );
+
+        let mut index = FieldIndex::new();
+        for (key, value) in &line.fields {
+            index.insert(key, value);
+        }
+        assert!(index.may_contain("req_id", "abc"));
+        assert!(index.may_contain("fields.req_id", "abc"));
+        assert!(!index.may_contain("req_id", "xyz"));
+
+        let pino = StructuredLine::new(&flatten(r#"{"level":50,"msg":"boom"}"#).unwrap());
+        assert_eq!(pino.level, Some(Level::Error));
+        let loglevel = StructuredLine::new(&flatten(r#"{"loglevel":"debug"}"#).unwrap());
+        assert_eq!(loglevel.level, Some(Level::Debug));
+        assert!(loglevel.fields.is_empty());
+    }
+}
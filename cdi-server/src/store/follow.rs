@@ -0,0 +1,278 @@
+use cdi_shared::log::{LogLine, Stream};
+use tokio::sync::watch;
+
+use super::{handle::StoreHandle, query::LogView, snapshot::LogSnapshot};
+
+/// Most lines yielded by one `LogFollower::next`.
+const FOLLOW_BATCH: usize = 512;
+
+#[derive(Debug)]
+pub enum Followed {
+    /// New lines, oldest first. An entry that grows after it was yielded,
+    /// e.g. a stack trace still being printed, comes again with the same id
+    /// and only its new lines.
+    Lines(Vec<LogLine>),
+    /// `missed` entries of the followed stream were evicted before they
+    /// could be yielded. Following goes on from the oldest entry still
+    /// stored.
+    Lagged { missed: u64 },
+}
+
+/// `tail -f` over the output of one process, see `StoreHandle::follow`.
+pub struct LogFollower {
+    store: StoreHandle,
+    process_id: u64,
+    stream: Option<Stream>,
+    /// First entry not yielded yet.
+    next_id: u64,
+    /// The newest yielded entry and how many of its lines were yielded.
+    last: Option<(u64, usize)>,
+    /// Entries of the followed stream before `next_id`, evicted ones
+    /// included, to tell how many of those evicted since were missed.
+    passed: u64,
+    changes: watch::Receiver<u64>,
+}
+
+impl LogFollower {
+    pub(super) fn new(
+        store: StoreHandle,
+        process_id: u64,
+        after: Option<u64>,
+        seen_lines: Option<usize>,
+        stream: Option<Stream>,
+    ) -> Self {
+        let changes = store.changes();
+        let mut follower = Self {
+            store,
+            process_id,
+            stream,
+            next_id: after.map_or(0, |id| id + 1),
+            last: None,
+            passed: 0,
+            changes,
+        };
+        let Some(snapshot) = follower.store.snapshot(process_id) else {
+            return follower;
+        };
+
+        match after {
+            // Lines added to entry `after` later on are yielded too.
+            Some(after) => {
+                let view = match after.checked_sub(1) {
+                    Some(before) => snapshot.query().after(before),
+                    None => snapshot.query(),
+                };
+                if let Some((meta, content)) = view.limit(1).iter().next()
+                    && meta.id == after
+                {
+                    let seen = seen_lines.unwrap_or_else(|| content.split('\n').count());
+                    follower.last = Some((after, seen));
+                }
+            }
+            // Without a starting point only what comes from now on is
+            // yielded, lines added to the newest entry included.
+            None => {
+                if let Some((meta, _)) = snapshot.query().tail(1).first() {
+                    follower.next_id = meta.id + 1;
+                }
+                if let Some((meta, content)) = follower.view(&snapshot).tail(1).first() {
+                    follower.last = Some((meta.id, content.split('\n').count()));
+                }
+            }
+        }
+
+        let stream = follower.stream.as_ref();
+        follower.passed =
+            snapshot.evicted(stream) + snapshot.count_before(stream, follower.next_id);
+
+        follower
+    }
+
+    /// Waits for new lines. `None` once the store is gone.
+    pub async fn next(&mut self) -> Option<Followed> {
+        loop {
+            self.changes.borrow_and_update();
+            if let Some(followed) = self.poll() {
+                return Some(followed);
+            }
+            self.changes.changed().await.ok()?;
+        }
+    }
+
+    /// The snapshot's entries on the followed stream.
+    fn view<'a>(&self, snapshot: &'a LogSnapshot) -> LogView<'a> {
+        match &self.stream {
+            Some(stream) => snapshot.query().stream(stream.clone()),
+            None => snapshot.query(),
+        }
+    }
+
+    fn poll(&mut self) -> Option<Followed> {
+        let snapshot = self.store.snapshot(self.process_id)?;
+        let oldest = snapshot.first_id()?;
+        if oldest > self.next_id {
+            // Everything before the oldest entry is evicted now.
+            let evicted = snapshot.evicted(self.stream.as_ref());
+            let missed = evicted.saturating_sub(self.passed);
+            self.next_id = oldest;
+            self.passed = evicted;
+            self.last = None;
+            if missed > 0 {
+                return Some(Followed::Lagged { missed });
+            }
+        }
+
+        let session_id = self.store.session_id();
+        let mut lines = Vec::new();
+
+        if let Some((id, seen)) = self.last {
+            let view = match id.checked_sub(1) {
+                Some(before) => snapshot.query().after(before),
+                None => snapshot.query(),
+            };
+            if let Some((meta, content)) = view.limit(1).iter().next()
+                && meta.id == id
+            {
+                let new: Vec<&str> = content.split('\n').skip(seen).collect();
+                if !new.is_empty() {
+                    self.last = Some((id, seen + new.len()));
+                    lines.push(meta.to_log_line(self.process_id, session_id, &new.join("\n")));
+                }
+            }
+        }
+
+        let mut view = self.view(&snapshot).limit(FOLLOW_BATCH);
+        if let Some(before) = self.next_id.checked_sub(1) {
+            view = view.after(before);
+        }
+
+        let entries: Vec<_> = view.iter().collect();
+        self.passed += entries.len() as u64;
+        for (meta, content) in &entries {
+            lines.push(meta.to_log_line(self.process_id, session_id, content));
+            self.last = Some((meta.id, content.split('\n').count()));
+        }
+
+        // A short batch means everything was looked at, including entries
+        // of the other stream.
+        self.next_id = match entries.last() {
+            Some((meta, _)) if entries.len() == FOLLOW_BATCH => meta.id + 1,
+            _ => snapshot
+                .query()
+                .tail(1)
+                .first()
+                .map_or(self.next_id, |(meta, _)| meta.id + 1),
+        };
+
+        (!lines.is_empty()).then_some(Followed::Lines(lines))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cdi_shared::log::{ProcessInfo, ReadTime};
+
+    use super::*;
+    use crate::store::{MemoryStore, block::BLOCK_CAP};
+
+    #[tokio::test]
+    async fn follows_new_lines() {
+        let info = ProcessInfo::new("api".into(), "cargo run".into(), None);
+        let process_id = info.id;
+        let store = StoreHandle::new(MemoryStore::new(vec![info], 0, 2 * BLOCK_CAP));
+        let append = |line: &str| {
+            store
+                .write()
+                .append(process_id, Stream::Stderr, line, ReadTime::now())
+        };
+        let contents = |followed: Option<Followed>| -> Vec<String> {
+            match followed {
+                Some(Followed::Lines(lines)) => {
+                    lines.into_iter().map(|line| line.content).collect()
+                }
+                other => panic!("expected lines, got {other:?}"),
+            }
+        };
+
+        append("old");
+        let mut follower = store.follow(process_id, None, None, None);
+        append("Error: boom");
+        assert_eq!(contents(follower.next().await), ["Error: boom"]);
+
+        // The trace joins the entry already yielded.
+        append("    at main (src/main.js:3:5)");
+        append("ready");
+        assert_eq!(
+            contents(follower.next().await),
+            ["    at main (src/main.js:3:5)", "ready"]
+        );
+
+        for n in 0..300 {
+            append(&format!("line {n}"));
+        }
+        assert!(matches!(
+            follower.next().await,
+            Some(Followed::Lagged { missed }) if missed > 0
+        ));
+        assert_eq!(contents(follower.next().await).last().unwrap(), "line 299");
+
+        // Resuming after an entry yields the lines it gained since.
+        append("Error: again");
+        let after = store.snapshot(process_id).unwrap().query().tail(1)[0].0.id;
+        append("    at retry (src/main.js:9:1)");
+        let mut follower = store.follow(process_id, Some(after), Some(1), None);
+        assert_eq!(
+            contents(follower.next().await),
+            ["    at retry (src/main.js:9:1)"]
+        );
+        append("    at main (src/main.js:3:5)");
+        append("done");
+        assert_eq!(
+            contents(follower.next().await),
+            ["    at main (src/main.js:3:5)", "done"]
+        );
+    }
+
+    #[tokio::test]
+    async fn follows_one_stream() {
+        let info = ProcessInfo::new("api".into(), "cargo run".into(), None);
+        let process_id = info.id;
+        let store = StoreHandle::new(MemoryStore::new(vec![info], 0, 2 * BLOCK_CAP));
+        let append = |stream: Stream, line: &str| {
+            store
+                .write()
+                .append(process_id, stream, line, ReadTime::now())
+        };
+
+        append(Stream::Stdout, "listening");
+        append(Stream::Stderr, "Error: boom");
+        let mut follower = store.follow(process_id, None, None, Some(Stream::Stdout));
+        // Continues the stderr entry, which isn't followed.
+        append(Stream::Stderr, "    at main (src/main.js:3:5)");
+        append(Stream::Stdout, "ready");
+        match follower.next().await {
+            Some(Followed::Lines(lines)) => {
+                assert_eq!(lines.len(), 1);
+                assert_eq!(lines[0].content, "ready");
+            }
+            other => panic!("expected lines, got {other:?}"),
+        }
+
+        // Of the 300 evicted entries, the 100 on stdout count as missed.
+        for n in 0..300 {
+            let stream = if n % 3 == 0 {
+                Stream::Stdout
+            } else {
+                Stream::Stderr
+            };
+            append(stream, &format!("line {n}"));
+        }
+        for n in 0..256 {
+            append(Stream::Stderr, &format!("filler {n}"));
+        }
+        assert!(matches!(
+            follower.next().await,
+            Some(Followed::Lagged { missed: 100 })
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+use std::time::Duration;
+
+use cdi_shared::{
+    ansi::strip_ansi,
+    log::{ReadTime, Stream},
+};
+use regex::Regex;
+
+/// Entries stop growing past this many lines or bytes, so a service that
+/// indents everything doesn't end up as one huge entry.
+const MAX_GROUP_LINES: usize = 512;
+const MAX_GROUP_BYTES: usize = 64 * 1024;
+
+/// Longest pause between two lines of one entry. A trace is written in one
+/// go; an indented line arriving later is output of its own.
+const MAX_GROUP_GAP: Duration = Duration::from_millis(100);
+
+const TRACEBACK: &str = "Traceback (most recent call last):";
+
+/// Decides which output lines continue the previous entry instead of
+/// starting a new one, so stack traces are stored, searched and classified
+/// as a unit.
+///
+/// A line continues the entry when it is indented (stack frames, rustc code
+/// frames), starts with `at `, `Caused by:`, `help:` or `note:`, matches the
+/// service's `multiline` pattern, or is part of a Python traceback up to
+/// and including its closing exception line, and it is read within
+/// `MAX_GROUP_GAP` of the line before it.
+pub struct Grouper {
+    pattern: Option<Regex>,
+    stdout: GroupState,
+    stderr: GroupState,
+}
+
+#[derive(Default)]
+struct GroupState {
+    /// Whether there is an entry to continue.
+    open: bool,
+    lines: usize,
+    bytes: usize,
+    traceback: Traceback,
+    /// When the stream's previous line was read, see `ReadTime::elapsed`.
+    last_read: u64,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Traceback {
+    #[default]
+    None,
+    /// Inside the frames; the first unindented line is the exception.
+    Frames,
+    /// Exception printed; only chained tracebacks continue.
+    Exception,
+}
+
+impl Grouper {
+    pub fn new(pattern: Option<Regex>) -> Self {
+        Self {
+            pattern,
+            stdout: GroupState::default(),
+            stderr: GroupState::default(),
+        }
+    }
+
+    /// Whether `content` continues the stream's current entry. Must be
+    /// called for every line; if the store can't extend the entry after
+    /// all, `restart` makes the line the start of a new one.
+    pub fn continues(&mut self, stream: &Stream, content: &str, time: &ReadTime) -> bool {
+        let text = strip_ansi(content);
+        let pattern = self.pattern.as_ref();
+        let state = match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        };
+
+        let gap = Duration::from_micros(time.elapsed.saturating_sub(state.last_read));
+        state.last_read = time.elapsed;
+
+        let continues = state.open
+            && gap <= MAX_GROUP_GAP
+            && state.lines < MAX_GROUP_LINES
+            && state.bytes + text.len() < MAX_GROUP_BYTES
+            && state.is_continuation(&text, pattern);
+
+        if continues {
+            state.add(&text);
+        } else {
+            state.start(&text);
+        }
+
+        continues
+    }
+
+    /// Takes up an entry stored whole, as if its lines had been read at
+    /// `time`, so later lines can still continue it.
+    pub fn resume(&mut self, stream: &Stream, content: &str, time: &ReadTime) {
+        let state = match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        };
+        state.last_read = time.elapsed;
+
+        let text = strip_ansi(content);
+        let mut lines = text.split('\n');
+        state.start(lines.next().unwrap_or_default());
+        for line in lines {
+            state.add(line);
+        }
+    }
+
+    pub fn restart(&mut self, stream: &Stream, content: &str) {
+        let state = match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        };
+        state.start(&strip_ansi(content));
+    }
+}
+
+impl GroupState {
+    fn start(&mut self, text: &str) {
+        self.open = true;
+        self.lines = 1;
+        self.bytes = text.len();
+        self.traceback = if text.trim_end() == TRACEBACK {
+            Traceback::Frames
+        } else {
+            Traceback::None
+        };
+    }
+
+    fn add(&mut self, text: &str) {
+        self.lines += 1;
+        self.bytes += text.len();
+        if text.trim_end() == TRACEBACK {
+            self.traceback = Traceback::Frames;
+        } else if self.traceback == Traceback::Frames && is_exception(text) {
+            self.traceback = Traceback::Exception;
+        }
+    }
+
+    fn is_continuation(&self, text: &str, pattern: Option<&Regex>) -> bool {
+        if pattern.is_some_and(|pattern| pattern.is_match(text)) {
+            return true;
+        }
+
+        if text.trim().is_empty() {
+            // Blank lines separate chained tracebacks.
+            return self.traceback != Traceback::None;
+        }
+
+        if text.starts_with([' ', '\t'])
+            || text.starts_with("at ")
+            || text.starts_with("Caused by:")
+            || text.starts_with("help:")
+            || text.starts_with("note:")
+            || is_code_frame(text)
+        {
+            return true;
+        }
+
+        match self.traceback {
+            Traceback::None => false,
+            Traceback::Frames => true,
+            Traceback::Exception => {
+                text.trim_end() == TRACEBACK
+                    || text.starts_with("During handling of the above exception")
+                    || text.starts_with("The above exception was the direct cause")
+            }
+        }
+    }
+}
+
+/// rustc's numbered source lines: `10 |     let x = y;`.
+fn is_code_frame(text: &str) -> bool {
+    text.split_once(" |")
+        .is_some_and(|(number, _)| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// The closing line of a traceback frame list, e.g. `ValueError: bad`.
+fn is_exception(text: &str) -> bool {
+    !text.starts_with([' ', '\t'])
+        && !text.trim().is_empty()
+        && !text.starts_with("During handling of the above exception")
+        && !text.starts_with("The above exception was the direct cause")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_stack_traces() {
+        let output = "starting
+Traceback (most recent call last):
+  File \"/app/main.py\", line 10, in <module>
+    main()
+KeyError: 'x'
+
+During handling of the above exception, another exception occurred:
+
+Traceback (most recent call last):
+  File \"/app/main.py\", line 12, in <module>
+ValueError: bad
+Exception in thread \"main\" java.lang.IllegalStateException: boom
+\tat com.example.App.run(App.java:12)
+Caused by: java.io.IOException: closed
+\t... 3 more
+listening";
+
+        let mut grouper = Grouper::new(None);
+        let mut entries: Vec<Vec<&str>> = Vec::new();
+        for (n, line) in output.lines().enumerate() {
+            if grouper.continues(&Stream::Stderr, line, &read_at(n as u64 * 1000)) {
+                entries.last_mut().unwrap().push(line);
+            } else {
+                entries.push(vec![line]);
+            }
+        }
+
+        let sizes: Vec<usize> = entries.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [1, 10, 4, 1]);
+        assert_eq!(entries[1].last(), Some(&"ValueError: bad"));
+    }
+
+    #[test]
+    fn late_indented_lines_start_entries() {
+        let mut grouper = Grouper::new(None);
+        let stream = Stream::Stdout;
+
+        assert!(!grouper.continues(&stream, "config:", &read_at(0)));
+        assert!(grouper.continues(&stream, "  port: 3000", &read_at(2_000)));
+        // Printed well after the block above, e.g. by a periodic report.
+        assert!(!grouper.continues(&stream, "  uptime: 5s", &read_at(5_000_000)));
+        assert!(grouper.continues(&stream, "  requests: 12", &read_at(5_001_000)));
+    }
+
+    fn read_at(elapsed: u64) -> ReadTime {
+        ReadTime {
+            timestamp: 0,
+            elapsed,
+            seq: 0,
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::{
+    Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use cdi_shared::{
+    detect::DetectedError,
+    event::store::StoreEvent,
+    log::{LogStats, ProcessInfo, ReadTime, Stream},
+    resource::{ProcessNode, ResourceSample},
+};
+use tokio::sync::{broadcast, watch};
+
+use super::{
+    export::{ExportFormat, export},
+    follow::LogFollower,
+    memory::MemoryStore,
+    query::{LogQuery, MergedView},
+    snapshot::LogSnapshot,
+};
+
+/// Shared access wrapper for clients. Readers take a snapshot and release
+/// the lock immediately; all querying happens on the snapshot.
+#[derive(Clone)]
+pub struct StoreHandle {
+    store: Arc<RwLock<MemoryStore>>,
+    /// Every event applied to the store, for replicas.
+    events: broadcast::Sender<StoreEvent>,
+    /// Bumped after each batch of events, for followers.
+    changes: Arc<watch::Sender<u64>>,
+    /// Events waiting on the store bus when the last batch was taken.
+    backlog: Arc<AtomicUsize>,
+}
+
+/// Events a replica may fall behind by before it is cut off.
+const REPLICA_BUFFER: usize = 16 * 1024;
+
+impl StoreHandle {
+    pub(super) fn new(store: MemoryStore) -> Self {
+        Self {
+            store: Arc::new(RwLock::new(store)),
+            events: broadcast::channel(REPLICA_BUFFER).0,
+            changes: Arc::new(watch::Sender::new(0)),
+            backlog: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    #[inline]
+    pub(super) fn set_backlog(&self, events: usize) {
+        self.backlog.store(events, Ordering::Relaxed);
+    }
+
+    /// Events still queued on the store bus when the last batch was
+    /// applied. Grows when services log faster than the store keeps up.
+    pub fn backlog(&self) -> usize {
+        self.backlog.load(Ordering::Relaxed)
+    }
+
+    /// Events the slowest replica has yet to receive.
+    pub fn replica_backlog(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Approximate heap bytes held by the retained output.
+    pub fn heap_size(&self) -> usize {
+        self.read().heap_size()
+    }
+
+    /// Wakes followers once a batch of events is applied.
+    #[inline]
+    pub(super) fn notify_changed(&self) {
+        self.changes.send_modify(|version| *version += 1);
+    }
+
+    pub(super) fn changes(&self) -> watch::Receiver<u64> {
+        self.changes.subscribe()
+    }
+
+    /// Yields the process's lines after entry `after` as they are added, or
+    /// with `after` omitted, the lines added from now on. `seen_lines` of
+    /// entry `after` count as yielded, all it has now if omitted.
+    pub fn follow(
+        &self,
+        process_id: u64,
+        after: Option<u64>,
+        seen_lines: Option<usize>,
+        stream: Option<Stream>,
+    ) -> LogFollower {
+        LogFollower::new(self.clone(), process_id, after, seen_lines, stream)
+    }
+
+    /// Every event applied to the store from now on, e.g. to watch for
+    /// status changes. A receiver falling too far behind lags.
+    pub fn subscribe(&self) -> broadcast::Receiver<StoreEvent> {
+        self.events.subscribe()
+    }
+
+    /// Passes an event on to replicas. Called with the write lock held, so
+    /// `replicate` sees each event either in the store or on the channel.
+    #[inline]
+    pub(super) fn publish(&self, event: &StoreEvent) {
+        if self.events.receiver_count() > 0 {
+            let _ = self.events.send(event.clone());
+        }
+    }
+
+    #[inline]
+    pub(super) fn read(&self) -> RwLockReadGuard<'_, MemoryStore> {
+        self.store.read().unwrap()
+    }
+
+    #[inline]
+    pub(super) fn write(&self) -> RwLockWriteGuard<'_, MemoryStore> {
+        self.store.write().unwrap()
+    }
+
+    /// What a replica needs to mirror the store: the processes, events
+    /// rebuilding the retained output and resource history, and every event
+    /// applied from now on.
+    pub fn replicate(
+        &self,
+    ) -> (
+        Vec<ProcessInfo>,
+        Vec<StoreEvent>,
+        broadcast::Receiver<StoreEvent>,
+    ) {
+        let store = self.read();
+        let receiver = self.events.subscribe();
+        let processes: Vec<ProcessInfo> = store.get_processes().into_iter().cloned().collect();
+
+        // Only the snapshots are taken under the lock; sealed blocks are
+        // decompressed after releasing it.
+        let mut pending = Vec::with_capacity(processes.len());
+        for info in &processes {
+            let process_id = info.id;
+            let mut rest = Vec::new();
+            for (stream, content) in store.get_partials(process_id).unwrap_or_default() {
+                rest.push(StoreEvent::PartialLog {
+                    process_id,
+                    stream: stream.clone(),
+                    content: content.clone(),
+                });
+            }
+
+            let samples = store.get_resources(process_id).into_iter().flatten();
+            let tree = store.get_tree(process_id).unwrap_or_default();
+            let mut samples = samples.peekable();
+            while let Some(sample) = samples.next() {
+                rest.push(StoreEvent::ResourceSample {
+                    process_id,
+                    sample: sample.clone(),
+                    tree: if samples.peek().is_none() {
+                        tree.to_vec()
+                    } else {
+                        Vec::new()
+                    },
+                });
+            }
+
+            pending.push((process_id, store.snapshot(process_id), rest));
+        }
+        drop(store);
+
+        let mut history = Vec::new();
+        for (process_id, snapshot, rest) in pending {
+            if let Some(snapshot) = snapshot {
+                // Whole entries, so the replica doesn't group them again.
+                history.extend(snapshot.query().iter().map(|(meta, content)| {
+                    StoreEvent::AppendEntry {
+                        process_id,
+                        stream: meta.stream.clone(),
+                        content: content.to_string(),
+                        level: meta.level,
+                        time: ReadTime {
+                            timestamp: meta.timestamp,
+                            elapsed: meta.elapsed,
+                            seq: meta.seq,
+                        },
+                    }
+                }));
+            }
+            // After the entries, which drop the partials of their stream.
+            history.extend(rest);
+        }
+
+        (processes, history, receiver)
+    }
+
+    pub fn session_id(&self) -> u64 {
+        self.read().session_id()
+    }
+
+    pub fn snapshot(&self, process_id: u64) -> Option<LogSnapshot> {
+        self.read().snapshot(process_id)
+    }
+
+    pub fn snapshot_all(&self) -> HashMap<u64, LogSnapshot> {
+        let store = self.read();
+
+        store
+            .get_processes()
+            .iter()
+            .filter_map(|info| store.snapshot(info.id).map(|snap| (info.id, snap)))
+            .collect()
+    }
+
+    /// Writes the entries of the processes `process_ids` matching `query`
+    /// to `out`, interleaved in read order, see `export`.
+    pub fn export(
+        &self,
+        process_ids: &[u64],
+        query: LogQuery,
+        format: ExportFormat,
+        out: &mut impl Write,
+    ) -> io::Result<usize> {
+        let (processes, snapshots, session_id) = {
+            let store = self.read();
+            let processes: Vec<ProcessInfo> = store
+                .get_processes()
+                .into_iter()
+                .filter(|info| process_ids.contains(&info.id))
+                .cloned()
+                .collect();
+            let snapshots: Vec<LogSnapshot> = processes
+                .iter()
+                .filter_map(|info| store.snapshot(info.id))
+                .collect();
+            (processes, snapshots, store.session_id())
+        };
+
+        let view = MergedView::new(&snapshots).filter(query);
+        export(&view, &processes, session_id, format, out)
+    }
+
+    /// Processes in config order.
+    pub fn processes(&self) -> Vec<ProcessInfo> {
+        self.read().get_processes().into_iter().cloned().collect()
+    }
+
+    pub fn process(&self, process_id: u64) -> Option<ProcessInfo> {
+        self.read().get_process(process_id).cloned()
+    }
+
+    /// Looks a process up by service name, falling back to its numeric id.
+    pub fn find_process(&self, name: &str) -> Option<ProcessInfo> {
+        let processes = self.processes();
+
+        processes
+            .iter()
+            .find(|info| info.name == name)
+            .or_else(|| {
+                let id: u64 = name.parse().ok()?;
+                processes.iter().find(|info| info.id == id)
+            })
+            .cloned()
+    }
+
+    pub fn stats(&self, process_id: u64) -> Option<LogStats> {
+        self.read().get_stats(process_id).cloned()
+    }
+
+    /// Recent resource usage of the process's tree, oldest first.
+    pub fn resources(&self, process_id: u64) -> Vec<ResourceSample> {
+        self.read()
+            .get_resources(process_id)
+            .map(|samples| samples.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Live processes of the service at the latest sample, depth-first.
+    pub fn process_tree(&self, process_id: u64) -> Vec<ProcessNode> {
+        self.read()
+            .get_tree(process_id)
+            .map(<[_]>::to_vec)
+            .unwrap_or_default()
+    }
+
+    /// Lines the process is still writing, see `StoreEvent::PartialLog`.
+    pub fn partials(&self, process_id: u64) -> Vec<(Stream, String)> {
+        self.read()
+            .get_partials(process_id)
+            .map(<[_]>::to_vec)
+            .unwrap_or_default()
+    }
+
+    /// Diagnostics of one process, or of all of them when `process_id` is
+    /// `None`, in the order they were found.
+    pub fn errors(&self, process_id: Option<u64>) -> Vec<DetectedError> {
+        let store = self.read();
+        let ids = match process_id {
+            Some(id) => vec![id],
+            None => store.get_processes().iter().map(|info| info.id).collect(),
+        };
+
+        let mut errors: Vec<DetectedError> = ids
+            .into_iter()
+            .filter_map(|id| store.get_errors(id))
+            .flatten()
+            .cloned()
+            .collect();
+        errors.sort_by_key(|error| error.id);
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::block::BLOCK_CAP;
+
+    #[test]
+    fn replicates_store() {
+        let info = ProcessInfo::new("api".into(), "cargo run".into(), None);
+        let process_id = info.id;
+        let store = StoreHandle::new(MemoryStore::new(vec![info], 7, 4 * BLOCK_CAP));
+        let output = [
+            "panicked at src/main.rs:3:5:",
+            "  boom",
+            "listening",
+            "ready",
+        ];
+        for line in &output[..3] {
+            store
+                .write()
+                .append(process_id, Stream::Stderr, line, ReadTime::now());
+        }
+
+        let (processes, history, mut events) = store.replicate();
+        store
+            .write()
+            .append(process_id, Stream::Stderr, output[3], ReadTime::now());
+        store.publish(&StoreEvent::AppendLog {
+            process_id,
+            stream: Stream::Stderr,
+            content: output[3].to_string(),
+            time: ReadTime::now(),
+        });
+
+        let mut replica = MemoryStore::new(processes, 7, 4 * BLOCK_CAP);
+        for event in history {
+            replica.apply(event);
+        }
+        replica.apply(events.try_recv().unwrap());
+
+        let entries = |store: &MemoryStore| -> Vec<String> {
+            let snapshot = store.snapshot(process_id).unwrap();
+            snapshot
+                .query()
+                .iter()
+                .map(|(_, c)| c.to_string())
+                .collect()
+        };
+        assert_eq!(entries(&replica), entries(&store.read()));
+        assert_eq!(entries(&replica).len(), 3);
+
+        let stats = |store: &MemoryStore| {
+            let stats = store.get_stats(process_id).unwrap();
+            (stats.stderr_lines, stats.errors)
+        };
+        assert_eq!(stats(&replica), stats(&store.read()));
+    }
+}
@@ -0,0 +1,195 @@
+use cdi_shared::{
+    ansi::strip_ansi,
+    log::{Level, Stream},
+};
+
+/// Only the start of a line is searched for bare level tokens (`ERROR`,
+/// `[W]`, ...), so words in the message body don't reclassify it.
+const PREFIX_LEN: usize = 96;
+
+const LEVEL_KEYS: [&str; 4] = ["level", "severity", "lvl", "loglevel"];
+
+/// Classifies a line's severity.
+///
+/// Explicit markers win: a level field (`"level":"warn"`, `level=warn`,
+/// pino's numeric levels), an uppercase level token or `[E]`-style tag near
+/// the start, a `error:`/`warning:` prefix as printed by compilers, or an
+/// exception name as ending stack traces (`TypeError: ...`). Lines
+/// without one fall back to weak evidence, which only counts on stderr:
+/// words like `error` or `failed` anywhere in the line.
+pub fn detect(content: &str, stream: &Stream) -> Option<Level> {
+    let text = strip_ansi(content);
+    let text = text.trim_start();
+
+    level_field(text)
+        .or_else(|| level_token(text))
+        .or_else(|| level_prefix(text))
+        .or_else(|| exception_prefix(text))
+        .or_else(|| match stream {
+            Stream::Stderr => weak_level(text),
+            Stream::Stdout => None,
+        })
+}
+
+/// `"level":"warn"`, `"level": 40`, `level=warn`.
+fn level_field(text: &str) -> Option<Level> {
+    LEVEL_KEYS.iter().find_map(|key| {
+        let json = format!("\"{key}\"");
+        if let Some(pos) = text.find(&json) {
+            let rest = text[pos + json.len()..].trim_start().strip_prefix(':')?;
+            return json_level(rest.trim_start());
+        }
+
+        let logfmt = format!("{key}=");
+        text.match_indices(&logfmt)
+            .find(|(pos, _)| *pos == 0 || text.as_bytes()[pos - 1] == b' ')
+            .and_then(|(pos, _)| {
+                let value = text[pos + logfmt.len()..]
+                    .trim_start_matches('"')
+                    .split(|c: char| !c.is_ascii_alphabetic())
+                    .next()?;
+                Level::parse(value)
+            })
+    })
+}
+
+fn json_level(value: &str) -> Option<Level> {
+    if let Some(value) = value.strip_prefix('"') {
+        return Level::parse(value.split('"').next()?);
+    }
+
+    let number = value
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()?;
+
+    Some(numeric_level(number))
+}
+
+/// Level of a flattened JSON field (see `fields::flatten`), `None` unless
+/// `key` is a level key and `value` a level name or number.
+pub fn field_level(key: &str, value: &str) -> Option<Level> {
+    if !LEVEL_KEYS.contains(&key) {
+        return None;
+    }
+
+    match value.parse() {
+        Ok(number) => Some(numeric_level(number)),
+        Err(_) => Level::parse(value),
+    }
+}
+
+/// pino / bunyan numeric levels.
+fn numeric_level(number: u32) -> Level {
+    match number {
+        0..=10 => Level::Trace,
+        11..=20 => Level::Debug,
+        21..=30 => Level::Info,
+        31..=40 => Level::Warn,
+        _ => Level::Error,
+    }
+}
+
+/// Uppercase tokens (`ERROR`, `WARN`, ...) and single-letter tags (`[E]`)
+/// near the start of the line.
+fn level_token(text: &str) -> Option<Level> {
+    let end = text
+        .char_indices()
+        .nth(PREFIX_LEN)
+        .map_or(text.len(), |(idx, _)| idx);
+    let prefix = &text[..end];
+
+    for (tag, level) in [
+        ("[E]", Level::Error),
+        ("[W]", Level::Warn),
+        ("[I]", Level::Info),
+        ("[D]", Level::Debug),
+        ("[T]", Level::Trace),
+    ] {
+        if prefix.contains(tag) {
+            return Some(level);
+        }
+    }
+
+    prefix
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|token| token.len() >= 3 && token.bytes().all(|b| b.is_ascii_uppercase()))
+        .find_map(Level::parse)
+}
+
+/// `error: ...`, `error[E0308]: ...`, `warning: ...` as printed by rustc, tsc
+/// and most CLIs.
+fn level_prefix(text: &str) -> Option<Level> {
+    let word = text
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .filter(|word| !word.is_empty())?;
+    let rest = &text[word.len()..];
+
+    if rest.starts_with(':') || rest.starts_with('[') {
+        Level::parse(word)
+    } else {
+        None
+    }
+}
+
+/// `TypeError: ...`, `java.lang.IllegalStateException: ...`,
+/// `Exception in thread "main" ...`.
+fn exception_prefix(text: &str) -> Option<Level> {
+    if text.starts_with("Exception in thread ") {
+        return Some(Level::Error);
+    }
+
+    let name = text
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+        .next()?;
+    let is_exception = name.ends_with("Error") || name.ends_with("Exception");
+
+    (is_exception && text[name.len()..].starts_with(':')).then_some(Level::Error)
+}
+
+fn weak_level(text: &str) -> Option<Level> {
+    let lower = text.to_ascii_lowercase();
+    let words = || lower.split(|c: char| !c.is_ascii_alphabetic());
+
+    if words().any(|w| matches!(w, "error" | "failed" | "fatal" | "panic" | "exception")) {
+        Some(Level::Error)
+    } else if words().any(|w| matches!(w, "warn" | "warning" | "deprecated")) {
+        Some(Level::Warn)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_common_formats() {
+        let cases = [
+            ("2024-01-01T00:00:00Z ERROR api: boom", Some(Level::Error)),
+            ("\x1b[33m WARN\x1b[0m slow query", Some(Level::Warn)),
+            ("[I] listening on :3000", Some(Level::Info)),
+            (r#"{"level":"debug","msg":"hi"}"#, Some(Level::Debug)),
+            (r#"{"level":50,"msg":"boom"}"#, Some(Level::Error)),
+            ("ts=1 level=warn msg=hi", Some(Level::Warn)),
+            ("error[E0308]: mismatched types", Some(Level::Error)),
+            ("warning: unused variable", Some(Level::Warn)),
+            ("TypeError: x is undefined", Some(Level::Error)),
+            ("GET /users 200", None),
+            ("request failed, retrying", None),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(detect(line, &Stream::Stdout), expected, "{line}");
+        }
+
+        assert_eq!(
+            detect("request failed, retrying", &Stream::Stderr),
+            Some(Level::Error)
+        );
+        assert_eq!(detect("compiling...", &Stream::Stderr), None);
+    }
+}
@@ -0,0 +1,79 @@
+use cdi_shared::{
+    event::{store::StoreEvent, ui::TuiEvent},
+    log::ProcessInfo,
+};
+use tokio::sync::mpsc;
+
+use super::{handle::StoreHandle, memory::MemoryStore};
+use crate::session::SessionLock;
+
+/// Owns the write side of the store and applies events from the store bus.
+pub struct StoreManager {
+    store: StoreHandle,
+    /// Kept up to date with the process groups of running services.
+    session: Option<SessionLock>,
+}
+
+impl StoreManager {
+    pub fn new(
+        processes: Vec<ProcessInfo>,
+        session_id: u64,
+        max_lines: usize,
+        session: Option<SessionLock>,
+    ) -> (StoreManager, StoreHandle) {
+        let store = StoreHandle::new(MemoryStore::new(processes, session_id, max_lines));
+
+        (
+            StoreManager {
+                store: store.clone(),
+                session,
+            },
+            store,
+        )
+    }
+
+    pub async fn run(mut self, mut events: mpsc::UnboundedReceiver<StoreEvent>) {
+        let mut batch = Vec::with_capacity(64);
+
+        while events.recv_many(&mut batch, 64).await > 0 {
+            self.store.set_backlog(events.len());
+            let processes = {
+                let mut store = self.store.write();
+                let groups_changed = batch.iter().any(|event| {
+                    matches!(
+                        event,
+                        StoreEvent::ProcessStarted { .. } | StoreEvent::ProcessExited { .. }
+                    )
+                });
+
+                for event in batch.drain(..) {
+                    self.store.publish(&event);
+                    store.apply(event);
+                }
+
+                // Recorded after releasing the lock, reading `/proc` and
+                // writing the file would hold up every reader.
+                self.session
+                    .as_ref()
+                    .is_some_and(|session| groups_changed || session.needs_record())
+                    .then(|| {
+                        store
+                            .get_processes()
+                            .into_iter()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+            };
+
+            self.store.notify_changed();
+            TuiEvent::Render.emit();
+
+            if let Some(processes) = processes
+                && let Some(session) = &mut self.session
+                && let Err(e) = session.record(&processes)
+            {
+                eprintln!("Failed to update session lockfile: {e}");
+            }
+        }
+    }
+}
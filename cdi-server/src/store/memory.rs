@@ -1,98 +1,513 @@
 use std::collections::{HashMap, VecDeque};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
 
-use super::LogStore;
-use cdi_shared::log::{LogLine, ProcessInfo, Stream};
+use regex::Regex;
 
-// - [ ] Implement remaining trait methods
+use cdi_shared::{
+    detect::DetectedError,
+    event::store::StoreEvent,
+    log::{Level, LogStats, ProcessInfo, ProcessStatus, ReadTime, Stream},
+    resource::{ProcessNode, ResourceSample},
+};
+
+use super::{
+    block::{BLOCK_CAP, Block, LogLineMeta},
+    cache::BlockCache,
+    errors::ProcessErrors,
+    fields,
+    group::Grouper,
+    level,
+    snapshot::{Evicted, LogSnapshot},
+};
+
+/// Blocks for a single process, oldest first. Only the last block is ever
+/// open; every other block is sealed (compressed).
+pub struct ProcessLogs {
+    process_id: u64,
+    session_id: u64,
+    blocks: VecDeque<Arc<Block>>,
+    max_blocks: usize,
+    next_id: u64,
+    evicted: Evicted,
+}
+
+impl ProcessLogs {
+    pub fn new(process_id: u64, session_id: u64, max_blocks: usize) -> Self {
+        Self {
+            process_id,
+            session_id,
+            blocks: VecDeque::with_capacity(max_blocks),
+            max_blocks,
+            next_id: 0,
+            evicted: Evicted::default(),
+        }
+    }
+
+    /// Returns the new line's id.
+    pub fn append(
+        &mut self,
+        time: ReadTime,
+        stream: Stream,
+        level: Option<Level>,
+        content: &str,
+        fields: Option<Arc<[(String, String)]>>,
+    ) -> u64 {
+        // Full blocks are sealed only once the next entry arrives, so their
+        // last entry can still take continuation lines.
+        if let Some(last) = self.blocks.back_mut()
+            && last.is_full()
+            && !last.is_sealed()
+        {
+            Arc::make_mut(last).seal();
+        }
+
+        if self.blocks.back().is_none_or(|block| block.is_sealed()) {
+            self.blocks
+                .push_back(Arc::new(Block::new(self.process_id, self.session_id)));
+        }
+
+        // Copy-on-write: clones the block only if a snapshot still holds it.
+        let current = Arc::make_mut(self.blocks.back_mut().unwrap());
+        let id = self.next_id;
+        let mut meta = LogLineMeta::new(id, time, stream, level);
+        if let Some(fields) = &fields {
+            current.index_fields(fields);
+        }
+        meta.fields = fields;
+        current.push(meta, content);
+        self.next_id += 1;
+
+        if self.blocks.len() > self.max_blocks
+            && let Some(block) = self.blocks.pop_front()
+        {
+            self.evicted.add(&block);
+        }
+
+        id
+    }
+
+    /// Adds `content` as another line of the newest entry, if it is on
+    /// `stream` and its block is still open. Returns the entry's id and its
+    /// level before the change.
+    pub fn extend(
+        &mut self,
+        stream: &Stream,
+        content: &str,
+        level: Option<Level>,
+    ) -> Option<(u64, Option<Level>)> {
+        let last = self.blocks.back_mut()?;
+        if last.is_sealed() {
+            return None;
+        }
+
+        Arc::make_mut(last).extend_last(stream, content, level)
+    }
+
+    pub fn snapshot(&self) -> (Vec<Arc<Block>>, Evicted) {
+        (self.blocks.iter().cloned().collect(), self.evicted)
+    }
+
+    /// Approximate heap bytes held by the blocks.
+    pub fn heap_size(&self) -> usize {
+        self.blocks.iter().map(|block| block.heap_size()).sum()
+    }
+}
+
+/// Resource samples kept per process, two minutes at the monitor's rate.
+const RESOURCE_HISTORY: usize = 120;
 
 struct ProcessData {
     info: ProcessInfo,
-    logs: VecDeque<LogLine>,
+    logs: ProcessLogs,
+    stats: LogStats,
+    errors: ProcessErrors,
+    grouper: Grouper,
+    /// Unterminated lines still being written, at most one per stream.
+    partials: Vec<(Stream, String)>,
+    /// Recent resource samples, oldest first.
+    resources: VecDeque<ResourceSample>,
+    /// Live processes at the latest sample.
+    tree: Vec<ProcessNode>,
 }
 
 pub struct MemoryStore {
     session_id: u64,
-    max_lines: usize,
     processes: HashMap<u64, ProcessData>,
+    /// Process ids in config order.
+    order: Vec<u64>,
+    cache: Arc<BlockCache>,
+    next_error_id: u64,
 }
 
 impl MemoryStore {
+    /// Keeps at least the newest `max_lines` entries of each process,
+    /// dropping whole blocks.
     pub fn new(processes: Vec<ProcessInfo>, session_id: u64, max_lines: usize) -> Self {
+        let max_blocks = max_lines.div_ceil(BLOCK_CAP).max(1);
+        let order = processes.iter().map(|info| info.id).collect();
+        let mut invalid = Vec::new();
         let processes = processes
-            .iter()
+            .into_iter()
             .map(|info| {
+                let pattern = info.multiline.as_deref().and_then(|pattern| {
+                    Regex::new(pattern)
+                        .map_err(|e| invalid.push((info.id, e)))
+                        .ok()
+                });
                 let data = ProcessData {
-                    info: info.clone(),
-                    logs: VecDeque::with_capacity(max_lines),
+                    logs: ProcessLogs::new(info.id, session_id, max_blocks),
+                    stats: LogStats::default(),
+                    errors: ProcessErrors::new(),
+                    grouper: Grouper::new(pattern),
+                    partials: Vec::new(),
+                    resources: VecDeque::with_capacity(RESOURCE_HISTORY),
+                    tree: Vec::new(),
+                    info,
                 };
 
-                (info.id, data)
+                (data.info.id, data)
             })
             .collect();
 
-        MemoryStore {
+        let mut store = MemoryStore {
             session_id,
-            max_lines,
             processes,
+            order,
+            cache: Arc::new(BlockCache::default()),
+            next_error_id: 0,
+        };
+
+        // Surfaced in the service's own output, where it is noticed.
+        for (process_id, e) in invalid {
+            store.append(
+                process_id,
+                Stream::Stderr,
+                &format!("cdi: invalid multiline pattern: {e}"),
+                ReadTime::now(),
+            );
         }
+
+        store
     }
-}
 
-impl LogStore for MemoryStore {
-    fn append(&mut self, process_id: u64, stream: Stream, content: String) {
-        let proc = self.processes.get_mut(&process_id).unwrap();
-        let next_id = proc.logs.back().map(|log| log.id + 1).unwrap_or(0);
+    #[inline]
+    pub fn session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    /// Approximate heap bytes held by the retained output of every process.
+    pub fn heap_size(&self) -> usize {
+        self.processes
+            .values()
+            .map(|proc| proc.logs.heap_size())
+            .sum()
+    }
 
-        proc.logs.push_back(LogLine {
-            id: next_id,
+    pub fn apply(&mut self, event: StoreEvent) {
+        match event {
+            StoreEvent::AppendLog {
+                process_id,
+                stream,
+                content,
+                time,
+            } => self.append(process_id, stream, &content, time),
+            StoreEvent::AppendEntry {
+                process_id,
+                stream,
+                content,
+                level,
+                time,
+            } => self.append_entry(process_id, stream, &content, level, time),
+            StoreEvent::PartialLog {
+                process_id,
+                stream,
+                content,
+            } => self.set_partial(process_id, stream, content),
+            StoreEvent::ProcessStarted {
+                process_id,
+                pid,
+                time,
+            } => self.set_started(process_id, pid, time),
+            StoreEvent::ResourceSample {
+                process_id,
+                sample,
+                tree,
+            } => self.record_resources(process_id, sample, tree),
+            StoreEvent::ProcessExited {
+                process_id,
+                status,
+                exit_code,
+            } => self.set_status(process_id, status, exit_code),
+        }
+    }
+
+    /// Stores a line read at `time`. Continuation lines keep the time of the
+    /// entry they join.
+    pub fn append(&mut self, process_id: u64, stream: Stream, content: &str, time: ReadTime) {
+        let Some(proc) = self.processes.get_mut(&process_id) else {
+            return;
+        };
+
+        proc.partials.retain(|(partial, _)| *partial != stream);
+        proc.stats.read(&stream, content.len());
+
+        let level = level::detect(content, &stream);
+
+        // Continuation lines join the newest entry, whose level becomes the
+        // highest of its lines.
+        let extended = if proc.grouper.continues(&stream, content, &time) {
+            proc.logs.extend(&stream, content, level)
+        } else {
+            None
+        };
+        let line_id = match extended {
+            Some((id, previous)) => {
+                proc.stats.count(previous.max(level), previous);
+                id
+            }
+            None => {
+                proc.grouper.restart(&stream, content);
+                proc.stats.count(level, None);
+
+                let fields = fields::flatten(content).map(Arc::from);
+                proc.logs
+                    .append(time, stream.clone(), level, content, fields)
+            }
+        };
+
+        proc.errors.feed(
+            &mut self.next_error_id,
             process_id,
-            session_id: self.session_id,
-            stream,
+            line_id,
+            time.timestamp,
+            &stream,
             content,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis(),
-        });
+        );
+    }
+
+    /// Stores a whole entry at `level`, as `append` left it in another
+    /// store, without grouping its lines again.
+    pub fn append_entry(
+        &mut self,
+        process_id: u64,
+        stream: Stream,
+        content: &str,
+        level: Option<Level>,
+        time: ReadTime,
+    ) {
+        let Some(proc) = self.processes.get_mut(&process_id) else {
+            return;
+        };
 
-        if proc.logs.len() > self.max_lines {
-            proc.logs.pop_front();
+        proc.partials.retain(|(partial, _)| *partial != stream);
+        for line in content.split('\n') {
+            proc.stats.read(&stream, line.len());
+        }
+        proc.stats.count(level, None);
+        proc.grouper.resume(&stream, content, &time);
+
+        let first = content.split('\n').next().unwrap_or_default();
+        let fields = fields::flatten(first).map(Arc::from);
+        let line_id = proc
+            .logs
+            .append(time, stream.clone(), level, content, fields);
+
+        for line in content.split('\n') {
+            proc.errors.feed(
+                &mut self.next_error_id,
+                process_id,
+                line_id,
+                time.timestamp,
+                &stream,
+                line,
+            );
         }
     }
 
-    fn get_lines(&self, process_id: u64, limit: Option<usize>) -> Vec<&LogLine> {
-        let proc = self.processes.get(&process_id).unwrap();
-        let start = limit
-            .map(|n| proc.logs.len().saturating_sub(n))
-            .unwrap_or(0);
+    pub fn set_partial(&mut self, process_id: u64, stream: Stream, content: String) {
+        let Some(proc) = self.processes.get_mut(&process_id) else {
+            return;
+        };
 
-        proc.logs.range(start..).collect()
+        match proc
+            .partials
+            .iter_mut()
+            .find(|(partial, _)| *partial == stream)
+        {
+            Some((_, partial)) => *partial = content,
+            None => proc.partials.push((stream, content)),
+        }
     }
 
-    fn get_lines_since(&self, process_id: u64, since_id: u64) -> Vec<&LogLine> {
-        let proc = self.processes.get(&process_id).unwrap();
-        let start = proc
-            .logs
-            .binary_search_by(|log| log.id.cmp(&since_id))
-            .unwrap_or(0);
+    pub fn get_partials(&self, process_id: u64) -> Option<&[(Stream, String)]> {
+        self.processes
+            .get(&process_id)
+            .map(|proc| proc.partials.as_slice())
+    }
+
+    pub fn snapshot(&self, process_id: u64) -> Option<LogSnapshot> {
+        let proc = self.processes.get(&process_id)?;
+        let (blocks, evicted) = proc.logs.snapshot();
+
+        Some(LogSnapshot::new(
+            process_id,
+            blocks,
+            self.cache.clone(),
+            evicted,
+        ))
+    }
+
+    pub fn get_process(&self, process_id: u64) -> Option<&ProcessInfo> {
+        self.processes.get(&process_id).map(|proc| &proc.info)
+    }
+
+    pub fn get_stats(&self, process_id: u64) -> Option<&LogStats> {
+        self.processes.get(&process_id).map(|proc| &proc.stats)
+    }
+
+    /// Diagnostics found in the process's output, oldest first.
+    pub fn get_errors(&self, process_id: u64) -> Option<impl Iterator<Item = &DetectedError>> {
+        self.processes
+            .get(&process_id)
+            .map(|proc| proc.errors.errors().iter())
+    }
+
+    pub fn get_processes(&self) -> Vec<&ProcessInfo> {
+        self.order
+            .iter()
+            .filter_map(|id| self.get_process(*id))
+            .collect()
+    }
+
+    /// Marks the process running from `time`, which its lines are read
+    /// after.
+    pub fn set_started(&mut self, process_id: u64, pid: u32, time: ReadTime) {
+        if let Some(proc) = self.processes.get_mut(&process_id) {
+            proc.info.pid = Some(pid as usize);
+            proc.info.status = ProcessStatus::Running;
+            proc.info.exit_code = None;
+            proc.stats.starts += 1;
+            proc.info.started_at = Some(time.timestamp);
+        }
+    }
 
-        proc.logs.range(start..).collect()
+    pub fn set_status(&mut self, process_id: u64, status: ProcessStatus, exit_code: Option<i32>) {
+        if let Some(proc) = self.processes.get_mut(&process_id) {
+            if status != ProcessStatus::Running {
+                proc.info.pid = None;
+                proc.tree.clear();
+            }
+            proc.info.status = status;
+            proc.info.exit_code = exit_code;
+        }
+    }
+
+    pub fn record_resources(
+        &mut self,
+        process_id: u64,
+        sample: ResourceSample,
+        tree: Vec<ProcessNode>,
+    ) {
+        if let Some(proc) = self.processes.get_mut(&process_id) {
+            if proc.resources.len() == RESOURCE_HISTORY {
+                proc.resources.pop_front();
+            }
+            proc.resources.push_back(sample);
+            proc.tree = tree;
+        }
+    }
+
+    pub fn get_tree(&self, process_id: u64) -> Option<&[ProcessNode]> {
+        self.processes
+            .get(&process_id)
+            .map(|proc| proc.tree.as_slice())
     }
 
-    fn get_process(&self, process_id: u64) -> &ProcessInfo {
-        &self.processes.get(&process_id).unwrap().info
+    pub fn get_resources(&self, process_id: u64) -> Option<&VecDeque<ResourceSample>> {
+        self.processes.get(&process_id).map(|proc| &proc.resources)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_starts_before_its_lines() {
+        let info = ProcessInfo::new("api".into(), "pnpm dev".into(), None);
+        let process_id = info.id;
+        let mut store = MemoryStore::new(vec![info], 0, 4 * BLOCK_CAP);
+
+        // The reader may get the first line to the store before the start.
+        let started = ReadTime::now();
+        let ready = ReadTime {
+            timestamp: started.timestamp,
+            ..ReadTime::now()
+        };
+        store.append(process_id, Stream::Stdout, "ready", ready);
+        store.apply(StoreEvent::ProcessStarted {
+            process_id,
+            pid: 1,
+            time: started,
+        });
 
-    fn get_processes(&self) -> Vec<&ProcessInfo> {
-        self.processes.values().map(|value| &value.info).collect()
+        let since = store.get_process(process_id).unwrap().started_at.unwrap();
+        let snapshot = store.snapshot(process_id).unwrap();
+        let run: Vec<_> = snapshot.query().since(since).iter().collect();
+        assert_eq!(run.len(), 1);
     }
 
-    // fn set_process_status(
-    //     &mut self,
-    //     process_id: u64,
-    //     status: cdi_shared::log::ProcessStatus,
-    //     exit_code: Option<i32>,
-    // ) {
-    // }
+    #[test]
+    fn counts_errors_and_warnings() {
+        let info = ProcessInfo::new("api".into(), "pnpm dev".into(), None);
+        let process_id = info.id;
+        let mut store = MemoryStore::new(vec![info], 0, 4 * BLOCK_CAP);
+
+        for (stream, line) in [
+            (Stream::Stdout, "WARN cache is cold"),
+            (Stream::Stdout, "ERROR database is down"),
+            (Stream::Stdout, "INFO listening"),
+            (Stream::Stderr, "WARN retrying"),
+            // Joins the warning, which now counts as an error.
+            (Stream::Stderr, "Caused by: connection error"),
+        ] {
+            store.append(process_id, stream, line, ReadTime::now());
+        }
+
+        let stats = store.get_stats(process_id).unwrap();
+        assert_eq!((stats.errors, stats.warnings), (2, 1));
+        assert_eq!((stats.stdout_lines, stats.stderr_lines), (3, 2));
+        assert_eq!(store.snapshot(process_id).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn tail_spans_sealed_blocks() {
+        let info = ProcessInfo::new("api".into(), "pnpm dev".into(), None);
+        let process_id = info.id;
+        let mut store = MemoryStore::new(vec![info], 0, 4 * BLOCK_CAP);
+
+        for n in 0..1000 {
+            store.append(
+                process_id,
+                Stream::Stdout,
+                &format!("line {n}"),
+                ReadTime::now(),
+            );
+        }
+
+        let snapshot = store.snapshot(process_id).unwrap();
+        // 4 blocks retained: 3 sealed + the open tail.
+        assert_eq!(snapshot.len(), 3 * 128 + 1000 % 128);
+
+        let tail = snapshot.query().tail(200);
+        assert_eq!(tail.len(), 200);
+        assert_eq!(tail.first().unwrap().1, "line 800");
+        assert_eq!(tail.last().unwrap().1, "line 999");
+
+        let page: Vec<_> = snapshot.query().after(900).limit(3).iter().collect();
+        assert_eq!(
+            page.iter().map(|(_, c)| *c).collect::<Vec<_>>(),
+            ["line 901", "line 902", "line 903"]
+        );
+    }
 }
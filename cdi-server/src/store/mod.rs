@@ -1,18 +1,23 @@
 mod block;
+mod cache;
+mod errors;
+mod export;
+mod fields;
+mod follow;
+mod group;
+mod handle;
+pub(crate) mod level;
+mod manager;
 mod memory;
+mod query;
+mod snapshot;
 
-use cdi_shared::log::{LogLine, ProcessInfo, ProcessStatus, Stream};
-
-pub trait LogStore {
-    fn append(&mut self, process_id: u64, stream: Stream, content: String);
-    fn get_lines(&self, process_id: u64, limit: Option<usize>) -> Vec<&LogLine>;
-    fn get_lines_since(&self, process_id: u64, since_id: u64) -> Vec<&LogLine>;
-    fn get_process(&self, process_id: u64) -> &ProcessInfo;
-    fn get_processes(&self) -> Vec<&ProcessInfo>;
-    // fn set_process_status(
-    //     &mut self,
-    //     process_id: u64,
-    //     status: ProcessStatus,
-    //     exit_code: Option<i32>,
-    // );
-}
+pub use block::{Block, LogLineMeta};
+pub use export::{ExportFormat, export};
+pub use fields::StructuredLine;
+pub use follow::{Followed, LogFollower};
+pub use handle::StoreHandle;
+pub use manager::StoreManager;
+pub use memory::MemoryStore;
+pub use query::{LogQuery, LogView, MergedLine, MergedView};
+pub use snapshot::LogSnapshot;
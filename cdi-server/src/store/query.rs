@@ -0,0 +1,475 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use cdi_shared::{
+    ansi::strip_ansi,
+    log::{Level, Stream},
+};
+
+use super::{
+    block::{Block, LogLineMeta},
+    fields,
+    snapshot::LogSnapshot,
+};
+
+/// Filter parameters applied to a snapshot.
+#[derive(Clone, Debug, Default)]
+pub struct LogQuery {
+    pub process_id: Option<u64>,
+    pub stream: Option<Stream>,
+    pub after_id: Option<u64>,
+    pub before_id: Option<u64>,
+    pub limit: Option<usize>,
+    pub min_level: Option<Level>,
+    /// `key = value` pairs a structured line must all have.
+    pub fields: Vec<(String, String)>,
+    /// Lowercased text the entry must contain, see `search`.
+    pub search: Option<String>,
+    /// Unix time in milliseconds the entry must be read at or after.
+    pub since: Option<u128>,
+}
+
+impl LogQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process(mut self, id: u64) -> Self {
+        self.process_id = Some(id);
+        self
+    }
+
+    pub fn stream(mut self, stream: Stream) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    pub fn after(mut self, id: u64) -> Self {
+        self.after_id = Some(id);
+        self
+    }
+
+    /// Only lines older than `id`. With `tail`, pages backwards.
+    pub fn before(mut self, id: u64) -> Self {
+        self.before_id = Some(id);
+        self
+    }
+
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Only lines at `level` or above. Unclassified lines count as info.
+    pub fn min_level(mut self, level: Level) -> Self {
+        self.min_level = Some(level);
+        self
+    }
+
+    /// Only structured (JSON) lines with `key` = `value`. `key` is a dotted
+    /// path or its last segment, so `req_id` also finds `fields.req_id`.
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.push((key.into(), value.into()));
+        self
+    }
+
+    /// Only entries containing `text`, ignoring case and ANSI styling.
+    /// Multi-line entries match as a whole.
+    pub fn search(mut self, text: &str) -> Self {
+        self.search = Some(text.to_lowercase());
+        self
+    }
+
+    /// Only entries read at or after `timestamp`, in Unix milliseconds.
+    pub fn since(mut self, timestamp: u128) -> Self {
+        self.since = Some(timestamp);
+        self
+    }
+
+    /// Whether any line of `block` can match, judged from the header alone.
+    /// Lets views skip blocks without decompressing them.
+    fn may_match(&self, block: &Block) -> bool {
+        if self.process_id.is_some_and(|id| id != block.process_id) {
+            return false;
+        }
+
+        if self.min_level.is_some_and(|min| block.max_level() < min) {
+            return false;
+        }
+
+        if !self
+            .fields
+            .iter()
+            .all(|(key, value)| block.may_contain_field(key, value))
+        {
+            return false;
+        }
+
+        if let (Some(before), Some(first)) = (self.before_id, block.first_id())
+            && first >= before
+        {
+            return false;
+        }
+
+        match (self.after_id, block.last_id()) {
+            (_, None) => false,
+            (Some(after), Some(last)) => last > after,
+            (None, Some(_)) => true,
+        }
+    }
+
+    fn matches(&self, meta: &LogLineMeta, content: &str) -> bool {
+        if self.after_id.is_some_and(|after| meta.id <= after) {
+            return false;
+        }
+
+        if self.before_id.is_some_and(|before| meta.id >= before) {
+            return false;
+        }
+
+        if self.stream.as_ref().is_some_and(|s| *s != meta.stream) {
+            return false;
+        }
+
+        if self.min_level.is_some_and(|min| meta.severity() < min) {
+            return false;
+        }
+
+        if self.since.is_some_and(|since| meta.timestamp < since) {
+            return false;
+        }
+
+        if let Some(search) = &self.search
+            && !strip_ansi(content).to_lowercase().contains(search.as_str())
+        {
+            return false;
+        }
+
+        if !self.fields.is_empty() {
+            let Some(line_fields) = &meta.fields else {
+                return false;
+            };
+
+            return self.fields.iter().all(|(name, value)| {
+                line_fields
+                    .iter()
+                    .any(|(key, v)| v == value && fields::key_matches(key, name))
+            });
+        }
+
+        true
+    }
+}
+
+/// Filtered view over a snapshot, returns references. Blocks that can't
+/// match are skipped without being decompressed, so views over a small
+/// window (the TUI viewport, a page of API results) only pay for the blocks
+/// they touch.
+pub struct LogView<'a> {
+    snapshot: &'a LogSnapshot,
+    query: LogQuery,
+}
+
+impl<'a> LogView<'a> {
+    pub(super) fn new(snapshot: &'a LogSnapshot) -> Self {
+        Self {
+            snapshot,
+            query: LogQuery::new(),
+        }
+    }
+
+    pub fn filter(mut self, query: LogQuery) -> Self {
+        self.query = query;
+        self
+    }
+
+    pub fn stream(mut self, stream: Stream) -> Self {
+        self.query = self.query.stream(stream);
+        self
+    }
+
+    pub fn after(mut self, id: u64) -> Self {
+        self.query = self.query.after(id);
+        self
+    }
+
+    pub fn before(mut self, id: u64) -> Self {
+        self.query = self.query.before(id);
+        self
+    }
+
+    pub fn limit(mut self, n: usize) -> Self {
+        self.query = self.query.limit(n);
+        self
+    }
+
+    pub fn min_level(mut self, level: Level) -> Self {
+        self.query = self.query.min_level(level);
+        self
+    }
+
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query = self.query.field(key, value);
+        self
+    }
+
+    pub fn search(mut self, text: &str) -> Self {
+        self.query = self.query.search(text);
+        self
+    }
+
+    pub fn since(mut self, timestamp: u128) -> Self {
+        self.query = self.query.since(timestamp);
+        self
+    }
+
+    /// Iterate over matching lines, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a LogLineMeta, &'a str)> + '_ {
+        let snapshot = self.snapshot;
+
+        snapshot
+            .blocks()
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| self.query.may_match(block))
+            .flat_map(move |(idx, block)| block.iter(snapshot.text(idx)))
+            .filter(|(meta, content)| self.query.matches(meta, content))
+            .take(self.query.limit.unwrap_or(usize::MAX))
+    }
+
+    /// Get last N matching lines (for TUI viewport).
+    pub fn tail(&self, n: usize) -> Vec<(&'a LogLineMeta, &'a str)> {
+        let snapshot = self.snapshot;
+        let mut lines = Vec::with_capacity(n);
+
+        for (idx, block) in snapshot.blocks().iter().enumerate().rev() {
+            if lines.len() >= n {
+                break;
+            }
+
+            if !self.query.may_match(block) {
+                continue;
+            }
+
+            let text = snapshot.text(idx);
+            lines.extend(
+                block
+                    .lines()
+                    .iter()
+                    .rev()
+                    .map(|meta| (meta, block.get_content(text, meta)))
+                    .filter(|(meta, content)| self.query.matches(meta, content))
+                    .take(n - lines.len()),
+            );
+        }
+
+        lines.reverse();
+        lines
+    }
+}
+
+/// A line from a merged view, tagged with the process it came from.
+pub type MergedLine<'a> = (u64, &'a LogLineMeta, &'a str);
+
+/// Read-ordered view interleaving the lines of several snapshots. Each
+/// snapshot is already ordered, so this is a k-way merge rather than a sort.
+pub struct MergedView<'a> {
+    views: Vec<LogView<'a>>,
+    limit: Option<usize>,
+}
+
+impl<'a> MergedView<'a> {
+    pub fn new(snapshots: impl IntoIterator<Item = &'a LogSnapshot>) -> Self {
+        Self {
+            views: snapshots.into_iter().map(LogSnapshot::query).collect(),
+            limit: None,
+        }
+    }
+
+    /// Applies `query` to every snapshot. `limit` applies to the merged
+    /// output rather than to each snapshot.
+    pub fn filter(mut self, mut query: LogQuery) -> Self {
+        self.limit = query.limit.take();
+        self.views = self
+            .views
+            .into_iter()
+            .map(|view| view.filter(query.clone()))
+            .collect();
+        self
+    }
+
+    /// Iterate over matching lines of all snapshots, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = MergedLine<'a>> + '_ {
+        KMerge::new(
+            self.views
+                .iter()
+                .map(|view| (view.snapshot.process_id(), view.iter())),
+        )
+        .take(self.limit.unwrap_or(usize::MAX))
+    }
+
+    /// Get last N matching lines across all snapshots.
+    pub fn tail(&self, n: usize) -> Vec<MergedLine<'a>> {
+        let merged: Vec<_> = KMerge::new(
+            self.views
+                .iter()
+                .map(|view| (view.snapshot.process_id(), view.tail(n).into_iter())),
+        )
+        .collect();
+
+        let start = merged.len().saturating_sub(n);
+        merged[start..].to_vec()
+    }
+}
+
+struct Head<'a> {
+    source: usize,
+    meta: &'a LogLineMeta,
+    content: &'a str,
+}
+
+impl Head<'_> {
+    #[inline]
+    fn key(&self) -> (u64, usize, u64) {
+        (self.meta.seq, self.source, self.meta.id)
+    }
+}
+
+impl PartialEq for Head<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Head<'_> {}
+
+impl PartialOrd for Head<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head<'_> {
+    // Reversed so `BinaryHeap` pops the oldest line first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+struct KMerge<'a, I> {
+    sources: Vec<(u64, I)>,
+    heap: BinaryHeap<Head<'a>>,
+}
+
+impl<'a, I> KMerge<'a, I>
+where
+    I: Iterator<Item = (&'a LogLineMeta, &'a str)>,
+{
+    fn new(sources: impl Iterator<Item = (u64, I)>) -> Self {
+        let sources: Vec<_> = sources.collect();
+        let mut merge = Self {
+            heap: BinaryHeap::with_capacity(sources.len()),
+            sources,
+        };
+
+        for source in 0..merge.sources.len() {
+            merge.advance(source);
+        }
+
+        merge
+    }
+
+    fn advance(&mut self, source: usize) {
+        if let Some((meta, content)) = self.sources[source].1.next() {
+            self.heap.push(Head {
+                source,
+                meta,
+                content,
+            });
+        }
+    }
+}
+
+impl<'a, I> Iterator for KMerge<'a, I>
+where
+    I: Iterator<Item = (&'a LogLineMeta, &'a str)>,
+{
+    type Item = MergedLine<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let head = self.heap.pop()?;
+        self.advance(head.source);
+
+        Some((self.sources[head.source].0, head.meta, head.content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cdi_shared::log::{ProcessInfo, ReadTime};
+
+    use super::*;
+    use crate::store::{MemoryStore, block::BLOCK_CAP};
+
+    fn read_at(seq: u64) -> ReadTime {
+        ReadTime {
+            timestamp: 0,
+            elapsed: 0,
+            seq,
+        }
+    }
+
+    #[test]
+    fn merges_by_read_order() {
+        let api = ProcessInfo::new("api".into(), "pnpm dev".into(), None);
+        let db = ProcessInfo::new("db".into(), "postgres".into(), None);
+        let (api_id, db_id) = (api.id, db.id);
+        let mut store = MemoryStore::new(vec![api, db], 0, 4 * BLOCK_CAP);
+
+        for (process_id, content, seq) in [
+            (db_id, "db 0", 0),
+            (api_id, "api 1", 1),
+            (api_id, "api 2", 2),
+            (db_id, "db 3", 3),
+            // Same read order: the earlier snapshot wins, then line id.
+            (db_id, "db 4", 4),
+            (api_id, "api 4", 4),
+            (api_id, "api 5", 5),
+        ] {
+            store.append(process_id, Stream::Stdout, content, read_at(seq));
+        }
+
+        let snapshots = [
+            store.snapshot(api_id).unwrap(),
+            store.snapshot(db_id).unwrap(),
+        ];
+        let merged = MergedView::new(&snapshots);
+
+        let all: Vec<_> = merged
+            .iter()
+            .map(|(id, _, content)| (id, content))
+            .collect();
+        assert_eq!(
+            all,
+            [
+                (db_id, "db 0"),
+                (api_id, "api 1"),
+                (api_id, "api 2"),
+                (db_id, "db 3"),
+                (api_id, "api 4"),
+                (db_id, "db 4"),
+                (api_id, "api 5"),
+            ]
+        );
+
+        let tail: Vec<_> = merged.tail(3).into_iter().map(|(_, _, c)| c).collect();
+        assert_eq!(tail, ["api 4", "db 4", "api 5"]);
+
+        let page: Vec<_> = MergedView::new(&snapshots)
+            .filter(LogQuery::new().limit(2))
+            .iter()
+            .map(|(_, _, c)| c)
+            .collect();
+        assert_eq!(page, ["db 0", "api 1"]);
+    }
+}
@@ -0,0 +1,112 @@
+use std::cell::OnceCell;
+use std::sync::Arc;
+
+use cdi_shared::log::Stream;
+
+use super::{block::Block, cache::BlockCache, query::LogView};
+
+/// Entries of a process dropped to keep it within `max_blocks`, per stream.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct Evicted {
+    pub stdout: u64,
+    pub stderr: u64,
+}
+
+impl Evicted {
+    pub fn add(&mut self, block: &Block) {
+        for meta in block.lines() {
+            match meta.stream {
+                Stream::Stdout => self.stdout += 1,
+                Stream::Stderr => self.stderr += 1,
+            }
+        }
+    }
+}
+
+/// Immutable view of a process's logs at a point in time. Cheap to take
+/// (one `Arc` clone per block) and cheap to drop.
+///
+/// Sealed blocks are decompressed lazily, the first time a query reaches
+/// them, and stay decompressed for the lifetime of the snapshot.
+pub struct LogSnapshot {
+    blocks: Vec<Arc<Block>>,
+    texts: Vec<OnceCell<Arc<str>>>,
+    process_id: u64,
+    cache: Arc<BlockCache>,
+    evicted: Evicted,
+}
+
+impl LogSnapshot {
+    pub(super) fn new(
+        process_id: u64,
+        blocks: Vec<Arc<Block>>,
+        cache: Arc<BlockCache>,
+        evicted: Evicted,
+    ) -> Self {
+        Self {
+            texts: blocks.iter().map(|_| OnceCell::new()).collect(),
+            blocks,
+            process_id,
+            cache,
+            evicted,
+        }
+    }
+
+    pub fn query(&self) -> LogView<'_> {
+        LogView::new(self)
+    }
+
+    #[inline]
+    pub fn process_id(&self) -> u64 {
+        self.process_id
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.iter().map(|block| block.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|block| block.is_empty())
+    }
+
+    /// Id of the oldest entry still stored, read without decompressing.
+    pub fn first_id(&self) -> Option<u64> {
+        self.blocks.first().and_then(|block| block.first_id())
+    }
+
+    /// Entries on `stream`, or on either, evicted before the snapshot was
+    /// taken.
+    pub fn evicted(&self, stream: Option<&Stream>) -> u64 {
+        match stream {
+            Some(Stream::Stdout) => self.evicted.stdout,
+            Some(Stream::Stderr) => self.evicted.stderr,
+            None => self.evicted.stdout + self.evicted.stderr,
+        }
+    }
+
+    /// Stored entries on `stream`, or on either, with ids below `id`. Reads
+    /// only line metadata.
+    pub fn count_before(&self, stream: Option<&Stream>, id: u64) -> u64 {
+        self.blocks
+            .iter()
+            .take_while(|block| block.first_id().is_some_and(|first| first < id))
+            .flat_map(|block| block.lines())
+            .filter(|meta| meta.id < id && stream.is_none_or(|stream| meta.stream == *stream))
+            .count() as u64
+    }
+
+    #[inline]
+    pub(super) fn blocks(&self) -> &[Arc<Block>] {
+        &self.blocks
+    }
+
+    /// String data of the block at `idx`, decompressing it if sealed.
+    pub(super) fn text(&self, idx: usize) -> &str {
+        let block = &self.blocks[idx];
+
+        match block.text() {
+            Some(text) => text,
+            None => self.texts[idx].get_or_init(|| self.cache.get(block)),
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use anyhow::{Error, Result};
+use std::mem;
+
+use anyhow::Result;
 use cdi_shared::log::ProcessInfo;
-use tokio::{sync::mpsc, task::JoinHandle};
+use tokio::{select, sync::mpsc, task::JoinHandle};
 
 use crate::{
     Connection,
@@ -16,59 +18,132 @@ struct ProcessContext {
     info: ProcessInfo,
     conn: Connection,
     handle: JoinHandle<()>,
+    /// Whether the process is stopping to be started again.
+    restarting: bool,
+}
+
+impl ProcessContext {
+    fn spawn(info: ProcessInfo) -> Self {
+        let (supervisor_sender, process_receiver) = mpsc::channel::<Message>(1);
+        let (process_sender, supervisor_receiver) = mpsc::channel::<Message>(1);
+
+        let task = Process::start(
+            info.clone(),
+            Connection {
+                sender: process_sender,
+                receiver: process_receiver,
+            },
+        )
+        .unwrap();
+
+        Self {
+            info,
+            conn: Connection {
+                sender: supervisor_sender,
+                receiver: supervisor_receiver,
+            },
+            handle: task,
+            restarting: false,
+        }
+    }
+
+    /// Asks the process to stop. Fails quietly if it already exited, or
+    /// was asked already: it stops taking messages once stopping, and
+    /// waiting for room would hold up every other command.
+    fn stop(&self) {
+        let _ = self
+            .conn
+            .sender
+            .try_send(Message::Command(ServerCommand::Shutdown));
+    }
+
+    /// Asks the process to stop and sends its id to `exited` once it did,
+    /// without waiting. The waiting task stands in for the process in
+    /// `handle` until then, so shutting down waits for it too.
+    fn restart(&mut self, exited: mpsc::UnboundedSender<u64>) {
+        self.stop();
+        self.restarting = true;
+
+        let process_id = self.info.id;
+        let process = mem::replace(&mut self.handle, tokio::spawn(async {}));
+        self.handle = tokio::spawn(async move {
+            let _ = process.await;
+            let _ = exited.send(process_id);
+        });
+    }
 }
 
 impl Supervisor {
     pub fn start(process_infos: Vec<ProcessInfo>, server_conn: Connection) -> Result<()> {
         let supervisor = Self { server_conn };
-        let _ = tokio::spawn(supervisor.run(process_infos));
+        tokio::spawn(supervisor.run(process_infos));
 
         Ok(())
     }
 
     async fn run(mut self, process_infos: Vec<ProcessInfo>) -> Result<()> {
-        let processes: Vec<ProcessContext> = process_infos
-            .iter()
-            .map(|proc_info| {
-                let (supervisor_sender, process_receiver) = mpsc::channel::<Message>(1);
-                let (process_sender, supervisor_receiver) = mpsc::channel::<Message>(1);
-
-                let task = Process::start(
-                    proc_info.clone(),
-                    Connection {
-                        sender: process_sender,
-                        receiver: process_receiver,
-                    },
-                )
-                .unwrap();
-
-                ProcessContext {
-                    info: proc_info.clone(),
-                    conn: Connection {
-                        sender: supervisor_sender,
-                        receiver: supervisor_receiver,
-                    },
-                    handle: task,
-                }
-            })
+        let mut processes: Vec<ProcessContext> = process_infos
+            .into_iter()
+            .map(ProcessContext::spawn)
             .collect();
 
-        while let Some(msg) = self.server_conn.receiver.recv().await {
+        // Ids of restarting processes that exited, sent by the tasks
+        // waiting on them so the loop keeps taking commands meanwhile.
+        let (exited_sender, mut exited) = mpsc::unbounded_channel::<u64>();
+
+        loop {
+            let msg = select! {
+                msg = self.server_conn.receiver.recv() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                Some(process_id) = exited.recv() => {
+                    if let Some(proc) = processes
+                        .iter_mut()
+                        .find(|p| p.info.id == process_id && p.restarting)
+                    {
+                        // Started afresh, not adopted again.
+                        *proc = ProcessContext::spawn(ProcessInfo {
+                            pid: None,
+                            ..proc.info.clone()
+                        });
+                    }
+                    continue;
+                }
+            };
+
             match msg {
                 Message::Command(cmd) => match cmd {
                     ServerCommand::Shutdown => {
                         for proc in processes.iter() {
-                            let _ = proc
-                                .conn
-                                .sender
-                                .send(Message::Command(ServerCommand::Shutdown))
-                                .await;
+                            proc.stop();
+                        }
+                        for proc in processes {
+                            let _ = proc.handle.await;
                         }
 
+                        let _ = self.server_conn.sender.send(Message::Stopped).await;
                         return Ok(());
                     }
+                    ServerCommand::Stop { process_id } => {
+                        if let Some(proc) = processes.iter_mut().find(|p| p.info.id == process_id) {
+                            proc.restarting = false;
+                            proc.stop();
+                        }
+                    }
+                    ServerCommand::Restart { process_id } => {
+                        let Some(proc) = processes
+                            .iter_mut()
+                            .find(|p| p.info.id == process_id && !p.restarting)
+                        else {
+                            continue;
+                        };
+
+                        proc.restart(exited_sender.clone());
+                    }
                 },
-                _ => todo!(),
+                // Sent by the supervisor, not to it.
+                Message::ProcessOutput { .. } | Message::Stopped => {}
             }
         }
 
@@ -32,7 +32,7 @@ pub fn split_command_into_parts(input: &str) -> Option<(String, Vec<String>)> {
         Some(command) => {
             let args = parts.iter().skip(1).cloned().collect();
 
-            return Some((command.to_string(), args));
+            Some((command.to_string(), args))
         }
         None => None,
     }
@@ -0,0 +1,227 @@
+use std::{borrow::Cow, fmt::Write as _, iter::Peekable, str::Chars};
+
+/// Removes ANSI escape sequences (CSI, OSC and two-byte escapes) from `text`.
+/// Borrows when there is nothing to strip.
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            skip_escape(&mut chars);
+        } else {
+            out.push(c);
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+/// Converts `text` to HTML, escaping it and turning SGR colors and styles
+/// into `<span>`s. The 16 basic colors become `ansi-fg-N` and `ansi-bg-N`
+/// classes so the page can theme them; 256-color and RGB ones are inlined.
+/// Other escape sequences are dropped.
+pub fn to_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut style = Style::default();
+    // The style of the open `<span>`, if any.
+    let mut open = Style::default();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if let Some(params) = skip_escape(&mut chars) {
+                style.apply(&params);
+            }
+            continue;
+        }
+
+        if style != open {
+            if open != Style::default() {
+                out.push_str("</span>");
+            }
+            if style != Style::default() {
+                style.write_tag(&mut out);
+            }
+            open = style;
+        }
+
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+
+    if open != Style::default() {
+        out.push_str("</span>");
+    }
+
+    out
+}
+
+/// Skips the escape sequence after an `ESC`, returning the parameters of an
+/// SGR sequence (`ESC [ ... m`).
+fn skip_escape(chars: &mut Peekable<Chars>) -> Option<String> {
+    match chars.next() {
+        // CSI: parameters and intermediates, terminated by 0x40..=0x7e.
+        Some('[') => {
+            let mut params = String::new();
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    return (c == 'm').then_some(params);
+                }
+                params.push(c);
+            }
+        }
+        // OSC: terminated by BEL or ST (ESC \).
+        Some(']') => {
+            while let Some(c) = chars.next() {
+                if c == '\x07' {
+                    break;
+                }
+                if c == '\x1b' && chars.peek() == Some(&'\\') {
+                    chars.next();
+                    break;
+                }
+            }
+        }
+        _ => {}
+    }
+
+    None
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Color {
+    /// One of the 256 palette colors.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    /// Applies the `;`-separated parameters of an SGR sequence.
+    fn apply(&mut self, params: &str) {
+        let mut codes = params
+            .split(';')
+            .map(|code| code.parse::<u8>().unwrap_or(0));
+
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.fg = Some(Color::Indexed(code - 30)),
+                38 => self.fg = extended_color(&mut codes),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Indexed(code - 40)),
+                48 => self.bg = extended_color(&mut codes),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some(Color::Indexed(code - 90 + 8)),
+                100..=107 => self.bg = Some(Color::Indexed(code - 100 + 8)),
+                _ => {}
+            }
+        }
+    }
+
+    fn write_tag(&self, out: &mut String) {
+        let mut classes = Vec::new();
+        let mut styles = Vec::new();
+        for (color, name, property) in [(self.fg, "fg", "color"), (self.bg, "bg", "background")] {
+            match color {
+                Some(Color::Indexed(n)) if n < 16 => classes.push(format!("ansi-{name}-{n}")),
+                Some(color) => {
+                    let (r, g, b) = color.rgb();
+                    styles.push(format!("{property}:#{r:02x}{g:02x}{b:02x}"));
+                }
+                None => {}
+            }
+        }
+        if self.bold {
+            styles.push("font-weight:bold".to_string());
+        }
+        if self.dim {
+            styles.push("opacity:0.7".to_string());
+        }
+        if self.italic {
+            styles.push("font-style:italic".to_string());
+        }
+        if self.underline {
+            styles.push("text-decoration:underline".to_string());
+        }
+
+        out.push_str("<span");
+        if !classes.is_empty() {
+            let _ = write!(out, " class=\"{}\"", classes.join(" "));
+        }
+        if !styles.is_empty() {
+            let _ = write!(out, " style=\"{}\"", styles.join(";"));
+        }
+        out.push('>');
+    }
+}
+
+/// The color after a `38` or `48`: `5;n` or `2;r;g;b`.
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match codes.next()? {
+        5 => Some(Color::Indexed(codes.next()?)),
+        2 => Some(Color::Rgb(codes.next()?, codes.next()?, codes.next()?)),
+        _ => None,
+    }
+}
+
+impl Color {
+    /// The xterm value of palette colors past the basic 16.
+    fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(n @ 16..=231) => {
+                let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+                let i = n - 16;
+                (level(i / 36), level(i / 6 % 6), level(i % 6))
+            }
+            Color::Indexed(n @ 232..) => {
+                let gray = 8 + (n - 232) * 10;
+                (gray, gray, gray)
+            }
+            Color::Indexed(_) => (0, 0, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_html() {
+        let html = to_html("\x1b[1;31merror\x1b[0m: <a> \x1b]8;;url\x07\x1b[38;5;208mx\x1b[m");
+        assert_eq!(
+            html,
+            "<span class=\"ansi-fg-1\" style=\"font-weight:bold\">error</span>: \
+             &lt;a&gt; <span style=\"color:#ff8700\">x</span>"
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorSeverity {
+    Warning,
+    Error,
+}
+
+impl ErrorSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorSeverity::Warning => "warning",
+            ErrorSeverity::Error => "error",
+        }
+    }
+}
+
+/// A `file:line[:column]` reference, relative to the process's cwd unless
+/// absolute.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub file: String,
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{column}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A compiler diagnostic, stack trace or error log line found in a process's
+/// output. Multi-line diagnostics cover the stored lines
+/// `first_line_id..=last_line_id`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DetectedError {
+    pub id: u64,
+    pub process_id: u64,
+    /// Detector that recognised it: `cargo`, `tsc`, `eslint`, `go`,
+    /// `python`, `node` or `generic`.
+    pub source: String,
+    pub severity: ErrorSeverity,
+    pub message: String,
+    pub location: Option<Location>,
+    pub first_line_id: u64,
+    pub last_line_id: u64,
+    pub timestamp: u128,
+}
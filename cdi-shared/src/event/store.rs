@@ -1,17 +1,49 @@
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
-use crate::log::{ProcessStatus, Stream};
+use crate::log::{Level, ProcessStatus, ReadTime, Stream};
+use crate::resource::{ProcessNode, ResourceSample};
 use crate::ro_cell::RoCell;
 
 static STORE_TX: RoCell<mpsc::UnboundedSender<StoreEvent>> = RoCell::new();
 static STORE_RX: RoCell<mpsc::UnboundedReceiver<StoreEvent>> = RoCell::new();
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum StoreEvent {
     AppendLog {
         process_id: u64,
         stream: Stream,
         content: String,
+        time: ReadTime,
+    },
+    /// A whole entry of a replicated history, its lines joined by `\n`.
+    /// Stored as one entry instead of being grouped again.
+    AppendEntry {
+        process_id: u64,
+        stream: Stream,
+        content: String,
+        level: Option<Level>,
+        time: ReadTime,
+    },
+    /// The unterminated line a process is writing, e.g. a prompt or a
+    /// progress bar. Replaced by later partials and dropped once the line is
+    /// appended.
+    PartialLog {
+        process_id: u64,
+        stream: Stream,
+        content: String,
+    },
+    ProcessStarted {
+        process_id: u64,
+        pid: u32,
+        /// Taken before spawning, so every line of the run is read after it.
+        time: ReadTime,
+    },
+    ResourceSample {
+        process_id: u64,
+        sample: ResourceSample,
+        /// The live processes the sample covers, depth-first.
+        tree: Vec<ProcessNode>,
     },
     ProcessExited {
         process_id: u64,
@@ -1,5 +1,4 @@
-use crossterm::event::{KeyEvent};
-// use crossterm::event::{MouseEvent};
+use crossterm::event::{KeyEvent, MouseEvent};
 use tokio::sync::mpsc;
 
 use crate::ro_cell::RoCell;
@@ -10,7 +9,11 @@ static RX: RoCell<mpsc::UnboundedReceiver<TuiEvent>> = RoCell::new();
 #[derive(Debug)]
 pub enum TuiEvent {
     Key(KeyEvent),
+    Mouse(MouseEvent),
     Render,
+    /// Outcome of work done off the UI loop, e.g. an export, for the
+    /// status line.
+    Message(String),
     // ProcessMessage { process_id: usize, line: String },
     Quit,
 }
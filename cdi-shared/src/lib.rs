@@ -1,4 +1,7 @@
+pub mod ansi;
+pub mod detect;
 pub mod event;
+pub mod resource;
 pub mod ro_cell;
 pub mod log;
 
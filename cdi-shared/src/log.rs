@@ -1,35 +1,185 @@
-#[derive(Clone, Debug, PartialEq, Eq)]
+use std::sync::{
+    LazyLock,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Stream {
     Stdout,
     Stderr,
 }
 
-#[derive(Clone, Debug)]
+/// Severity of a log line, lowest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub const ALL: [Level; 5] = [
+        Level::Trace,
+        Level::Debug,
+        Level::Info,
+        Level::Warn,
+        Level::Error,
+    ];
+
+    /// Parses common spellings (`warning`, `ERR`, `fatal`, ...), ignoring case.
+    pub fn parse(s: &str) -> Option<Level> {
+        let level = match s.to_ascii_lowercase().as_str() {
+            "trace" | "trc" => Level::Trace,
+            "debug" | "dbg" => Level::Debug,
+            "info" | "inf" | "notice" => Level::Info,
+            "warn" | "warning" | "wrn" => Level::Warn,
+            "error" | "err" | "fatal" | "critical" | "crit" | "panic" | "alert" | "emerg" => {
+                Level::Error
+            }
+            _ => return None,
+        };
+
+        Some(level)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Trace => "trace",
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// Per-process counters maintained by the store.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LogStats {
+    pub errors: u64,
+    pub warnings: u64,
+    /// Lines read, by stream.
+    pub stdout_lines: u64,
+    pub stderr_lines: u64,
+    /// Bytes read, line endings excluded, by stream.
+    pub stdout_bytes: u64,
+    pub stderr_bytes: u64,
+    /// Times the service was started, adoptions included.
+    pub starts: u64,
+}
+
+impl LogStats {
+    /// Counts a line read from `stream`.
+    pub fn read(&mut self, stream: &Stream, bytes: usize) {
+        let (lines, total) = match stream {
+            Stream::Stdout => (&mut self.stdout_lines, &mut self.stdout_bytes),
+            Stream::Stderr => (&mut self.stderr_lines, &mut self.stderr_bytes),
+        };
+        *lines += 1;
+        *total += bytes as u64;
+    }
+
+    /// Counts an entry now at `level`, which was counted at `previous`
+    /// before (`None` for new entries).
+    pub fn count(&mut self, level: Option<Level>, previous: Option<Level>) {
+        if level == previous {
+            return;
+        }
+
+        match previous {
+            Some(Level::Error) => self.errors -= 1,
+            Some(Level::Warn) => self.warnings -= 1,
+            _ => {}
+        }
+        match level {
+            Some(Level::Error) => self.errors += 1,
+            Some(Level::Warn) => self.warnings += 1,
+            _ => {}
+        }
+    }
+}
+
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// When a line was read. Taken by the reader task, before the line crosses
+/// the event bus, so a busy store doesn't skew it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadTime {
+    /// Wall clock, milliseconds since the Unix epoch.
+    pub timestamp: u128,
+    /// Monotonic microseconds since `ReadTime::start`; unaffected by clock
+    /// changes.
+    pub elapsed: u64,
+    /// Read order across all processes.
+    pub seq: u64,
+}
+
+impl ReadTime {
+    /// Marks the start of the session `elapsed` is counted from.
+    pub fn start() {
+        LazyLock::force(&START);
+    }
+
+    pub fn now() -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+            elapsed: START.elapsed().as_micros() as u64,
+            seq: SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LogLine {
     pub id: u64,
     pub process_id: u64,
     pub session_id: u64,
     pub timestamp: u128,
+    /// Read order across all processes of the session.
+    pub seq: u64,
     pub stream: Stream,
+    pub level: Option<Level>,
     pub content: String,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ProcessStatus {
     Running,
     Stopped,
     Crashed,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProcessInfo {
     pub id: u64,
     pub name: String,
     pub command: String,
     pub cwd: Option<String>,
+    /// Process group leader while running. Set before the service starts to
+    /// adopt a group left running by a previous session instead.
     pub pid: Option<usize>,
     pub status: ProcessStatus,
     pub exit_code: Option<i32>,
+    /// Pattern of lines that continue the previous entry, on top of the
+    /// built-in stack trace rules.
+    pub multiline: Option<String>,
+    /// TCP ports the service is configured to listen on.
+    pub ports: Vec<u16>,
+    /// When the service last started, in Unix milliseconds.
+    #[serde(default)]
+    pub started_at: Option<u128>,
 }
 
 impl ProcessInfo {
@@ -44,6 +194,9 @@ impl ProcessInfo {
             pid: None,
             status: ProcessStatus::Stopped,
             exit_code: None,
+            multiline: None,
+            ports: Vec::new(),
+            started_at: None,
         }
     }
 
@@ -59,7 +212,7 @@ impl ProcessInfo {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SessionInfo {
     pub id: u64,
     pub started_at: u64,
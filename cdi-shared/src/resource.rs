@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+/// Resource usage of a service's whole process tree at one point in time.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceSample {
+    /// Wall clock, milliseconds since the Unix epoch.
+    pub timestamp: u128,
+    /// Percent of one core, so a busy tree can exceed 100.
+    pub cpu: f32,
+    /// Resident memory in bytes.
+    pub memory: u64,
+    pub threads: u32,
+    /// Open file descriptors.
+    pub fds: u32,
+    /// Live processes in the tree.
+    pub processes: u32,
+    /// Every process in the tree is stopped, e.g. by `SIGSTOP`.
+    pub stopped: bool,
+}
+
+/// A live process in a service's tree.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProcessNode {
+    pub pid: u32,
+    pub ppid: u32,
+    /// Depth below the service's own process, which is 0.
+    pub depth: u32,
+    /// Command line, or the executable name in brackets if it has none.
+    pub command: String,
+    /// Percent of one core.
+    pub cpu: f32,
+    /// Resident memory in bytes.
+    pub memory: u64,
+    /// TCP ports it is listening on.
+    pub ports: Vec<u16>,
+}
@@ -1,4 +1,7 @@
-use ::crossterm::event::{KeyCode as CTKeyCode, KeyEvent, KeyEventKind as KEK};
+use ::crossterm::event::{
+    KeyCode as CTKeyCode, KeyEvent, KeyEventKind as KEK, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
 use ansi_to_tui::IntoText;
 use anyhow::Result;
 use ratatui::{
@@ -6,28 +9,135 @@ use ratatui::{
     buffer::Buffer,
     crossterm::{
         self,
-        event::{self, Event, KeyCode, KeyEventKind, poll},
+        event::{DisableMouseCapture, EnableMouseCapture},
         terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
     },
-    layout::{Constraint, Layout, Rect},
+    layout::{Constraint, Layout, Position, Rect},
     prelude::CrosstermBackend,
-    style::{ self, Modifier, Style, palette::tailwind::{SLATE, YELLOW}, },
-    text::{Line, Text},
+    style::{
+        Color, Modifier, Style,
+        palette::tailwind::{SLATE, YELLOW},
+    },
+    text::{Line, Span},
     widgets::{
-        Block, Borders, HighlightSpacing, List, ListState, Paragraph, StatefulWidget, Widget, Wrap,
+        Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState, Sparkline,
+        StatefulWidget, Widget,
     },
 };
-use std::io;
-use std::time::Duration;
-use tokio::time;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::signals::{self, Signals};
-use cdi_server::{Connection, server::Message};
-use cdi_shared::event::ui::TuiEvent;
+use crate::{
+    copy::{CopyMode, RowSource, copy_to_clipboard},
+    editor,
+    errors::{JUMP_STYLE, error_line},
+    lines::{
+        Timestamps, add_timestamp, content_lines, file_time, fold, level_style, line_text,
+        patch_range,
+    },
+    panes::{MAX_PANES, Pane, Panes},
+    resources,
+    signals::Signals,
+};
+use cdi_config::{Action, Config, Key, KeyChord, Keymap, Mode};
+use cdi_server::{
+    Connection, StoreHandle,
+    client::{self, ClientRequest, ClientResponse},
+    detect::find_locations,
+    server::{Message, ServerCommand},
+    session, signal,
+    store::{ExportFormat, LogLineMeta, LogSnapshot, MergedView},
+};
+use cdi_shared::{
+    detect::{DetectedError, Location},
+    event::ui::TuiEvent,
+    log::{Level, ProcessInfo, ProcessStatus},
+};
 
 const SELECTED_STYLE: Style = Style::new().fg(YELLOW.c600).add_modifier(Modifier::BOLD);
+const HIDDEN_STYLE: Style = Style::new()
+    .fg(SLATE.c600)
+    .add_modifier(Modifier::CROSSED_OUT);
+const REFERENCE_STYLE: Style = Style::new().add_modifier(Modifier::REVERSED);
+
+/// Name of the virtual tab interleaving every process's output.
+const MERGED_TAB: &str = "all";
+
+/// Rows of the errors panel, border included.
+const ERRORS_HEIGHT: u16 = 10;
+
+/// Rows of the process tree panel, border included.
+const TREE_HEIGHT: u16 = 10;
+
+/// Signals the process tree panel can send: action, signal, name.
+const TREE_SIGNALS: [(Action, libc::c_int, &str); 4] = [
+    (Action::TreeTerm, libc::SIGTERM, "TERM"),
+    (Action::TreeKill, libc::SIGKILL, "KILL"),
+    (Action::TreeInt, libc::SIGINT, "INT"),
+    (Action::TreeHup, libc::SIGHUP, "HUP"),
+];
+
+/// Columns of the sidebar, divider included, until it is dragged.
+const SIDEBAR_WIDTH: u16 = 20;
+
+/// Narrowest the sidebar and the output pane can be dragged to.
+const MIN_SIDEBAR_WIDTH: u16 = 8;
+const MIN_OUTPUT_WIDTH: u16 = 20;
+
+/// Lines a mouse wheel notch scrolls.
+const WHEEL_LINES: usize = 3;
+
+/// Entries above the screen copy mode can select from.
+const COPY_HISTORY: usize = 1000;
+
+/// Formats the export prompt offers: action, format.
+const EXPORT_FORMATS: [(Action, ExportFormat); 4] = [
+    (Action::ExportPlain, ExportFormat::Plain),
+    (Action::ExportAnsi, ExportFormat::Ansi),
+    (Action::ExportNdjson, ExportFormat::Ndjson),
+    (Action::ExportHtml, ExportFormat::Html),
+];
+
+/// Rows of the signal picker, border included.
+const SIGNALS_HEIGHT: u16 = signal::SIGNALS.len() as u16 + 1;
+
+/// Rows of the resources panel, border included.
+const RESOURCES_HEIGHT: u16 = 7;
+
+const SPARKLINE_STYLE: Style = Style::new().fg(Color::Green);
+
+/// Lines shown above an error the view jumps to.
+const JUMP_CONTEXT: usize = 2;
+
+/// Service name colors in the merged view, assigned by config order.
+const SERVICE_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::Yellow,
+    Color::LightRed,
+];
+
+pub async fn run(
+    conn: Option<Connection>,
+    store: StoreHandle,
+    config: Config,
+    project_dir: &Path,
+) -> Result<()> {
+    let layout = session::state_file(project_dir, "layout.json");
+    let mut app = App::new(
+        conn,
+        store,
+        config,
+        client::socket_path(project_dir),
+        &layout,
+    )?;
 
-pub async fn run(conn: Connection, services: Vec<String>) -> Result<()> {
     let stdout = io::stdout();
     let mut stdout = stdout.lock();
 
@@ -37,37 +147,34 @@ pub async fn run(conn: Connection, services: Vec<String>) -> Result<()> {
     let term_backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(term_backend)?;
 
-    let mut app = App {
-        state: AppState::default(),
-        conn,
-        process_tab_group: ProcessTabGroup::default(),
-        list_state: ListState::default().with_selected(Some(0)),
-    };
-    for service in services {
-        app.process_tab_group.tabs.push(ProcessTab {
-            name: service,
-            data: vec![],
-        })
-    }
-
     terminal.clear()?;
 
     app.start_loop(&mut terminal).await?;
 
     terminal.clear()?;
     disable_raw_mode()?;
-    crossterm::execute!(terminal.backend_mut(), LeaveAlternateScreen,)?;
+    crossterm::execute!(
+        terminal.backend_mut(),
+        DisableMouseCapture,
+        LeaveAlternateScreen,
+    )?;
 
-    println!("Cleaning up resources");
+    if let Err(e) = app.panes.save(&layout, &app.processes) {
+        eprintln!(
+            "Failed to save the pane layout to {}: {e}",
+            layout.display()
+        );
+    }
 
-    app.conn
-        .sender
-        .send(Message::Command(
-            cdi_server::server::ServerCommand::Shutdown,
-        ))
-        .await?;
+    let Some(conn) = &mut app.conn else {
+        return Ok(());
+    };
 
-    time::sleep(time::Duration::from_secs(1)).await;
+    println!("Cleaning up resources");
+
+    if !conn.shutdown().await {
+        eprintln!("Some services did not exit");
+    }
 
     Ok(())
 }
@@ -79,47 +186,147 @@ enum AppState {
     Quitting,
 }
 
-struct ProcessTab {
-    name: String,
-    data: Vec<String>,
+struct App {
+    state: AppState,
+    /// The in-process server, `None` when attached to a detached one.
+    conn: Option<Connection>,
+    /// Socket of the session, for commands while attached.
+    socket: PathBuf,
+    store: StoreHandle,
+    keymap: Keymap,
+    /// Whether the keys are shown over everything else.
+    help: bool,
+    /// Service to restart once the current key batch is handled.
+    restart: Option<u64>,
+    /// Processes in config order. Sidebar entry `i + 1` is `processes[i]`,
+    /// entry 0 is the merged view.
+    processes: Vec<ProcessInfo>,
+    /// Log panes of the output area. The sidebar selects what the focused
+    /// one shows.
+    panes: Panes,
+    list_state: ListState,
+    /// Processes left out of the merged view.
+    hidden: HashSet<u64>,
+    /// Render JSON lines as `timestamp level message key=value...`.
+    pretty: bool,
+    /// Selection in the errors panel, `None` while it is closed.
+    errors: Option<ListState>,
+    /// Lines of the error last jumped to, highlighted until the next jump.
+    jumped: Option<(u64, RangeInclusive<u64>)>,
+    /// `editor` command template from the config.
+    editor: Option<String>,
+    /// `file:line` references in the focused pane at the last render, top
+    /// to bottom.
+    references: Vec<Reference>,
+    /// Selected reference counted from the bottom, `None` unless selecting.
+    reference: Option<usize>,
+    /// Location to open in the editor once the current key batch is handled.
+    open: Option<(u64, Location)>,
+    /// One-off notice shown in the footer until the next key press.
+    message: Option<String>,
+    /// Search being typed, `None` unless the prompt is open.
+    search_input: Option<String>,
+    /// Show multi-line entries (stack traces) as their first line only.
+    folded: bool,
+    timestamps: Timestamps,
+    /// Whether the resources panel is open.
+    resources: bool,
+    /// Selection in the process tree panel, `None` while it is closed.
+    tree: Option<ListState>,
+    /// Selection in the signal picker, `None` while it is closed.
+    signal_picker: Option<ListState>,
+    /// Whether the picker signals the whole process group or only the
+    /// process cdi started.
+    signal_group: bool,
+    /// Whether the footer asks for the format to export in.
+    export_prompt: bool,
+    /// Copy mode, `None` unless the output pane is frozen for copying.
+    copy: Option<CopyMode>,
+    /// Text to put on the clipboard once the current key batch is handled.
+    clipboard: Option<String>,
+    /// Whether mouse events are wanted, and whether the terminal was last
+    /// told to send them.
+    mouse: bool,
+    mouse_captured: bool,
+    /// Columns of the sidebar, divider included.
+    sidebar_width: u16,
+    /// Sidebar and output area at the last render, for mouse events.
+    sidebar: Rect,
+    logs: Rect,
+    /// Screen rows of the sidebar entries at the last render.
+    tab_rows: Vec<(Range<u16>, usize)>,
+    /// Whether the sidebar divider is being dragged.
+    dragging: bool,
 }
 
-impl Default for ProcessTab {
-    fn default() -> Self {
-        Self {
-            data: vec![],
-            name: String::default(),
-        }
-    }
+/// A `file:line` reference on screen.
+struct Reference {
+    process_id: u64,
+    row: usize,
+    range: Range<usize>,
+    /// Screen columns of `range`, from the left of the output pane.
+    columns: Range<u16>,
+    location: Location,
 }
 
-struct ProcessTabGroup {
-    // selected: usize,
-    tabs: Vec<ProcessTab>,
-}
+impl App {
+    /// The TUI's state before anything is drawn; `layout` is where the pane
+    /// layout was saved.
+    fn new(
+        conn: Option<Connection>,
+        store: StoreHandle,
+        config: Config,
+        socket: PathBuf,
+        layout: &Path,
+    ) -> Result<Self> {
+        let keymap = config.keybindings.keymap().map_err(anyhow::Error::msg)?;
+        let processes = store.processes();
 
-impl Default for ProcessTabGroup {
-    fn default() -> Self {
-        Self {
-            // selected: 0,
-            tabs: Vec::new(),
-        }
+        Ok(App {
+            state: AppState::default(),
+            conn,
+            socket,
+            keymap,
+            help: false,
+            restart: None,
+            panes: Panes::load(layout, &processes),
+            processes,
+            store,
+            list_state: ListState::default().with_selected(Some(0)),
+            hidden: HashSet::new(),
+            pretty: true,
+            errors: None,
+            jumped: None,
+            editor: config.editor,
+            references: Vec::new(),
+            reference: None,
+            open: None,
+            message: None,
+            search_input: None,
+            folded: false,
+            timestamps: Timestamps::default(),
+            resources: false,
+            tree: None,
+            signal_picker: None,
+            signal_group: true,
+            export_prompt: false,
+            copy: None,
+            clipboard: None,
+            mouse: config.mouse.unwrap_or(false),
+            mouse_captured: false,
+            sidebar_width: SIDEBAR_WIDTH,
+            sidebar: Rect::default(),
+            logs: Rect::default(),
+            tab_rows: Vec::new(),
+            dragging: false,
+        })
     }
-}
-
-struct App {
-    state: AppState,
-    conn: Connection,
-    process_tab_group: ProcessTabGroup,
-    list_state: ListState,
-}
 
-impl App {
     async fn start_loop(
         &mut self,
         terminal: &mut Terminal<CrosstermBackend<std::io::StdoutLock<'_>>>,
     ) -> Result<()> {
-        let (mut evt_rx, _signals) = (TuiEvent::take(), Signals::start()?);
+        let (mut evt_rx, signals) = (TuiEvent::take(), Signals::start()?);
         self.render(terminal)?;
 
         let mut events_proccessed = 0;
@@ -134,6 +341,26 @@ impl App {
                 break;
             }
 
+            if self.mouse != self.mouse_captured {
+                self.capture_mouse(terminal, self.mouse)?;
+            }
+
+            if let Some(text) = self.clipboard.take()
+                && let Err(e) = copy_to_clipboard(terminal.backend_mut(), text)
+            {
+                self.message = Some(format!("copy failed: {e}"));
+            }
+
+            if let Some(process_id) = self.restart.take() {
+                self.restart_service(process_id).await;
+            }
+
+            if let Some((process_id, location)) = self.open.take() {
+                self.open_in_editor(terminal, &signals, process_id, &location)
+                    .await?;
+                self.render(terminal)?;
+            }
+
             if events_proccessed >= 50 {
                 events_proccessed = 0;
                 self.render(terminal)?;
@@ -153,11 +380,12 @@ impl App {
     fn disptach(&mut self, event: TuiEvent) -> Result<()> {
         match event {
             TuiEvent::Key(key) => self.dispatch_key(key),
-            // TuiEvent::ProcessMessage { process_id, line } => {
-            //     let tab = &mut self.process_tab_group.tabs[process_id];
-            //     tab.data.push(line.clone());
-            // }
-            _ => {} // TuiEvent::Render => self.render(sel)
+            TuiEvent::Mouse(mouse) => self.dispatch_mouse(mouse),
+            // Rendering pulls fresh data from the store after each batch.
+            TuiEvent::Render => {}
+            TuiEvent::Message(message) => self.message = Some(message),
+            // Also sent by clients, and when the attached server goes away.
+            TuiEvent::Quit => self.state = AppState::Quitting,
         }
 
         Ok(())
@@ -165,14 +393,238 @@ impl App {
 
     #[inline]
     fn dispatch_key(&mut self, key: KeyEvent) {
-        if key.kind == KEK::Press {
+        if key.kind != KEK::Press {
+            return;
+        }
+        self.message = None;
+
+        if let Some(input) = &mut self.search_input {
             match key.code {
-                CTKeyCode::Char('j') | CTKeyCode::Down => self.next_tab(),
-                CTKeyCode::Char('k') | CTKeyCode::Up => self.prev_tab(),
-                CTKeyCode::Char('l') | CTKeyCode::Right => todo!(),
-                CTKeyCode::Char('q') | CTKeyCode::Esc => self.quit(),
+                CTKeyCode::Char(c) => input.push(c),
+                CTKeyCode::Backspace => {
+                    input.pop();
+                }
+                CTKeyCode::Enter => {
+                    let search = self.search_input.take().filter(|input| !input.is_empty());
+                    let pane = self.panes.focused_mut();
+                    pane.search = search;
+                    pane.top = None;
+                }
+                CTKeyCode::Esc => self.search_input = None,
                 _ => {}
             }
+            return;
+        }
+
+        if self.help {
+            self.help = false;
+            return;
+        }
+
+        let action = chord(&key).and_then(|chord| self.keymap.action(self.mode(), chord));
+
+        if self.export_prompt {
+            self.export_prompt = false;
+            if let Some((_, format)) = EXPORT_FORMATS.iter().find(|(a, _)| Some(*a) == action) {
+                self.export(*format);
+            }
+            return;
+        }
+
+        if let Some(copy) = &mut self.copy {
+            let half_page = (copy.height() / 2).max(1) as isize;
+            match action {
+                Some(Action::NextService) => copy.move_by(1),
+                Some(Action::PrevService) => copy.move_by(-1),
+                Some(Action::ScrollPageDown) => copy.move_by(copy.height() as isize),
+                Some(Action::ScrollPageUp) => copy.move_by(-(copy.height() as isize)),
+                Some(Action::CopyHalfPageDown) => copy.move_by(half_page),
+                Some(Action::CopyHalfPageUp) => copy.move_by(-half_page),
+                Some(Action::Follow) => copy.move_to(copy.last_row()),
+                Some(Action::CopyTop) => copy.move_to(0),
+                Some(Action::CopyMode | Action::CopySelect) => copy.toggle_selection(),
+                Some(Action::CopyYank) => {
+                    let text = copy.text(|process_id, line_id| {
+                        let snapshot = self.store.snapshot(process_id)?;
+                        let view = match line_id.checked_sub(1) {
+                            Some(after) => snapshot.query().after(after),
+                            None => snapshot.query(),
+                        };
+                        view.limit(1)
+                            .iter()
+                            .next()
+                            .filter(|(meta, _)| meta.id == line_id)
+                            .map(|(_, content)| content.to_string())
+                    });
+                    // Folded entries copy more lines than their rows.
+                    let lines = text.split('\n').count();
+                    self.clipboard = Some(text);
+                    self.message = Some(format!(
+                        "copied {lines} line{}",
+                        if lines == 1 { "" } else { "s" }
+                    ));
+                    self.copy = None;
+                }
+                Some(Action::Quit) if copy.selecting() => copy.toggle_selection(),
+                Some(Action::Quit) => self.copy = None,
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(selected) = self.reference {
+            match (action, key.code) {
+                (Some(Action::PrevService), _) => self.reference = Some(selected + 1),
+                (Some(Action::NextService), _) => self.reference = Some(selected.saturating_sub(1)),
+                (Some(Action::OpenReference), _) | (_, CTKeyCode::Enter) => {
+                    self.open = self
+                        .selected_reference()
+                        .map(|reference| (reference.process_id, reference.location.clone()));
+                    self.reference = None;
+                }
+                (Some(Action::Quit), _) | (_, CTKeyCode::Esc) => self.reference = None,
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(tree) = &mut self.tree {
+            match (action, key.code) {
+                (Some(Action::NextService), _) => return tree.select_next(),
+                (Some(Action::PrevService), _) => return tree.select_previous(),
+                (Some(Action::ProcessTree | Action::Quit), _) | (_, CTKeyCode::Esc) => {
+                    self.tree = None;
+                    return;
+                }
+                (Some(action), _) => {
+                    if let Some((_, signal, name)) =
+                        TREE_SIGNALS.iter().find(|(a, ..)| *a == action)
+                    {
+                        return self.signal_selected_node(*signal, name);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(picker) = &mut self.signal_picker {
+            match (action, key.code) {
+                (Some(Action::NextService), _) => return picker.select_next(),
+                (Some(Action::PrevService), _) => return picker.select_previous(),
+                (Some(Action::SignalGroup), _) => {
+                    self.signal_group = !self.signal_group;
+                    return;
+                }
+                (_, CTKeyCode::Enter) => return self.signal_selected_process(),
+                (Some(Action::Signal | Action::Quit), _) | (_, CTKeyCode::Esc) => {
+                    self.signal_picker = None;
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(errors) = &mut self.errors {
+            match (action, key.code) {
+                (Some(Action::NextService), _) => return errors.select_next(),
+                (Some(Action::PrevService), _) => return errors.select_previous(),
+                (_, CTKeyCode::Enter) => return self.jump_to_error(),
+                (Some(Action::OpenReference), _) => return self.open_error(),
+                (Some(Action::Errors | Action::Quit), _) | (_, CTKeyCode::Esc) => {
+                    self.errors = None;
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        let Some(action) = action else {
+            return;
+        };
+        match action {
+            Action::NextService => self.next_tab(),
+            Action::PrevService => self.prev_tab(),
+            Action::ToggleHidden => self.toggle_hidden(),
+            Action::CycleLevel => self.panes.focused_mut().cycle_min_level(),
+            Action::Pretty => self.pretty = !self.pretty,
+            Action::Errors => {
+                self.tree = None;
+                self.signal_picker = None;
+                self.errors = Some(ListState::default().with_selected(Some(0)));
+            }
+            Action::OpenReference => self.select_reference(),
+            Action::Search => {
+                self.search_input = Some(self.panes.focused().search.clone().unwrap_or_default())
+            }
+            Action::Fold => self.folded = !self.folded,
+            Action::Export => self.export_prompt = true,
+            Action::CopyMode => self.enter_copy_mode(),
+            Action::ToggleMouse => {
+                self.mouse = !self.mouse;
+                self.message = Some(format!("mouse {}", if self.mouse { "on" } else { "off" }));
+            }
+            Action::Timestamps => self.timestamps = self.timestamps.next(),
+            Action::Resources => self.resources = !self.resources,
+            Action::ProcessTree => {
+                self.errors = None;
+                self.signal_picker = None;
+                self.tree = Some(ListState::default().with_selected(Some(0)));
+            }
+            Action::Signal => {
+                if self.selected_process().is_none() {
+                    self.message = Some("select a service to signal".to_string());
+                    return;
+                }
+                self.errors = None;
+                self.tree = None;
+                self.signal_picker = Some(ListState::default().with_selected(Some(0)));
+            }
+            Action::Restart => match self.selected_process() {
+                Some(info) => self.restart = Some(info.id),
+                None => self.message = Some("select a service to restart".to_string()),
+            },
+            Action::SplitSideBySide => self.split(true),
+            Action::SplitStacked => self.split(false),
+            Action::ClosePane => self.close_pane(),
+            Action::NextPane => self.panes.cycle_focus(true),
+            Action::PrevPane => self.panes.cycle_focus(false),
+            Action::ScrollPageUp => self.page_up(),
+            Action::ScrollPageDown => self.page_down(),
+            Action::Follow => self.panes.focused_mut().top = None,
+            Action::Help => self.help = true,
+            Action::Quit => self.quit(),
+            // Only in their modes.
+            Action::CopyTop
+            | Action::CopyHalfPageDown
+            | Action::CopyHalfPageUp
+            | Action::CopySelect
+            | Action::CopyYank
+            | Action::TreeTerm
+            | Action::TreeKill
+            | Action::TreeInt
+            | Action::TreeHup
+            | Action::SignalGroup
+            | Action::ExportPlain
+            | Action::ExportAnsi
+            | Action::ExportNdjson
+            | Action::ExportHtml => {}
+        }
+    }
+
+    /// Which keys apply, see `Mode::handles`.
+    fn mode(&self) -> Mode {
+        if self.export_prompt {
+            Mode::Export
+        } else if self.copy.is_some() {
+            Mode::Copy
+        } else if self.reference.is_some() {
+            Mode::Normal
+        } else if self.tree.is_some() {
+            Mode::ProcessTree
+        } else if self.signal_picker.is_some() {
+            Mode::SignalPicker
+        } else {
+            Mode::Normal
         }
     }
 
@@ -180,17 +632,9 @@ impl App {
         &mut self,
         terminal: &mut Terminal<CrosstermBackend<std::io::StdoutLock<'_>>>,
     ) -> Result<()> {
-        terminal.draw(|frame| {
-            use Constraint::{Length, Min};
-            let vertical = Layout::vertical([Min(0), Length(1)]);
-            let [inner_area, _footer_area] = vertical.areas(frame.area());
+        self.processes = self.store.processes();
 
-            let horizontal = Layout::horizontal([Length(20), Min(0)]);
-            let [tabs_area, output_area] = horizontal.areas(inner_area);
-
-            self.render_tabs(tabs_area, frame.buffer_mut());
-            self.render_selected_process_tab(output_area, frame.buffer_mut());
-        })?;
+        terminal.draw(|frame| frame.render_widget(&mut *self, frame.area()))?;
 
         Ok(())
     }
@@ -201,60 +645,1344 @@ impl App {
     }
 
     pub fn next_tab(&mut self) {
-        self.list_state.select_next();
+        let last = self.processes.len();
+        let pane = self.panes.focused_mut();
+        pane.entry = (pane.entry + 1).min(last);
+        pane.top = None;
     }
 
     pub fn prev_tab(&mut self) {
-        self.list_state.select_previous();
+        let pane = self.panes.focused_mut();
+        pane.entry = pane.entry.saturating_sub(1);
+        pane.top = None;
     }
 
-    fn render_tabs(&mut self, area: Rect, buf: &mut Buffer) {
-        let titles = self
-            .process_tab_group
-            .tabs
+    /// Splits the focused pane side by side or stacked.
+    fn split(&mut self, side_by_side: bool) {
+        if !self.panes.split(side_by_side) {
+            self.message = Some(format!("at most {MAX_PANES} panes"));
+        }
+    }
+
+    fn close_pane(&mut self) {
+        if !self.panes.close() {
+            self.message = Some("the last pane cannot be closed".to_string());
+        }
+    }
+
+    /// Scrolls the focused single-process view up a screen, leaving follow
+    /// mode.
+    pub fn page_up(&mut self) {
+        let focus = self.panes.focus();
+        self.scroll_up(focus, self.panes.focused().viewport());
+    }
+
+    /// Scrolls down a screen, back to following once the tail is reached.
+    pub fn page_down(&mut self) {
+        let focus = self.panes.focus();
+        self.scroll_down(focus, self.panes.focused().viewport());
+    }
+
+    /// Scrolls the single-process view of pane `idx` up `entries`, leaving
+    /// follow mode.
+    pub fn scroll_up(&mut self, idx: usize, entries: usize) {
+        let pane = self.panes.get(idx);
+        let Some(snapshot) = self
+            .pane_process(pane)
+            .and_then(|info| self.store.snapshot(info.id))
+        else {
+            return;
+        };
+
+        let view = snapshot.query().filter(pane.log_query());
+        let top = match pane.top {
+            Some(top) => top,
+            None => match view.tail(pane.viewport()).first() {
+                Some((meta, _)) => meta.id,
+                None => return,
+            },
+        };
+
+        let page = snapshot
+            .query()
+            .filter(pane.log_query().before(top))
+            .tail(entries);
+        self.panes.get_mut(idx).top = Some(page.first().map_or(top, |(meta, _)| meta.id));
+    }
+
+    /// Scrolls pane `idx` down `entries`, back to following once the tail
+    /// is reached.
+    pub fn scroll_down(&mut self, idx: usize, entries: usize) {
+        let pane = self.panes.get(idx);
+        let (Some(top), Some(snapshot)) = (
+            pane.top,
+            self.pane_process(pane)
+                .and_then(|info| self.store.snapshot(info.id)),
+        ) else {
+            return;
+        };
+
+        let view = snapshot.query().filter(pane.log_query_from(top));
+        let mut lines = view.iter().skip(entries);
+        let top = match lines.next() {
+            // Stop scrolling once the last page is on screen.
+            Some((meta, _)) if lines.nth(pane.viewport().saturating_sub(2)).is_some() => {
+                Some(meta.id)
+            }
+            _ => None,
+        };
+        self.panes.get_mut(idx).top = top;
+    }
+
+    /// Starts picking a `file:line` reference on screen to open, beginning
+    /// with the newest. A following single-process view stops scrolling so
+    /// the references stay put.
+    pub fn select_reference(&mut self) {
+        if self.references.is_empty() {
+            self.message = Some("no file references on screen".to_string());
+            return;
+        }
+
+        let pane = self.panes.focused();
+        if pane.top.is_none()
+            && let Some(snapshot) = self
+                .selected_process()
+                .and_then(|info| self.store.snapshot(info.id))
+        {
+            let tail = snapshot
+                .query()
+                .filter(pane.log_query())
+                .tail(pane.viewport());
+            self.panes.focused_mut().top = tail.first().map(|(meta, _)| meta.id);
+        }
+
+        self.reference = Some(0);
+    }
+
+    fn selected_reference(&self) -> Option<&Reference> {
+        let selected = self.reference?.min(self.references.len().checked_sub(1)?);
+        self.references.get(self.references.len() - 1 - selected)
+    }
+
+    /// Opens the location of the error selected in the errors panel.
+    fn open_error(&mut self) {
+        let errors = self.errors();
+        let error = self
+            .errors
+            .as_ref()
+            .and_then(|state| state.selected())
+            .and_then(|idx| errors.get(idx.min(errors.len().saturating_sub(1))));
+
+        match error {
+            Some(DetectedError {
+                process_id,
+                location: Some(location),
+                ..
+            }) => self.open = Some((*process_id, location.clone())),
+            Some(_) => self.message = Some("error has no file location".to_string()),
+            None => {}
+        }
+    }
+
+    /// Sends `signal` to the process selected in the process tree panel.
+    fn signal_selected_node(&mut self, signal: libc::c_int, name: &str) {
+        let Some(info) = self.selected_process() else {
+            return;
+        };
+        let tree = self.store.process_tree(info.id);
+        let Some(node) = self
+            .tree
+            .as_ref()
+            .and_then(|state| state.selected())
+            .and_then(|idx| tree.get(idx.min(tree.len().saturating_sub(1))))
+        else {
+            return;
+        };
+
+        self.message = Some(
+            match signal::signal_descendant(&self.store, info.id, node.pid, signal) {
+                Ok(()) => format!("sent SIG{name} to {}", node.pid),
+                Err(e) => format!("SIG{name} to {} failed: {e}", node.pid),
+            },
+        );
+    }
+
+    /// Sends the signal selected in the signal picker to the selected
+    /// service.
+    fn signal_selected_process(&mut self) {
+        let Some(info) = self.selected_process() else {
+            return;
+        };
+        let Some((name, signal, _)) = self
+            .signal_picker
+            .as_ref()
+            .and_then(|state| state.selected())
+            .map(|idx| signal::SIGNALS[idx.min(signal::SIGNALS.len() - 1)])
+        else {
+            return;
+        };
+
+        let target = if self.signal_group {
+            "process group"
+        } else {
+            "process"
+        };
+        self.message = Some(
+            match signal::signal_service(&self.store, info.id, signal, self.signal_group) {
+                Ok(()) => format!("sent SIG{name} to the {} {target}", info.name),
+                Err(e) => format!("SIG{name} to {} failed: {e}", info.name),
+            },
+        );
+        self.signal_picker = None;
+    }
+
+    /// Restarts a service through the server in this process, or the one
+    /// attached to.
+    async fn restart_service(&mut self, process_id: u64) {
+        let Some(name) = self
+            .processes
             .iter()
-            .map(|tab| tab.name.clone());
+            .find(|info| info.id == process_id)
+            .map(|info| info.name.clone())
+        else {
+            return;
+        };
 
-        let list = List::new(titles)
+        let result = match &self.conn {
+            Some(conn) => conn
+                .sender
+                .send(Message::Command(ServerCommand::Restart { process_id }))
+                .await
+                .map_err(|e| e.to_string()),
+            None => {
+                let request = ClientRequest::RestartProcess {
+                    process_id: name.clone(),
+                };
+                match client::request(&self.socket, &request).await {
+                    Ok(ClientResponse::Error { message }) => Err(message),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+        };
+        self.message = Some(match result {
+            Ok(()) => format!("restarting {name}"),
+            Err(e) => format!("restarting {name} failed: {e}"),
+        });
+    }
+
+    /// Tells the terminal whether to send mouse events. Without them it
+    /// handles the mouse itself, so text can be selected natively.
+    fn capture_mouse(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::StdoutLock<'_>>>,
+        capture: bool,
+    ) -> Result<()> {
+        if capture {
+            crossterm::execute!(terminal.backend_mut(), EnableMouseCapture)?;
+        } else {
+            crossterm::execute!(terminal.backend_mut(), DisableMouseCapture)?;
+        }
+        self.mouse_captured = capture;
+
+        Ok(())
+    }
+
+    fn dispatch_mouse(&mut self, mouse: MouseEvent) {
+        let position = Position::new(mouse.column, mouse.row);
+        let divider = self.sidebar.right().saturating_sub(1);
+
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.message = None;
+                if mouse.column == divider && self.sidebar.contains(position) {
+                    self.dragging = true;
+                } else if self.sidebar.contains(position) {
+                    self.click_tab(mouse.row);
+                } else if self.logs.contains(position) {
+                    self.click_output(position);
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging => {
+                let total = self.sidebar.width + self.logs.width;
+                let max = total
+                    .saturating_sub(MIN_OUTPUT_WIDTH)
+                    .max(MIN_SIDEBAR_WIDTH);
+                self.sidebar_width = (mouse.column + 1).clamp(MIN_SIDEBAR_WIDTH, max);
+            }
+            MouseEventKind::Up(_) => self.dragging = false,
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let up = mouse.kind == MouseEventKind::ScrollUp;
+                if self.sidebar.contains(position) {
+                    if up { self.prev_tab() } else { self.next_tab() }
+                } else if let Some(copy) = &mut self.copy {
+                    let lines = WHEEL_LINES as isize;
+                    copy.move_by(if up { -lines } else { lines });
+                } else if let Some(idx) = self.panes.at(position) {
+                    if up {
+                        self.scroll_up(idx, WHEEL_LINES)
+                    } else {
+                        self.scroll_down(idx, WHEEL_LINES)
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Selects the sidebar entry on screen row `row`.
+    fn click_tab(&mut self, row: u16) {
+        if let Some((_, idx)) = self.tab_rows.iter().find(|(rows, _)| rows.contains(&row)) {
+            let pane = self.panes.focused_mut();
+            pane.entry = *idx;
+            pane.top = None;
+        }
+    }
+
+    /// Focuses the pane under `position`, or opens the `file:line` reference
+    /// under it in the focused pane.
+    fn click_output(&mut self, position: Position) {
+        if self.copy.is_some() {
+            return;
+        }
+
+        let focus = self.panes.focus();
+        if self.panes.focus_at(position) != Some(focus) {
+            self.reference = None;
+            return;
+        }
+
+        let area = self.panes.focused().area;
+        if !area.contains(position) {
+            return;
+        }
+        let row = (position.y - area.y) as usize;
+        let column = position.x - area.x;
+        self.open = self
+            .references
+            .iter()
+            .find(|reference| reference.row == row && reference.columns.contains(&column))
+            .map(|reference| (reference.process_id, reference.location.clone()));
+    }
+
+    /// Runs the editor on `location`, handing it the terminal until it exits.
+    async fn open_in_editor(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::StdoutLock<'_>>>,
+        signals: &Signals,
+        process_id: u64,
+        location: &Location,
+    ) -> Result<()> {
+        let cwd = self
+            .processes
+            .iter()
+            .find(|info| info.id == process_id)
+            .and_then(|info| info.cwd.clone());
+        let path = editor::resolve(&location.file, cwd.as_deref());
+        if !path.exists() {
+            self.message = Some(format!("{} not found", path.display()));
+            return Ok(());
+        }
+        let command = editor::command(self.editor.as_deref(), &path, location);
+
+        signals.stop().await;
+        self.capture_mouse(terminal, false)?;
+        disable_raw_mode()?;
+        crossterm::execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+
+        let status = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .status()
+            .await;
+
+        enable_raw_mode()?;
+        crossterm::execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+        terminal.clear()?;
+        self.capture_mouse(terminal, self.mouse)?;
+        signals.resume().await;
+
+        match status {
+            Ok(status) if !status.success() => {
+                self.message = Some(format!("`{command}` exited with {status}"))
+            }
+            Err(e) => self.message = Some(format!("failed to run `{command}`: {e}")),
+            Ok(_) => {}
+        }
+
+        Ok(())
+    }
+
+    /// Errors of the selected process, or of all processes in the merged
+    /// view.
+    fn errors(&self) -> Vec<DetectedError> {
+        self.store
+            .errors(self.selected_process().map(|info| info.id))
+    }
+
+    /// Shows the selected error's process with the error near the top of
+    /// the output pane.
+    fn jump_to_error(&mut self) {
+        let errors = self.errors();
+        let Some(error) = self
+            .errors
+            .take()
+            .and_then(|state| state.selected())
+            .and_then(|idx| errors.get(idx.min(errors.len().saturating_sub(1))))
+        else {
+            return;
+        };
+
+        let Some(idx) = self
+            .processes
+            .iter()
+            .position(|info| info.id == error.process_id)
+        else {
+            return;
+        };
+        let top = self.store.snapshot(error.process_id).and_then(|snapshot| {
+            let context = snapshot
+                .query()
+                .filter(self.panes.focused().log_query().before(error.first_line_id))
+                .tail(JUMP_CONTEXT);
+            context.first().map(|(meta, _)| meta.id)
+        });
+        let pane = self.panes.focused_mut();
+        pane.entry = idx + 1;
+        pane.top = Some(top.unwrap_or(error.first_line_id));
+        self.jumped = Some((error.process_id, error.first_line_id..=error.last_line_id));
+    }
+
+    /// Freezes the focused pane, with some history above it, for selecting
+    /// lines to copy.
+    fn enter_copy_mode(&mut self) {
+        let pane = self.panes.focused();
+        let rows = self.output_rows(pane, pane.viewport(), COPY_HISTORY);
+        if rows.is_empty() {
+            self.message = Some("nothing to copy".to_string());
+            return;
+        }
+
+        self.copy = Some(CopyMode::new(rows, pane.viewport()));
+    }
+
+    /// Saves the entries of the focused pane, filters applied, to a file in
+    /// the current directory. The merged view saves the visible processes.
+    fn export(&mut self, format: ExportFormat) {
+        let (name, process_ids) = match self.selected_process() {
+            Some(info) => (info.name.clone(), vec![info.id]),
+            None => (
+                MERGED_TAB.to_string(),
+                self.processes
+                    .iter()
+                    .filter(|info| !self.hidden.contains(&info.id))
+                    .map(|info| info.id)
+                    .collect(),
+            ),
+        };
+        let name: String = name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+                _ => '_',
+            })
+            .collect();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = format!("cdi-{name}-{}.{}", file_time(now), format.extension());
+
+        // Written off the UI loop, the result shows up as a message.
+        self.message = Some(format!("exporting to {path}"));
+        let store = self.store.clone();
+        let query = self.panes.focused().log_query();
+        tokio::task::spawn_blocking(move || {
+            let result = File::create(&path).and_then(|file| {
+                let mut out = BufWriter::new(file);
+                let entries = store.export(&process_ids, query, format, &mut out)?;
+                out.flush()?;
+                Ok(entries)
+            });
+            TuiEvent::Message(match result {
+                Ok(entries) => format!("exported {entries} entries to {path}"),
+                Err(e) => format!("export to {path} failed: {e}"),
+            })
+            .emit();
+        });
+    }
+
+    /// Shows or hides the selected process in the merged view.
+    pub fn toggle_hidden(&mut self) {
+        if let Some(info) = self.selected_process() {
+            let id = info.id;
+            if !self.hidden.remove(&id) {
+                self.hidden.insert(id);
+            }
+        }
+    }
+
+    /// Process of the focused pane, `None` on the merged view.
+    fn selected_process(&self) -> Option<&ProcessInfo> {
+        self.pane_process(self.panes.focused())
+    }
+
+    /// `None` when `pane` shows the merged view.
+    fn pane_process(&self, pane: &Pane) -> Option<&ProcessInfo> {
+        pane.entry
+            .checked_sub(1)
+            .and_then(|idx| self.processes.get(idx))
+    }
+
+    fn service_color(&self, process_id: u64) -> Color {
+        let idx = self
+            .processes
+            .iter()
+            .position(|info| info.id == process_id)
+            .unwrap_or(0);
+
+        SERVICE_COLORS[idx % SERVICE_COLORS.len()]
+    }
+
+    fn render_tabs(&mut self, area: Rect, buf: &mut Buffer) {
+        let merged = ListItem::new(MERGED_TAB);
+        let services = self.processes.iter().map(|info| {
+            let style = if self.hidden.contains(&info.id) {
+                HIDDEN_STYLE
+            } else {
+                Style::new().fg(self.service_color(info.id))
+            };
+
+            let mut spans = vec![Span::styled(info.name.clone(), style)];
+            if let Some(stats) = self.store.stats(info.id) {
+                if stats.errors > 0 {
+                    spans.push(Span::styled(
+                        format!(" E{}", stats.errors),
+                        level_style(Some(Level::Error)),
+                    ));
+                }
+                if stats.warnings > 0 {
+                    spans.push(Span::styled(
+                        format!(" W{}", stats.warnings),
+                        level_style(Some(Level::Warn)),
+                    ));
+                }
+            }
+
+            let mut lines = vec![Line::from(spans)];
+            if info.status == ProcessStatus::Running
+                && let Some(sample) = self.store.resources(info.id).last()
+            {
+                let mut figures = vec![Span::styled(
+                    format!("  {}", resources::compact(sample)),
+                    HIDDEN_STYLE.remove_modifier(Modifier::CROSSED_OUT),
+                )];
+                if sample.stopped {
+                    figures.push(Span::styled(" paused", level_style(Some(Level::Warn))));
+                }
+                figures.extend(
+                    resources::listening_ports(&self.store.process_tree(info.id))
+                        .into_iter()
+                        .map(|port| Span::styled(format!(" :{port}"), resources::PORT_STYLE)),
+                );
+                lines.push(Line::from(figures));
+            }
+
+            ListItem::new(lines)
+        });
+
+        let items: Vec<ListItem> = std::iter::once(merged).chain(services).collect();
+        let heights: Vec<u16> = items.iter().map(|item| item.height() as u16).collect();
+        let list = List::new(items)
             .highlight_style(SELECTED_STYLE)
             .highlight_symbol("> ")
             .highlight_spacing(HighlightSpacing::Always)
             .block(Block::new().borders(Borders::RIGHT));
 
-        StatefulWidget::render(list, area, buf, &mut self.list_state)
+        self.list_state.select(Some(self.panes.focused().entry));
+        StatefulWidget::render(list, area, buf, &mut self.list_state);
+
+        self.sidebar = area;
+        self.tab_rows.clear();
+        let mut y = area.y;
+        for (idx, height) in heights.iter().enumerate().skip(self.list_state.offset()) {
+            if y >= area.bottom() {
+                break;
+            }
+            self.tab_rows.push((y..y + height, idx));
+            y += height;
+        }
+    }
+
+    /// Draws pane `idx` in `area`, titled when the output area is split.
+    fn render_pane(&mut self, idx: usize, area: Rect, buf: &mut Buffer) {
+        let focused = idx == self.panes.focus();
+        let area = if self.panes.len() > 1 {
+            let borders = match area.x > self.logs.x {
+                true => Borders::TOP | Borders::LEFT,
+                false => Borders::TOP,
+            };
+            let block = Block::new()
+                .borders(borders)
+                .title(self.pane_title(self.panes.get(idx), focused));
+            let inner = block.inner(area);
+            block.render(area, buf);
+            inner
+        } else {
+            area
+        };
+        self.panes.get_mut(idx).area = area;
+
+        if focused && let Some(copy) = &mut self.copy {
+            self.references.clear();
+            return copy.render(area, buf);
+        }
+
+        let height = area.height as usize;
+        let rows = self.output_rows(self.panes.get(idx), height, 0);
+        let start = rows.len().saturating_sub(height);
+        let mut rows: Vec<(RowSource, Line)> = rows.into_iter().skip(start).collect();
+        if !focused {
+            for (row, (_, line)) in rows.iter().enumerate() {
+                buf.set_line(area.x, area.y + row as u16, line, area.width);
+            }
+            return;
+        }
+
+        self.references = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, (source, line))| {
+                let process_id = source.process_id();
+                let text = line_text(line);
+                find_locations(&text)
+                    .into_iter()
+                    .map(move |(range, location)| {
+                        let start = Line::raw(&text[..range.start]).width() as u16;
+                        let end = start + Line::raw(&text[range.clone()]).width() as u16;
+                        Reference {
+                            process_id,
+                            row,
+                            range,
+                            columns: start..end,
+                            location,
+                        }
+                    })
+            })
+            .collect();
+        if let Some(reference) = self.selected_reference() {
+            patch_range(
+                &mut rows[reference.row].1,
+                reference.range.clone(),
+                REFERENCE_STYLE,
+            );
+        }
+
+        for (row, (_, line)) in rows.iter().enumerate() {
+            buf.set_line(area.x, area.y + row as u16, line, area.width);
+        }
+    }
+
+    /// Service shown by `pane` and its filters.
+    fn pane_title(&self, pane: &Pane, focused: bool) -> Line<'static> {
+        let (name, style) = match self.pane_process(pane) {
+            Some(info) => (
+                info.name.clone(),
+                Style::new().fg(self.service_color(info.id)),
+            ),
+            None => (MERGED_TAB.to_string(), Style::new()),
+        };
+        let style = match focused {
+            true => style.add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            false => style,
+        };
+
+        let mut title = vec![Span::styled(format!(" {name} "), style)];
+        if let Some(search) = &pane.search {
+            title.push(Span::raw(format!(" /{search}")));
+        }
+        if let Some(level) = pane.min_level {
+            title.push(Span::raw(" >= "));
+            title.push(Span::styled(level.as_str(), level_style(Some(level))));
+        }
+        if pane.top.is_some() && pane.entry > 0 {
+            title.push(Span::raw(" scrolled"));
+        }
+
+        Line::from(title)
+    }
+
+    fn render_footer(&self, area: Rect, buf: &mut Buffer) {
+        let mut status = Vec::new();
+        if let Some(input) = &self.search_input {
+            status.push(Span::raw(format!("/{input}█")));
+            buf.set_line(area.x, area.y, &Line::from(status), area.width);
+            return;
+        }
+
+        if let Some(copy) = &self.copy {
+            status.push(Span::styled(" COPY", SELECTED_STYLE));
+            if copy.selecting() {
+                status.push(Span::raw(format!(" {} selected", copy.selection().count())));
+            }
+            status.push(Span::raw(format!(
+                "  {}/{} move  {} select  {} yank  {}/{} top/bottom  {} exit",
+                self.key_hint(Action::NextService),
+                self.key_hint(Action::PrevService),
+                self.key_hint(Action::CopySelect),
+                self.key_hint(Action::CopyYank),
+                self.key_hint(Action::CopyTop),
+                self.key_hint(Action::Follow),
+                self.key_hint(Action::Quit),
+            )));
+            buf.set_line(area.x, area.y, &Line::from(status), area.width);
+            return;
+        }
+
+        if self.export_prompt {
+            let formats: Vec<String> = EXPORT_FORMATS
+                .iter()
+                .map(|(action, format)| format!("{} {format}", self.key_hint(*action)))
+                .collect();
+            status.push(Span::raw(format!(
+                " export as: {}  Esc cancel",
+                formats.join("  ")
+            )));
+            buf.set_line(area.x, area.y, &Line::from(status), area.width);
+            return;
+        }
+
+        let pane = self.panes.focused();
+        if self.panes.len() > 1 {
+            status.push(Span::raw(format!(
+                " pane {}/{}",
+                self.panes.focus() + 1,
+                self.panes.len()
+            )));
+        }
+        if let Some(search) = &pane.search {
+            status.push(Span::styled(format!(" /{search}"), SELECTED_STYLE));
+        }
+        if self.folded {
+            status.push(Span::raw(" folded"));
+        }
+        if let Some(level) = pane.min_level {
+            status.push(Span::raw(" level >= "));
+            status.push(Span::styled(level.as_str(), level_style(Some(level))));
+        }
+        if !self.pretty {
+            status.push(Span::raw(" raw"));
+        }
+        match self.timestamps {
+            Timestamps::Hidden => {}
+            Timestamps::Absolute => status.push(Span::raw(" local time")),
+            Timestamps::Relative => status.push(Span::raw(" since start")),
+        }
+        if pane.top.is_some() && self.selected_process().is_some() {
+            status.push(Span::raw(format!(
+                " scrolled, {} to follow",
+                self.key_hint(Action::Follow)
+            )));
+        }
+        if let Some(reference) = self.selected_reference() {
+            status.push(Span::raw(format!(
+                " open {} ({}/{} select, {} open, {} cancel)",
+                reference.location,
+                self.key_hint(Action::NextService),
+                self.key_hint(Action::PrevService),
+                self.key_hint(Action::OpenReference),
+                self.key_hint(Action::Quit),
+            )));
+        }
+        if let Some(message) = &self.message {
+            status.push(Span::styled(
+                format!(" {message}"),
+                level_style(Some(Level::Warn)),
+            ));
+        }
+        if status.is_empty() {
+            status.push(Span::styled(
+                format!(" {} for keys", self.key_hint(Action::Help)),
+                HIDDEN_STYLE.remove_modifier(Modifier::CROSSED_OUT),
+            ));
+        }
+
+        buf.set_line(area.x, area.y, &Line::from(status), area.width);
+    }
+
+    /// First key of `action`, for hints.
+    fn key_hint(&self, action: Action) -> String {
+        match self.keymap.keys(action).first() {
+            Some(key) => key.to_string(),
+            None => "-".to_string(),
+        }
+    }
+
+    /// Every bound action with its keys, over the middle of `area`, in as
+    /// many columns as it takes.
+    fn render_help(&self, area: Rect, buf: &mut Buffer) {
+        let rows: Vec<(String, &str)> = self
+            .keymap
+            .iter()
+            .filter(|(_, keys)| !keys.is_empty())
+            .map(|(action, keys)| {
+                let keys: Vec<String> = keys.iter().map(KeyChord::to_string).collect();
+                (keys.join(" "), action.description())
+            })
+            .collect();
+        let keys_width = rows.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+        let column_width = rows
+            .iter()
+            .map(|(_, description)| keys_width + 2 + description.len())
+            .max()
+            .unwrap_or(0) as u16;
+
+        let height = area.height.saturating_sub(4).max(1);
+        let columns = rows.len().div_ceil(height as usize).max(1) as u16;
+        let width = (columns * (column_width + 2) + 2).min(area.width);
+        let height = (rows.len().div_ceil(columns as usize) as u16 + 2).min(area.height);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        Clear.render(popup, buf);
+        let block = Block::bordered().title(" keys: any key to close ");
+        let inner = block.inner(popup);
+        block.render(popup, buf);
+
+        let per_column = inner.height.max(1) as usize;
+        for (idx, (keys, description)) in rows.iter().enumerate() {
+            let x = inner.x + 1 + (idx / per_column) as u16 * (column_width + 2);
+            let y = inner.y + (idx % per_column) as u16;
+            if x >= inner.right() || y >= inner.bottom() {
+                continue;
+            }
+            let line = Line::from(vec![
+                Span::styled(format!("{keys:<keys_width$}  "), SELECTED_STYLE),
+                Span::raw(*description),
+            ]);
+            buf.set_line(x, y, &line, inner.right() - x);
+        }
+    }
+
+    fn render_errors(&mut self, area: Rect, buf: &mut Buffer) {
+        let merged = self.selected_process().is_none();
+        let errors = self.errors();
+        let items: Vec<ListItem> = errors
+            .iter()
+            .map(|error| {
+                let service = merged
+                    .then(|| {
+                        self.processes
+                            .iter()
+                            .find(|info| info.id == error.process_id)
+                    })
+                    .flatten()
+                    .map(|info| (info.name.as_str(), self.service_color(info.id)));
+
+                ListItem::new(error_line(error, service))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .highlight_style(SELECTED_STYLE)
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always)
+            .block(
+                Block::new()
+                    .borders(Borders::TOP)
+                    .title(format!(" errors ({}) ", errors.len())),
+            );
+
+        if let Some(state) = &mut self.errors {
+            StatefulWidget::render(list, area, buf, state);
+        }
     }
 
-    fn render_selected_process_tab(&self, area: Rect, buf: &mut Buffer) {
-        let selected = self.list_state.selected().unwrap_or(0);
-        let tab = &self.process_tab_group.tabs[selected];
-        let raw_text: Vec<Text> = tab.data.as_slice()
-            [tab.data.len().saturating_sub(area.rows().count())..]
-            .to_vec()
+    /// Live processes of the selected service, with the keys for the signals
+    /// that can be sent to them.
+    fn render_tree(&mut self, area: Rect, buf: &mut Buffer) {
+        let keys: Vec<String> = TREE_SIGNALS
             .iter()
-            .map(|line| line.into_text().unwrap())
+            .map(|(action, _, name)| format!("{} {name}", self.key_hint(*action)))
             .collect();
+        let block = Block::new().borders(Borders::TOP);
 
-        let mut line_idx = 0;
+        let Some(info) = self.selected_process() else {
+            let inner = block.inner(area);
+            block.title(" processes ").render(area, buf);
+            buf.set_line(
+                inner.x,
+                inner.y,
+                &Line::raw("select a service to see its processes"),
+                inner.width,
+            );
+            return;
+        };
 
-        for text in raw_text.as_slice() {
-            for line in text.iter() {
-                buf.set_line(area.x, line_idx, line, area.width);
-                line_idx += 1;
+        let tree = self.store.process_tree(info.id);
+        let items: Vec<ListItem> = tree
+            .iter()
+            .map(|node| ListItem::new(resources::node_line(node)))
+            .collect();
+        let list = List::new(items)
+            .highlight_style(SELECTED_STYLE)
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always)
+            .block(block.title(format!(
+                " processes: {} ({})  {} ",
+                info.name,
+                tree.len(),
+                keys.join("  ")
+            )));
+
+        if let Some(state) = &mut self.tree {
+            StatefulWidget::render(list, area, buf, state);
+        }
+    }
+
+    fn render_signal_picker(&mut self, area: Rect, buf: &mut Buffer) {
+        let Some(info) = self.selected_process() else {
+            return;
+        };
+
+        let items: Vec<ListItem> = signal::SIGNALS
+            .iter()
+            .map(|(name, _, description)| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{name:<6}")),
+                    Span::styled(
+                        *description,
+                        HIDDEN_STYLE.remove_modifier(Modifier::CROSSED_OUT),
+                    ),
+                ]))
+            })
+            .collect();
+        let target = if self.signal_group {
+            "process group"
+        } else {
+            "process only"
+        };
+        let list = List::new(items)
+            .highlight_style(SELECTED_STYLE)
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always)
+            .block(Block::new().borders(Borders::TOP).title(format!(
+                " signal {}: {target}  {} toggle  Enter send ",
+                info.name,
+                self.key_hint(Action::SignalGroup)
+            )));
+
+        if let Some(state) = &mut self.signal_picker {
+            StatefulWidget::render(list, area, buf, state);
+        }
+    }
+
+    /// Resource usage of the selected service as CPU and memory sparklines,
+    /// or the latest figures of every service in the merged view.
+    fn render_resources(&self, area: Rect, buf: &mut Buffer) {
+        let block = Block::new().borders(Borders::TOP);
+
+        let Some(info) = self.selected_process() else {
+            let rows: Vec<Line> = self
+                .processes
+                .iter()
+                .map(|info| {
+                    let figures = match self.store.resources(info.id).last() {
+                        Some(sample) if info.status == ProcessStatus::Running => {
+                            resources::summary(sample)
+                        }
+                        _ => "not running".to_string(),
+                    };
+                    Line::from(vec![
+                        Span::styled(
+                            format!("{} ", info.name),
+                            Style::new().fg(self.service_color(info.id)),
+                        ),
+                        Span::raw(figures),
+                    ])
+                })
+                .collect();
+
+            let inner = block.inner(area);
+            block.title(" resources ").render(area, buf);
+            for (row, line) in rows.iter().take(inner.height as usize).enumerate() {
+                buf.set_line(inner.x, inner.y + row as u16, line, inner.width);
+            }
+            return;
+        };
+
+        let samples = self.store.resources(info.id);
+        let title = match samples.last() {
+            Some(sample) => format!(" resources: {} ", resources::summary(sample)),
+            None => " resources: no samples yet ".to_string(),
+        };
+        let inner = block.inner(area);
+        block.title(title).render(area, buf);
+
+        let [cpu_area, memory_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .spacing(2)
+                .areas(inner);
+        let [cpu_label, cpu_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(cpu_area);
+        let [memory_label, memory_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(memory_area);
+
+        // Newest samples on the right.
+        let width = cpu_area.width as usize;
+        let recent = &samples[samples.len().saturating_sub(width)..];
+        // CPU in tenths of a percent, scaled to at least one full core.
+        let cpu: Vec<u64> = recent.iter().map(|s| (s.cpu * 10.0) as u64).collect();
+        let memory: Vec<u64> = recent.iter().map(|s| s.memory).collect();
+        let peak = memory.iter().copied().max().unwrap_or(0);
+
+        buf.set_line(cpu_label.x, cpu_label.y, &Line::raw("cpu"), cpu_label.width);
+        Sparkline::default()
+            .data(&cpu)
+            .max(cpu.iter().copied().max().unwrap_or(0).max(1000))
+            .style(SPARKLINE_STYLE)
+            .render(cpu_area, buf);
+
+        buf.set_line(
+            memory_label.x,
+            memory_label.y,
+            &Line::raw(format!("memory (peak {})", resources::format_bytes(peak))),
+            memory_label.width,
+        );
+        Sparkline::default()
+            .data(&memory)
+            .style(SPARKLINE_STYLE)
+            .render(memory_area, buf);
+    }
+
+    /// Rows of `pane`, each with what it shows: the last `height` are on
+    /// screen, up to `history` entries before them are not.
+    fn output_rows(
+        &self,
+        pane: &Pane,
+        height: usize,
+        history: usize,
+    ) -> Vec<(RowSource, Line<'static>)> {
+        let Some(info) = self.pane_process(pane) else {
+            return self.merged_lines(pane, height + history);
+        };
+        let Some(snapshot) = self.store.snapshot(info.id) else {
+            return Vec::new();
+        };
+
+        match pane.top {
+            None => {
+                let entries = snapshot
+                    .query()
+                    .filter(pane.log_query())
+                    .tail(height + history);
+                let mut rows = self.entry_rows(info.id, entries);
+                rows.extend(self.partial_rows(info.id));
+                rows
+            }
+            // Scrolled views show the rows from `top` on.
+            Some(top) => {
+                let before = snapshot
+                    .query()
+                    .filter(pane.log_query().before(top))
+                    .tail(history);
+                let mut rows = self.entry_rows(info.id, before);
+                let view = snapshot
+                    .query()
+                    .filter(pane.log_query_from(top).limit(height));
+                let mut screen = self.entry_rows(info.id, view.iter());
+                screen.truncate(height);
+                rows.extend(screen);
+                rows
             }
         }
     }
+
+    /// Display rows of the entries of one process.
+    fn entry_rows<'a>(
+        &self,
+        process_id: u64,
+        entries: impl IntoIterator<Item = (&'a LogLineMeta, &'a str)>,
+    ) -> Vec<(RowSource, Line<'static>)> {
+        let jumped = self
+            .jumped
+            .as_ref()
+            .filter(|(jumped, _)| *jumped == process_id)
+            .map(|(_, lines)| lines);
+
+        entries
+            .into_iter()
+            .flat_map(|(meta, content)| {
+                let mut lines = content_lines(meta, content, self.pretty);
+                // The entry jumped to stays unfolded.
+                if jumped.is_some_and(|range| range.contains(&meta.id)) {
+                    for line in &mut lines {
+                        line.style = line.style.patch(JUMP_STYLE);
+                    }
+                } else if self.folded {
+                    fold(&mut lines);
+                }
+                add_timestamp(&mut lines, meta, self.timestamps);
+                entry_sources(process_id, meta.id, lines)
+            })
+            .collect()
+    }
+
+    /// Unterminated lines the process is still writing, shown below its
+    /// output while following.
+    fn partial_rows(&self, process_id: u64) -> Vec<(RowSource, Line<'static>)> {
+        self.store
+            .partials(process_id)
+            .into_iter()
+            .filter(|(_, content)| !content.is_empty())
+            .flat_map(|(_, content)| {
+                let lines = content.into_text().unwrap_or_default().lines;
+                lines.into_iter().map(move |line| {
+                    let source = RowSource::Partial {
+                        process_id,
+                        content: content.clone(),
+                    };
+                    (source, line)
+                })
+            })
+            .collect()
+    }
+
+    /// Lines of every visible process interleaved by timestamp, each
+    /// prefixed with the colored service name.
+    fn merged_lines(&self, pane: &Pane, height: usize) -> Vec<(RowSource, Line<'static>)> {
+        let visible: Vec<&ProcessInfo> = self
+            .processes
+            .iter()
+            .filter(|info| !self.hidden.contains(&info.id))
+            .collect();
+        let snapshots: Vec<LogSnapshot> = visible
+            .iter()
+            .filter_map(|info| self.store.snapshot(info.id))
+            .collect();
+        let width = visible
+            .iter()
+            .map(|info| info.name.len())
+            .max()
+            .unwrap_or(0);
+
+        let partials = visible
+            .iter()
+            .flat_map(|info| self.partial_rows(info.id))
+            .collect::<Vec<_>>();
+        let entries = MergedView::new(&snapshots)
+            .filter(pane.log_query())
+            .tail(height);
+
+        entries
+            .into_iter()
+            .map(|(process_id, meta, content)| {
+                let mut lines = content_lines(meta, content, self.pretty);
+                if self.folded {
+                    fold(&mut lines);
+                }
+                add_timestamp(&mut lines, meta, self.timestamps);
+
+                (process_id, entry_sources(process_id, meta.id, lines))
+            })
+            .chain(
+                partials
+                    .into_iter()
+                    .map(|row| (row.0.process_id(), vec![row])),
+            )
+            .flat_map(|(process_id, rows)| {
+                let name = visible
+                    .iter()
+                    .find(|info| info.id == process_id)
+                    .map(|info| info.name.as_str())
+                    .unwrap_or_default();
+                let color = self.service_color(process_id);
+
+                rows.into_iter()
+                    .enumerate()
+                    .map(move |(idx, (source, mut line))| {
+                        // Only the first row of a wrapped entry carries the name.
+                        let label = if idx == 0 { name } else { "" };
+                        line.spans.insert(
+                            0,
+                            Span::styled(format!("{label:<width$} │ "), Style::new().fg(color)),
+                        );
+                        (source, line)
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Pairs the rows of entry `line_id` with where they come from.
+fn entry_sources(
+    process_id: u64,
+    line_id: u64,
+    lines: Vec<Line<'static>>,
+) -> Vec<(RowSource, Line<'static>)> {
+    let count = lines.len();
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let source = RowSource::Entry {
+                process_id,
+                line_id,
+                row,
+                last: row + 1 == count,
+            };
+            (source, line)
+        })
+        .collect()
 }
 
 impl Widget for &mut App {
     fn render(self, area: Rect, buf: &mut Buffer) {
         use Constraint::{Length, Min};
         let vertical = Layout::vertical([Min(0), Length(1)]);
-        let [inner_area, _footer_area] = vertical.areas(area);
+        let [inner_area, footer_area] = vertical.areas(area);
 
-        let horizontal = Layout::horizontal([Length(20), Min(0)]);
+        let horizontal = Layout::horizontal([Length(self.sidebar_width), Min(0)]);
         let [tabs_area, output_area] = horizontal.areas(inner_area);
 
         self.render_tabs(tabs_area, buf);
-        self.render_selected_process_tab(output_area, buf);
+
+        let panel_height = if self.errors.is_some() {
+            ERRORS_HEIGHT
+        } else if self.tree.is_some() {
+            TREE_HEIGHT
+        } else if self.signal_picker.is_some() {
+            SIGNALS_HEIGHT
+        } else {
+            0
+        };
+        let resources_height = if self.resources { RESOURCES_HEIGHT } else { 0 };
+        let [logs_area, panel_area, resources_area] =
+            Layout::vertical([Min(0), Length(panel_height), Length(resources_height)])
+                .areas(output_area);
+
+        self.logs = logs_area;
+        for (idx, area) in self.panes.areas(logs_area) {
+            self.render_pane(idx, area, buf);
+        }
+        if self.errors.is_some() {
+            self.render_errors(panel_area, buf);
+        } else if self.tree.is_some() {
+            self.render_tree(panel_area, buf);
+        } else if self.signal_picker.is_some() {
+            self.render_signal_picker(panel_area, buf);
+        }
+        if self.resources {
+            self.render_resources(resources_area, buf);
+        }
+        self.render_footer(footer_area, buf);
+        if self.help {
+            self.render_help(area, buf);
+        }
+    }
+}
+
+/// `key` as written in the keybindings, `None` for keys that cannot be
+/// bound.
+fn chord(key: &KeyEvent) -> Option<KeyChord> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let code = match key.code {
+        // Shifted letters come upper case already.
+        CTKeyCode::Char(c) if ctrl => Key::Char(c.to_ascii_lowercase()),
+        CTKeyCode::Char(c) => Key::Char(c),
+        CTKeyCode::Enter => Key::Enter,
+        CTKeyCode::Esc => Key::Esc,
+        CTKeyCode::Tab => Key::Tab,
+        CTKeyCode::BackTab => Key::BackTab,
+        CTKeyCode::Backspace => Key::Backspace,
+        CTKeyCode::Delete => Key::Delete,
+        CTKeyCode::Up => Key::Up,
+        CTKeyCode::Down => Key::Down,
+        CTKeyCode::Left => Key::Left,
+        CTKeyCode::Right => Key::Right,
+        CTKeyCode::PageUp => Key::PageUp,
+        CTKeyCode::PageDown => Key::PageDown,
+        CTKeyCode::Home => Key::Home,
+        CTKeyCode::End => Key::End,
+        CTKeyCode::F(n) => Key::F(n),
+        _ => return None,
+    };
+
+    Some(KeyChord {
+        key: code,
+        ctrl,
+        alt: key.modifiers.contains(KeyModifiers::ALT),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cdi_server::store::StoreManager;
+
+    use super::*;
+
+    fn new_app(config: &str) -> App {
+        let processes = vec![
+            ProcessInfo::new("api".into(), "cargo run".into(), None),
+            ProcessInfo::new("web".into(), "pnpm dev".into(), None),
+        ];
+        let (_, store) = StoreManager::new(processes, 0, 512, None);
+        let config = Config::parse("test.kdl", config).unwrap();
+
+        App::new(
+            None,
+            store,
+            config,
+            PathBuf::from("/nonexistent/cdi.sock"),
+            Path::new("/nonexistent/layout.json"),
+        )
+        .unwrap()
+    }
+
+    fn press(app: &mut App, c: char) {
+        app.dispatch_key(KeyEvent::new(CTKeyCode::Char(c), KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn toggles_mouse() {
+        let mut app = new_app("");
+        assert!(!app.mouse);
+
+        press(&mut app, 'm');
+        assert!(app.mouse);
+        assert_eq!(app.message.as_deref(), Some("mouse on"));
+        press(&mut app, 'm');
+        assert!(!app.mouse);
+        assert_eq!(app.message.as_deref(), Some("mouse off"));
+
+        let mut app = new_app(r#"mouse true; keybindings { toggle-mouse "M"; }"#);
+        assert!(app.mouse);
+        press(&mut app, 'm');
+        assert!(app.mouse);
+        press(&mut app, 'M');
+        assert!(!app.mouse);
+    }
+
+    #[test]
+    fn tree_lets_normal_keys_through() {
+        let mut app = new_app("");
+        press(&mut app, 'T');
+        assert!(app.tree.is_some());
+
+        // Not a key of the tree, so it works as in normal mode.
+        press(&mut app, 'f');
+        assert!(app.tree.is_some());
+        assert_eq!(app.panes.focused().min_level, Some(Level::Debug));
+
+        // Tree keys do nothing once it is closed.
+        press(&mut app, 'q');
+        assert!(app.tree.is_none());
+        assert_eq!(app.state, AppState::Running);
+        press(&mut app, 'x');
+        assert_eq!(app.message, None);
     }
 }
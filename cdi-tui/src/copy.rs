@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, Write},
+    ops::RangeInclusive,
+    process::{Command, Stdio},
+};
+
+use cdi_shared::{ansi::strip_ansi, event::ui::TuiEvent};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style, palette::tailwind::SLATE},
+    text::Line,
+};
+
+use crate::lines::line_text;
+
+const SELECTION_STYLE: Style = Style::new().bg(SLATE.c700);
+const CURSOR_STYLE: Style = Style::new().bg(SLATE.c500);
+
+/// What a row of the output pane shows, so copying takes the output and
+/// leaves out service labels, timestamps and fold markers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum RowSource {
+    /// Line `row` of the process's entry `line_id`. `last` rows stand for
+    /// the rest of the entry too, which is hidden when it is folded.
+    Entry {
+        process_id: u64,
+        line_id: u64,
+        row: usize,
+        last: bool,
+    },
+    /// A line the process is still writing.
+    Partial { process_id: u64, content: String },
+}
+
+impl RowSource {
+    pub(crate) fn process_id(&self) -> u64 {
+        match self {
+            Self::Entry { process_id, .. } | Self::Partial { process_id, .. } => *process_id,
+        }
+    }
+}
+
+/// Copy mode: the output pane frozen when it was entered, with a line
+/// cursor and a visual selection to yank.
+pub(crate) struct CopyMode {
+    rows: Vec<(RowSource, Line<'static>)>,
+    cursor: usize,
+    /// Row the selection started at, `None` unless selecting.
+    anchor: Option<usize>,
+    /// First row on screen.
+    top: usize,
+    /// Rows on screen at the last render.
+    height: usize,
+}
+
+impl CopyMode {
+    /// Starts on the last row, with the last `height` rows on screen.
+    pub(crate) fn new(rows: Vec<(RowSource, Line<'static>)>, height: usize) -> Self {
+        Self {
+            top: rows.len().saturating_sub(height),
+            cursor: rows.len().saturating_sub(1),
+            anchor: None,
+            height,
+            rows,
+        }
+    }
+
+    pub(crate) fn move_by(&mut self, rows: isize) {
+        self.move_to(self.cursor.saturating_add_signed(rows));
+    }
+
+    /// Moves the cursor to `row`, scrolling it into view.
+    pub(crate) fn move_to(&mut self, row: usize) {
+        self.cursor = row.min(self.rows.len().saturating_sub(1));
+        self.scroll_to_cursor();
+    }
+
+    pub(crate) fn last_row(&self) -> usize {
+        self.rows.len().saturating_sub(1)
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Starts selecting at the cursor, or stops.
+    pub(crate) fn toggle_selection(&mut self) {
+        self.anchor = match self.anchor {
+            Some(_) => None,
+            None => Some(self.cursor),
+        };
+    }
+
+    pub(crate) fn selecting(&self) -> bool {
+        self.anchor.is_some()
+    }
+
+    /// The selected rows, or the cursor's row when not selecting.
+    pub(crate) fn selection(&self) -> RangeInclusive<usize> {
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        anchor.min(self.cursor)..=anchor.max(self.cursor)
+    }
+
+    /// Output of the selected rows without escape sequences, one line per
+    /// row and all of a folded entry. `entry` looks up the content of a
+    /// process's entry; rows of entries gone from the store are copied as
+    /// drawn.
+    pub(crate) fn text(&self, mut entry: impl FnMut(u64, u64) -> Option<String>) -> String {
+        let mut entries = HashMap::new();
+        let mut lines = Vec::new();
+
+        for (source, line) in self.rows.get(self.selection()).unwrap_or_default() {
+            let (process_id, line_id, row, last) = match source {
+                RowSource::Entry {
+                    process_id,
+                    line_id,
+                    row,
+                    last,
+                } => (*process_id, *line_id, *row, *last),
+                RowSource::Partial { content, .. } => {
+                    lines.push(strip_ansi(content).into_owned());
+                    continue;
+                }
+            };
+
+            let content = entries
+                .entry((process_id, line_id))
+                .or_insert_with(|| entry(process_id, line_id));
+            match content {
+                Some(content) => {
+                    let content = strip_ansi(content);
+                    let rows = content.split('\n').skip(row);
+                    lines.extend(
+                        rows.take(if last { usize::MAX } else { 1 })
+                            .map(str::to_string),
+                    );
+                }
+                None => lines.push(line_text(line).trim_end().to_string()),
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    pub(crate) fn render(&mut self, area: Rect, buf: &mut Buffer) {
+        self.height = area.height as usize;
+        self.scroll_to_cursor();
+
+        let selection = self.selection();
+        let rows = self.rows.iter().enumerate().skip(self.top);
+        for ((idx, (_, line)), y) in rows.zip(area.top()..area.bottom()) {
+            buf.set_line(area.x, y, line, area.width);
+
+            let style = if idx == self.cursor {
+                CURSOR_STYLE
+            } else if self.selecting() && selection.contains(&idx) {
+                SELECTION_STYLE
+            } else {
+                continue;
+            };
+            buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+        }
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if self.cursor >= self.top + self.height.max(1) {
+            self.top = self.cursor + 1 - self.height.max(1);
+        }
+    }
+}
+
+/// Puts `text` on the clipboard: with OSC 52 written to the terminal,
+/// which reaches the local clipboard over SSH too, and with `wl-copy` or
+/// `xclip` under a local display, for terminals that ignore OSC 52. The
+/// tools are fed off the UI loop; failing to, is reported as a message.
+pub(crate) fn copy_to_clipboard(terminal: &mut impl Write, text: String) -> io::Result<()> {
+    terminal.write_all(osc52(&text).as_bytes())?;
+    terminal.flush()?;
+
+    let command: &'static [&'static str] = if env::var_os("WAYLAND_DISPLAY").is_some() {
+        &["wl-copy"]
+    } else if env::var_os("DISPLAY").is_some() {
+        &["xclip", "-selection", "clipboard"]
+    } else {
+        return Ok(());
+    };
+
+    tokio::task::spawn_blocking(move || {
+        // Not installed is fine, OSC 52 may have done it.
+        let Ok(mut child) = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return;
+        };
+        if let Some(mut stdin) = child.stdin.take()
+            && let Err(e) = stdin.write_all(text.as_bytes())
+        {
+            TuiEvent::Message(format!("copy failed: {e}")).emit();
+        }
+        // Both fork to serve the selection, which this waits for.
+        let _ = child.wait();
+    });
+
+    Ok(())
+}
+
+/// The escape sequence setting the clipboard to `text`.
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (idx, byte)| n | (*byte as u32) << (16 - 8 * idx));
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * idx) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yanks_selection() {
+        let entry = |process_id, line_id, row, last| RowSource::Entry {
+            process_id,
+            line_id,
+            row,
+            last,
+        };
+        let rows = vec![
+            (entry(1, 10, 0, false), "api │ 10:00:00 panicked"),
+            (entry(1, 10, 1, true), "api │            at main.rs"),
+            (entry(2, 5, 0, true), "web │ a … +2 lines"),
+            (entry(1, 12, 0, true), "api │ evicted  "),
+            (
+                RowSource::Partial {
+                    process_id: 1,
+                    content: "\x1b[1mloading".into(),
+                },
+                "api │ loading",
+            ),
+        ]
+        .into_iter()
+        .map(|(source, line)| (source, Line::raw(line)))
+        .collect();
+        let entries = HashMap::from([
+            ((1, 10), "\x1b[31mpanicked\x1b[0m\n  at main.rs".to_string()),
+            ((2, 5), "a\nb\nc".to_string()),
+        ]);
+        let content = |process_id, line_id| entries.get(&(process_id, line_id)).cloned();
+
+        let mut copy = CopyMode::new(rows, 2);
+        assert_eq!(copy.text(content), "loading");
+
+        copy.toggle_selection();
+        copy.move_by(-3);
+        assert_eq!(
+            copy.text(content),
+            "  at main.rs\na\nb\nc\napi │ evicted\nloading"
+        );
+        assert_eq!(copy.top, 1);
+
+        copy.move_to(0);
+        copy.toggle_selection();
+        assert_eq!(copy.text(content), "panicked");
+
+        assert_eq!(
+            osc52("second\nthird\nfourth"),
+            "\x1b]52;c;c2Vjb25kCnRoaXJkCmZvdXJ0aA==\x07"
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+    }
+}
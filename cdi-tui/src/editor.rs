@@ -0,0 +1,116 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use cdi_shared::detect::Location;
+
+/// Absolute path of a reference printed by a service: relative paths are
+/// taken from the service's `cwd`, itself relative to where cdi was started.
+pub(crate) fn resolve(file: &str, cwd: Option<&str>) -> PathBuf {
+    let file = Path::new(file);
+    if file.is_absolute() {
+        return file.to_path_buf();
+    }
+
+    let mut base = env::current_dir().unwrap_or_default();
+    if let Some(cwd) = cwd {
+        base.push(cwd);
+    }
+
+    base.join(file.strip_prefix("./").unwrap_or(file))
+}
+
+/// Shell command opening `path` at `location`. `template` is the configured
+/// `editor` with `{file}`, `{line}` and `{column}` placeholders; without one
+/// the command is derived from `$VISUAL` or `$EDITOR`.
+pub(crate) fn command(template: Option<&str>, path: &Path, location: &Location) -> String {
+    let template = match template {
+        Some(template) => template.to_string(),
+        None => default_template(),
+    };
+
+    template
+        .replace("{file}", &shell_quote(&path.to_string_lossy()))
+        .replace("{line}", &location.line.to_string())
+        .replace("{column}", &location.column.unwrap_or(1).to_string())
+}
+
+fn default_template() -> String {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let program = editor
+        .split_whitespace()
+        .next()
+        .and_then(|program| program.rsplit('/').next())
+        .unwrap_or_default();
+
+    let args = match program {
+        "code" | "code-insiders" | "codium" | "cursor" => "-g {file}:{line}:{column}",
+        "hx" | "helix" | "zed" | "subl" => "{file}:{line}:{column}",
+        "kak" => "+{line}:{column} {file}",
+        "idea" | "goland" | "webstorm" | "rustrover" | "pycharm" => "--line {line} {file}",
+        // vi, vim, nvim, nano, emacs, micro and most others.
+        _ => "+{line} {file}",
+    };
+
+    format!("{editor} {args}")
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_against_service_cwd() {
+        assert_eq!(
+            resolve("/src/main.rs", Some("api")),
+            Path::new("/src/main.rs")
+        );
+
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(
+            resolve("./src/main.rs", Some("api")),
+            cwd.join("api/src/main.rs")
+        );
+        assert_eq!(resolve("src/main.rs", None), cwd.join("src/main.rs"));
+    }
+
+    #[test]
+    fn fills_in_template() {
+        let location = Location {
+            file: "src/it's.rs".into(),
+            line: 12,
+            column: None,
+        };
+        let path = Path::new("/app/src/it's.rs");
+
+        assert_eq!(
+            command(Some("code -g {file}:{line}:{column}"), path, &location),
+            r"code -g '/app/src/it'\''s.rs':12:1"
+        );
+        assert_eq!(
+            command(
+                Some("vim +{line} {file}"),
+                path,
+                &Location {
+                    column: Some(3),
+                    ..location
+                }
+            ),
+            r"vim +12 '/app/src/it'\''s.rs'"
+        );
+    }
+
+    #[test]
+    fn quotes_for_the_shell() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
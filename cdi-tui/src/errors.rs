@@ -0,0 +1,46 @@
+use ratatui::{
+    style::{Color, Modifier, Style, palette::tailwind::SLATE},
+    text::{Line, Span},
+};
+
+use cdi_shared::{
+    detect::{DetectedError, ErrorSeverity},
+    log::Level,
+};
+
+use crate::lines::level_style;
+
+const LOCATION_STYLE: Style = Style::new()
+    .fg(SLATE.c400)
+    .add_modifier(Modifier::UNDERLINED);
+
+/// Background of the lines a jump from the errors panel landed on.
+pub(crate) const JUMP_STYLE: Style = Style::new().bg(SLATE.c800);
+
+/// One row of the errors panel: `[service] error src/main.rs:4:18 message`.
+/// The service is only shown in the merged view.
+pub(crate) fn error_line(error: &DetectedError, service: Option<(&str, Color)>) -> Line<'static> {
+    let mut spans = Vec::with_capacity(5);
+
+    if let Some((name, color)) = service {
+        spans.push(Span::styled(format!("{name} "), Style::new().fg(color)));
+    }
+
+    let level = match error.severity {
+        ErrorSeverity::Error => Level::Error,
+        ErrorSeverity::Warning => Level::Warn,
+    };
+    spans.push(Span::styled(
+        format!("{:<7} ", error.severity.as_str()),
+        level_style(Some(level)),
+    ));
+
+    if let Some(location) = &error.location {
+        spans.push(Span::styled(location.to_string(), LOCATION_STYLE));
+        spans.push(Span::raw(" "));
+    }
+
+    spans.push(Span::raw(error.message.clone()));
+
+    Line::from(spans)
+}
@@ -1,11 +1,26 @@
-use anyhow;
-use cdi_server::Connection;
-use cdi_shared::log::ProcessInfo;
+use std::path::Path;
+
+use cdi_config::Config;
+use cdi_server::{Connection, StoreHandle};
 
 pub mod app;
+mod copy;
+mod editor;
+mod errors;
+mod lines;
+mod panes;
+mod resources;
 mod signals;
 
+/// Runs the TUI until it is quit. Without a `conn` it is attached to a
+/// server in another process, and quitting leaves the services running.
+/// The pane layout is saved for `project_dir`.
 #[doc(hidden)]
-pub async fn run(conn: Connection, services: Vec<ProcessInfo>) -> anyhow::Result<()> {
-    app::run(conn, services).await
+pub async fn run(
+    conn: Option<Connection>,
+    store: StoreHandle,
+    config: Config,
+    project_dir: &Path,
+) -> anyhow::Result<()> {
+    app::run(conn, store, config, project_dir).await
 }
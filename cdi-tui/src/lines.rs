@@ -0,0 +1,249 @@
+use std::ops::Range;
+
+use ansi_to_tui::IntoText;
+use ratatui::{
+    style::{Color, Style, palette::tailwind::SLATE},
+    text::{Line, Span},
+};
+
+use cdi_server::store::{LogLineMeta, StructuredLine};
+use cdi_shared::log::Level;
+
+const DIM_STYLE: Style = Style::new().fg(SLATE.c500);
+const KEY_STYLE: Style = Style::new().fg(Color::Cyan);
+
+/// Turns a stored line into display rows. Structured (JSON) lines are
+/// rendered as `timestamp level message key=value...` when `pretty` is set,
+/// everything else goes through the ANSI parser.
+pub(crate) fn content_lines(meta: &LogLineMeta, content: &str, pretty: bool) -> Vec<Line<'static>> {
+    let style = level_style(meta.level);
+
+    if pretty && let Some(fields) = &meta.fields {
+        return vec![structured_line(StructuredLine::new(fields), meta.level).patch_style(style)];
+    }
+
+    content
+        .into_text()
+        .unwrap_or_default()
+        .lines
+        .into_iter()
+        .map(|line| line.patch_style(style))
+        .collect()
+}
+
+fn structured_line(line: StructuredLine, fallback: Option<Level>) -> Line<'static> {
+    let mut spans = Vec::with_capacity(4 + line.fields.len() * 3);
+
+    if let Some(timestamp) = line.timestamp {
+        spans.push(Span::styled(format!("{timestamp} "), DIM_STYLE));
+    }
+
+    if let Some(level) = line.level.or(fallback) {
+        spans.push(Span::styled(
+            format!("{:<5} ", level.as_str().to_ascii_uppercase()),
+            level_style(Some(level)),
+        ));
+    }
+
+    if let Some(message) = line.message {
+        spans.push(Span::raw(message));
+    }
+
+    for (key, value) in line.fields {
+        spans.push(Span::styled(format!(" {key}"), KEY_STYLE));
+        spans.push(Span::styled("=", DIM_STYLE));
+        spans.push(Span::raw(value));
+    }
+
+    Line::from(spans)
+}
+
+pub(crate) fn level_style(level: Option<Level>) -> Style {
+    match level {
+        Some(Level::Error) => Style::new().fg(Color::Red),
+        Some(Level::Warn) => Style::new().fg(Color::Yellow),
+        Some(Level::Debug | Level::Trace) => DIM_STYLE,
+        Some(Level::Info) | None => Style::new(),
+    }
+}
+
+/// Plain text of a rendered row, which references are looked up in.
+pub(crate) fn line_text(line: &Line) -> String {
+    line.spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect()
+}
+
+/// Patches `style` onto the bytes `range` of the row's text, splitting the
+/// spans it starts or ends in.
+pub(crate) fn patch_range(line: &mut Line<'static>, range: Range<usize>, style: Style) {
+    let mut spans = Vec::with_capacity(line.spans.len() + 2);
+    let mut offset = 0;
+
+    for span in line.spans.drain(..) {
+        let (start, end) = (offset, offset + span.content.len());
+        offset = end;
+
+        if end <= range.start || start >= range.end {
+            spans.push(span);
+            continue;
+        }
+
+        let content = span.content.as_ref();
+        let from = range.start.saturating_sub(start);
+        let to = range.end.min(end) - start;
+        for (text, patched) in [
+            (&content[..from], false),
+            (&content[from..to], true),
+            (&content[to..], false),
+        ] {
+            if !text.is_empty() {
+                let text_style = if patched {
+                    span.style.patch(style)
+                } else {
+                    span.style
+                };
+                spans.push(Span::styled(text.to_string(), text_style));
+            }
+        }
+    }
+
+    line.spans = spans;
+}
+
+/// Collapses a multi-line entry to its first row plus a line count.
+pub(crate) fn fold(lines: &mut Vec<Line<'static>>) {
+    if lines.len() > 1 {
+        let hidden = lines.len() - 1;
+        lines.truncate(1);
+        lines[0]
+            .spans
+            .push(Span::styled(format!(" … +{hidden} lines"), DIM_STYLE));
+    }
+}
+
+/// How entry times are shown in front of the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Timestamps {
+    #[default]
+    Hidden,
+    /// Local wall-clock time.
+    Absolute,
+    /// Time since the session started, from the monotonic clock.
+    Relative,
+}
+
+impl Timestamps {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            Self::Hidden => Self::Absolute,
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Hidden,
+        }
+    }
+}
+
+/// Prefixes the first row of an entry with its time, and pads the rest to
+/// keep them aligned.
+pub(crate) fn add_timestamp(lines: &mut [Line<'static>], meta: &LogLineMeta, mode: Timestamps) {
+    let time = match mode {
+        Timestamps::Hidden => return,
+        Timestamps::Absolute => local_time(meta.timestamp),
+        Timestamps::Relative => relative_time(meta.elapsed),
+    };
+
+    let padding = " ".repeat(time.chars().count() + 1);
+    for (idx, line) in lines.iter_mut().enumerate() {
+        let prefix = if idx == 0 {
+            format!("{time} ")
+        } else {
+            padding.clone()
+        };
+        line.spans.insert(0, Span::styled(prefix, DIM_STYLE));
+    }
+}
+
+/// `HH:MM:SS.mmm` in the local timezone.
+fn local_time(timestamp: u128) -> String {
+    let secs = (timestamp / 1000) as libc::time_t;
+    let millis = timestamp % 1000;
+
+    let Some(tm) = local_tm(secs) else {
+        return format!("{secs}.{millis:03}");
+    };
+
+    format!(
+        "{:02}:{:02}:{:02}.{millis:03}",
+        tm.tm_hour, tm.tm_min, tm.tm_sec
+    )
+}
+
+/// `YYYYMMDD-HHMMSS` in the local timezone, for file names.
+pub(crate) fn file_time(timestamp: u128) -> String {
+    let secs = (timestamp / 1000) as libc::time_t;
+
+    let Some(tm) = local_tm(secs) else {
+        return secs.to_string();
+    };
+
+    format!(
+        "{}{:02}{:02}-{:02}{:02}{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/// Broken-down local time of `secs`, `None` if it is out of range. std
+/// doesn't know the local timezone, so this goes through libc.
+fn local_tm(secs: libc::time_t) -> Option<libc::tm> {
+    // SAFETY: `tm` is plain old data (integers and, on some platforms, a
+    // `tm_zone` pointer that `localtime_r` sets), so all-zero is a valid
+    // value.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // SAFETY: both pointers come from live references. `localtime_r`
+    // writes only to `tm`, unlike `localtime`, so concurrent calls from
+    // other threads don't race on a shared buffer.
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return None;
+    }
+
+    Some(tm)
+}
+
+/// `+MM:SS.mmm`, with hours once the session is that old.
+fn relative_time(elapsed: u64) -> String {
+    let millis = elapsed / 1000;
+    let (hours, minutes, seconds) = (millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60);
+
+    if hours > 0 {
+        format!("+{hours}:{minutes:02}:{seconds:02}.{:03}", millis % 1000)
+    } else {
+        format!("+{minutes:02}:{seconds:02}.{:03}", millis % 1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_relative_time() {
+        assert_eq!(relative_time(0), "+00:00.000");
+        assert_eq!(relative_time(61_234_567), "+01:01.234");
+        assert_eq!(relative_time(3_599_999_000), "+59:59.999");
+        assert_eq!(relative_time(3_600_000_000), "+1:00:00.000");
+        assert_eq!(relative_time(37_230_005_000), "+10:20:30.005");
+    }
+
+    #[test]
+    fn formats_local_time() {
+        let time = local_time(1_700_000_000_123);
+        assert_eq!(time.len(), "HH:MM:SS.mmm".len());
+        assert!(time.ends_with(":20.123"), "{time}");
+    }
+}
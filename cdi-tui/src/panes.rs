@@ -0,0 +1,427 @@
+use std::{
+    fs::{self, DirBuilder},
+    io,
+    os::unix::fs::DirBuilderExt,
+    path::Path,
+};
+
+use ratatui::layout::{Constraint, Direction, Layout, Position, Rect};
+use serde::{Deserialize, Serialize};
+
+use cdi_server::store::LogQuery;
+use cdi_shared::log::{Level, ProcessInfo};
+
+/// Most panes the output area is split into.
+pub(crate) const MAX_PANES: usize = 6;
+
+/// A log pane: what it shows, and its own scroll and filters.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Pane {
+    /// Sidebar entry shown: 0 is the merged view, `i + 1` is `processes[i]`.
+    pub(crate) entry: usize,
+    /// First visible line of a single-process view; `None` follows the tail.
+    pub(crate) top: Option<u64>,
+    /// Minimum severity shown.
+    pub(crate) min_level: Option<Level>,
+    /// Text shown entries must contain, see `LogQuery::search`.
+    pub(crate) search: Option<String>,
+    /// Rows the output was drawn in at the last render, title excluded.
+    pub(crate) area: Rect,
+}
+
+impl Pane {
+    /// Rows of output at the last render.
+    pub(crate) fn viewport(&self) -> usize {
+        self.area.height as usize
+    }
+
+    pub(crate) fn log_query(&self) -> LogQuery {
+        let mut query = LogQuery::new();
+        if let Some(level) = self.min_level {
+            query = query.min_level(level);
+        }
+        if let Some(search) = &self.search {
+            query = query.search(search);
+        }
+
+        query
+    }
+
+    /// `log_query` starting at line `top`, inclusive.
+    pub(crate) fn log_query_from(&self, top: u64) -> LogQuery {
+        match top.checked_sub(1) {
+            Some(after) => self.log_query().after(after),
+            None => self.log_query(),
+        }
+    }
+
+    /// Cycles the minimum level: all, debug, info, warn, error.
+    pub(crate) fn cycle_min_level(&mut self) {
+        self.min_level = match self.min_level {
+            None => Some(Level::Debug),
+            Some(Level::Error) => None,
+            Some(level) => Level::ALL.iter().copied().find(|l| *l > level),
+        };
+    }
+}
+
+/// How the output area is divided, with panes as indices into
+/// `Panes::panes`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Tiling {
+    Pane(usize),
+    /// Side by side, left to right.
+    Row(Vec<Tiling>),
+    /// Stacked, top to bottom.
+    Column(Vec<Tiling>),
+}
+
+impl Tiling {
+    /// Puts pane `new` next to pane `target`, joining the split `target` is
+    /// in when it runs the same way. Returns whether `target` was found.
+    fn split(&mut self, target: usize, new: usize, side_by_side: bool) -> bool {
+        let row = matches!(self, Tiling::Row(_));
+        match self {
+            Tiling::Pane(idx) if *idx == target => {
+                let pair = vec![Tiling::Pane(target), Tiling::Pane(new)];
+                *self = match side_by_side {
+                    true => Tiling::Row(pair),
+                    false => Tiling::Column(pair),
+                };
+                true
+            }
+            Tiling::Pane(_) => false,
+            Tiling::Row(children) | Tiling::Column(children) => {
+                if row == side_by_side
+                    && let Some(idx) = children
+                        .iter()
+                        .position(|child| *child == Tiling::Pane(target))
+                {
+                    children.insert(idx + 1, Tiling::Pane(new));
+                    return true;
+                }
+                children
+                    .iter_mut()
+                    .any(|child| child.split(target, new, side_by_side))
+            }
+        }
+    }
+
+    /// Takes pane `target` out, renumbering the panes after it and
+    /// collapsing splits left with a single pane.
+    fn remove(&mut self, target: usize) {
+        match self {
+            Tiling::Pane(idx) => {
+                if *idx > target {
+                    *idx -= 1;
+                }
+            }
+            Tiling::Row(children) | Tiling::Column(children) => {
+                children.retain(|child| *child != Tiling::Pane(target));
+                for child in children.iter_mut() {
+                    child.remove(target);
+                }
+                if children.len() == 1
+                    && let Some(only) = children.pop()
+                {
+                    *self = only;
+                }
+            }
+        }
+    }
+
+    /// Areas of the panes within `area`, in reading order.
+    fn areas(&self, area: Rect) -> Vec<(usize, Rect)> {
+        let (children, direction) = match self {
+            Tiling::Pane(idx) => return vec![(*idx, area)],
+            Tiling::Row(children) => (children, Direction::Horizontal),
+            Tiling::Column(children) => (children, Direction::Vertical),
+        };
+
+        let areas =
+            Layout::new(direction, children.iter().map(|_| Constraint::Fill(1))).split(area);
+        children
+            .iter()
+            .zip(areas.iter())
+            .flat_map(|(child, area)| child.areas(*area))
+            .collect()
+    }
+
+    /// Panes in reading order.
+    fn panes(&self) -> Vec<usize> {
+        self.areas(Rect::default())
+            .into_iter()
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
+
+/// The log panes of the output area, one of them focused.
+#[derive(Debug)]
+pub(crate) struct Panes {
+    tiling: Tiling,
+    panes: Vec<Pane>,
+    focus: usize,
+}
+
+impl Default for Panes {
+    /// A single pane on the merged view.
+    fn default() -> Self {
+        Self {
+            tiling: Tiling::Pane(0),
+            panes: vec![Pane::default()],
+            focus: 0,
+        }
+    }
+}
+
+impl Panes {
+    pub(crate) fn len(&self) -> usize {
+        self.panes.len()
+    }
+
+    pub(crate) fn focus(&self) -> usize {
+        self.focus
+    }
+
+    pub(crate) fn focused(&self) -> &Pane {
+        &self.panes[self.focus]
+    }
+
+    pub(crate) fn focused_mut(&mut self) -> &mut Pane {
+        &mut self.panes[self.focus]
+    }
+
+    pub(crate) fn get(&self, idx: usize) -> &Pane {
+        &self.panes[idx]
+    }
+
+    pub(crate) fn get_mut(&mut self, idx: usize) -> &mut Pane {
+        &mut self.panes[idx]
+    }
+
+    /// Splits the focused pane in two, focusing the new half, which starts
+    /// as a copy following the tail. Fails at `MAX_PANES`.
+    pub(crate) fn split(&mut self, side_by_side: bool) -> bool {
+        if self.panes.len() >= MAX_PANES {
+            return false;
+        }
+
+        let pane = Pane {
+            top: None,
+            ..self.focused().clone()
+        };
+        self.panes.push(pane);
+        let new = self.panes.len() - 1;
+        self.tiling.split(self.focus, new, side_by_side);
+        self.focus = new;
+
+        true
+    }
+
+    /// Closes the focused pane, focusing the one before it. Fails on the
+    /// last pane.
+    pub(crate) fn close(&mut self) -> bool {
+        if self.panes.len() == 1 {
+            return false;
+        }
+
+        let order = self.tiling.panes();
+        let position = order.iter().position(|idx| *idx == self.focus);
+        let next = match position {
+            Some(0) | None => order[1],
+            Some(position) => order[position - 1],
+        };
+
+        self.tiling.remove(self.focus);
+        self.panes.remove(self.focus);
+        self.focus = if next > self.focus { next - 1 } else { next };
+
+        true
+    }
+
+    /// Moves the focus to the next pane in reading order, or the previous.
+    pub(crate) fn cycle_focus(&mut self, forward: bool) {
+        let order = self.tiling.panes();
+        let Some(position) = order.iter().position(|idx| *idx == self.focus) else {
+            return;
+        };
+        let next = match forward {
+            true => (position + 1) % order.len(),
+            false => (position + order.len() - 1) % order.len(),
+        };
+        self.focus = order[next];
+    }
+
+    /// Focuses the pane drawn at `position` at the last render, returning
+    /// it. The title row counts as part of the pane.
+    pub(crate) fn focus_at(&mut self, position: Position) -> Option<usize> {
+        let idx = self.panes.iter().position(|pane| {
+            let mut area = pane.area;
+            area.y = area.y.saturating_sub(1);
+            area.height += 1;
+            area.contains(position)
+        })?;
+        self.focus = idx;
+
+        Some(idx)
+    }
+
+    /// Index under `position` at the last render.
+    pub(crate) fn at(&self, position: Position) -> Option<usize> {
+        self.panes
+            .iter()
+            .position(|pane| pane.area.contains(position))
+    }
+
+    /// Areas of the panes within `area`, in reading order.
+    pub(crate) fn areas(&self, area: Rect) -> Vec<(usize, Rect)> {
+        self.tiling.areas(area)
+    }
+
+    /// The layout saved at `path`, or a single pane when there is none or
+    /// it does not fit.
+    pub(crate) fn load(path: &Path, processes: &[ProcessInfo]) -> Self {
+        fs::read(path)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .and_then(|saved| Self::restore(saved, processes))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self, path: &Path, processes: &[ProcessInfo]) -> io::Result<()> {
+        // Private, as it may be the runtime dir fallback (see `state_file`).
+        if let Some(dir) = path.parent() {
+            DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        }
+        let json = serde_json::to_vec_pretty(&self.saved(processes))?;
+        fs::write(path, json)
+    }
+
+    fn saved(&self, processes: &[ProcessInfo]) -> SavedLayout {
+        let panes = self
+            .panes
+            .iter()
+            .map(|pane| SavedPane {
+                service: pane
+                    .entry
+                    .checked_sub(1)
+                    .and_then(|idx| processes.get(idx))
+                    .map(|info| info.name.clone()),
+                min_level: pane.min_level,
+                search: pane.search.clone(),
+            })
+            .collect();
+
+        SavedLayout {
+            tiling: self.tiling.clone(),
+            panes,
+            focus: self.focus,
+        }
+    }
+
+    /// Panes of `saved`, with services matched by name since process ids
+    /// differ between sessions. Services gone from the config show the
+    /// merged view.
+    fn restore(saved: SavedLayout, processes: &[ProcessInfo]) -> Option<Self> {
+        let mut order = saved.tiling.panes();
+        order.sort_unstable();
+        if !order.iter().copied().eq(0..saved.panes.len()) || saved.focus >= saved.panes.len() {
+            return None;
+        }
+
+        let panes = saved
+            .panes
+            .into_iter()
+            .map(|pane| Pane {
+                entry: pane
+                    .service
+                    .and_then(|name| processes.iter().position(|info| info.name == name))
+                    .map_or(0, |idx| idx + 1),
+                min_level: pane.min_level,
+                search: pane.search,
+                ..Pane::default()
+            })
+            .collect();
+
+        Some(Self {
+            tiling: saved.tiling,
+            panes,
+            focus: saved.focus,
+        })
+    }
+}
+
+/// A layout as saved for a project.
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedLayout {
+    tiling: Tiling,
+    panes: Vec<SavedPane>,
+    focus: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedPane {
+    /// `None` for the merged view.
+    service: Option<String>,
+    #[serde(default)]
+    min_level: Option<Level>,
+    #[serde(default)]
+    search: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_and_restores() {
+        let api = ProcessInfo::new("api".into(), "cargo run".into(), None);
+        let worker = ProcessInfo::new("worker".into(), "cargo run".into(), None);
+        let processes = vec![api, worker];
+
+        let mut panes = Panes::default();
+        panes.focused_mut().entry = 1;
+        assert!(panes.split(true));
+        panes.focused_mut().entry = 2;
+        panes.focused_mut().min_level = Some(Level::Warn);
+        assert!(panes.split(false));
+        assert_eq!(
+            panes.tiling,
+            Tiling::Row(vec![
+                Tiling::Pane(0),
+                Tiling::Column(vec![Tiling::Pane(1), Tiling::Pane(2)]),
+            ])
+        );
+
+        let areas = panes.areas(Rect::new(0, 0, 80, 20));
+        assert_eq!(
+            areas,
+            vec![
+                (0, Rect::new(0, 0, 40, 20)),
+                (1, Rect::new(40, 0, 40, 10)),
+                (2, Rect::new(40, 10, 40, 10)),
+            ]
+        );
+
+        // Closing the worker pane leaves the copy of it below, renumbered.
+        panes.cycle_focus(false);
+        assert_eq!(panes.focus(), 1);
+        assert!(panes.close());
+        assert_eq!(
+            panes.tiling,
+            Tiling::Row(vec![Tiling::Pane(0), Tiling::Pane(1)])
+        );
+        assert_eq!(panes.focus(), 0);
+
+        let json = serde_json::to_string(&panes.saved(&processes)).unwrap();
+        let mut processes = processes;
+        processes.reverse();
+        let restored = Panes::restore(serde_json::from_str(&json).unwrap(), &processes).unwrap();
+        assert_eq!(restored.tiling, panes.tiling);
+        assert_eq!(restored.get(0).entry, 2);
+        assert_eq!(restored.get(1).entry, 1);
+        assert_eq!(restored.get(1).min_level, Some(Level::Warn));
+    }
+}
@@ -0,0 +1,78 @@
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+};
+
+use cdi_shared::resource::{ProcessNode, ResourceSample};
+
+pub(crate) const PORT_STYLE: Style = Style::new().fg(Color::Cyan);
+
+/// `512B`, `1.5K`, `145M`, `2.1G`.
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+
+    if bytes < 1024 {
+        return format!("{bytes}B");
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if value < 10.0 {
+        format!("{value:.1}{}", UNITS[unit])
+    } else {
+        format!("{value:.0}{}", UNITS[unit])
+    }
+}
+
+/// Sidebar figures: `3% 145M`.
+pub(crate) fn compact(sample: &ResourceSample) -> String {
+    format!("{:.0}% {}", sample.cpu, format_bytes(sample.memory))
+}
+
+/// `cpu 3.2%  mem 145M  threads 12  fds 40  procs 4`.
+pub(crate) fn summary(sample: &ResourceSample) -> String {
+    format!(
+        "cpu {:.1}%  mem {}  threads {}  fds {}  procs {}",
+        sample.cpu,
+        format_bytes(sample.memory),
+        sample.threads,
+        sample.fds,
+        sample.processes
+    )
+}
+
+/// Ports the whole tree is listening on, ascending.
+pub(crate) fn listening_ports(tree: &[ProcessNode]) -> Vec<u16> {
+    let mut ports: Vec<u16> = tree
+        .iter()
+        .flat_map(|node| node.ports.iter().copied())
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+
+    ports
+}
+
+/// One row of the process tree panel:
+/// `  12345  3.2%   145M :3000 node server.js`, indented by depth.
+pub(crate) fn node_line(node: &ProcessNode) -> Line<'static> {
+    let mut spans = vec![Span::raw(format!(
+        "{}{:<7} {:>5.1}% {:>6} ",
+        "  ".repeat(node.depth as usize),
+        node.pid,
+        node.cpu,
+        format_bytes(node.memory)
+    ))];
+
+    for port in &node.ports {
+        spans.push(Span::styled(format!(":{port} "), PORT_STYLE));
+    }
+    spans.push(Span::raw(node.command.clone()));
+
+    Line::from(spans)
+}
@@ -6,7 +6,7 @@ use tokio::{
     sync::{mpsc, oneshot},
 };
 
-use cdi_shared::event::Event;
+use cdi_shared::event::ui::TuiEvent;
 
 pub(super) struct Signals {
     tx: mpsc::UnboundedSender<(bool, Option<oneshot::Sender<()>>)>,
@@ -20,6 +20,23 @@ impl Signals {
         Ok(Self { tx })
     }
 
+    /// Stops reading the terminal, e.g. while an editor owns it. Resolves
+    /// once the event stream is dropped.
+    pub(super) async fn stop(&self) {
+        self.set(false).await
+    }
+
+    pub(super) async fn resume(&self) {
+        self.set(true).await
+    }
+
+    async fn set(&self, state: bool) {
+        let (cb_tx, cb_rx) = oneshot::channel();
+        if self.tx.send((state, Some(cb_tx))).is_ok() {
+            let _ = cb_rx.await;
+        }
+    }
+
     #[inline]
     fn handle_event(event: CrosstermEvent) {
         match event {
@@ -28,7 +45,8 @@ impl Signals {
                     kind: KeyEventKind::Press,
                     ..
                 },
-            ) => Event::Key(key).emit(),
+            ) => TuiEvent::Key(key).emit(),
+            CrosstermEvent::Mouse(mouse) => TuiEvent::Mouse(mouse).emit(),
             _ => {}
         }
     }
@@ -38,16 +56,24 @@ impl Signals {
 
         tokio::spawn(async move {
             loop {
-                if let Some(evt) = &mut evt_stream {
-                    select! {
-                        biased;
-                        // Some((state, mut callback)) = rx.recv() => {
-                        //     evt_stream = evt_stream.filter(|_| state);
-                        //
-                        //     callback.take().map(|cb| cb.send(()));
-                        // },
-                        Some(Ok(e)) = evt.next() => Self::handle_event(e),
+                let next_event = async {
+                    match &mut evt_stream {
+                        Some(evt) => evt.next().await,
+                        None => std::future::pending().await,
                     }
+                };
+
+                select! {
+                    biased;
+                    Some((state, callback)) = rx.recv() => {
+                        evt_stream = state.then(EventStream::new);
+
+                        if let Some(cb) = callback {
+                            let _ = cb.send(());
+                        }
+                    },
+                    Some(Ok(e)) = next_event => Self::handle_event(e),
+                    else => break,
                 }
             }
         });
@@ -1,11 +1,105 @@
+use clap::{Parser, Subcommand};
 use miette::{Context as _, IntoDiagnostic};
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    os::fd::RawFd,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    select,
+    signal::unix::{SignalKind, signal},
+    time,
+};
 
 use cdi_config as config;
-use cdi_server as server;
-use cdi_shared::{event::Event, log::ProcessInfo};
+use cdi_server::{
+    self as server,
+    client::{ClientRequest, ClientResponse},
+    http::HttpListener,
+    session::SessionLock,
+    store::ExportFormat,
+};
+use cdi_shared::{
+    event::{store::StoreEvent, ui::TuiEvent},
+    log::{Level, ProcessInfo},
+};
 use cdi_tui as tui;
 
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Runs the services in .cdi.kdl and shows their output"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Start the services, the default
+    Up {
+        /// Keep the services running in the background, see `cdi attach`
+        #[arg(short, long)]
+        detach: bool,
+    },
+    /// Open the TUI of services started with `cdi up -d`
+    Attach,
+    /// Stop the services and their server
+    Down,
+    /// Print the output of a running service
+    Logs {
+        /// Service name
+        process: String,
+        /// Keep printing lines as they come
+        #[arg(short, long)]
+        follow: bool,
+        /// Number of lines to print first
+        #[arg(short = 'n', long, default_value_t = 10)]
+        lines: usize,
+    },
+    /// Save the output of a running session to a file
+    Export {
+        /// Service name, every service interleaved when omitted
+        process: Option<String>,
+        /// plain, ansi, ndjson or html
+        #[arg(short, long, default_value = "plain")]
+        format: ExportFormat,
+        /// File to write, stdout when omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Only entries containing this text, ignoring case
+        #[arg(short, long)]
+        search: Option<String>,
+        /// Only entries at this level or above
+        #[arg(short, long, value_parser = parse_level)]
+        level: Option<Level>,
+    },
+    /// Serve the Model Context Protocol on stdio, for coding agents to
+    /// inspect and restart the services of a running session
+    Mcp,
+    /// Run the background server, started by `cdi up -d`
+    #[command(hide = true)]
+    Serve {
+        /// Adopt process groups left by a crashed session
+        #[arg(long)]
+        adopt: bool,
+        /// The session lock, acquired by the `cdi up -d` that started it
+        #[arg(long)]
+        session_fd: Option<RawFd>,
+    },
+}
+
+/// What to do with process groups a crashed session left running.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Leftovers {
+    Kill,
+    /// Keep the groups matching a service by name, kill the rest.
+    Adopt,
+}
+
 fn get_config() -> miette::Result<config::Config> {
     let mut cwd: PathBuf = env::current_dir()
         .into_diagnostic()
@@ -15,24 +109,395 @@ fn get_config() -> miette::Result<config::Config> {
     config::Config::load(cwd.as_path())
 }
 
-#[tokio::main]
-async fn main() -> miette::Result<()> {
-    Event::init();
-    let mut services = Vec::new();
-    let cfg = get_config()?;
+fn process_infos(cfg: &config::Config) -> Vec<ProcessInfo> {
+    cfg.services
+        .iter()
+        .map(|s| ProcessInfo {
+            multiline: s.multiline.clone(),
+            ports: s.ports.clone(),
+            ..ProcessInfo::new(s.name.clone(), s.cmd.clone(), s.cwd.clone())
+        })
+        .collect()
+}
 
-    // for service in cfg.services.iter() {
-    //     services.push(service.name.clone());
-    // }
+fn parse_level(s: &str) -> Result<Level, String> {
+    Level::parse(s).ok_or_else(|| format!("unknown level '{s}'"))
+}
 
-    let process_infos: Vec<ProcessInfo> = cfg
-        .services
-        .iter()
-        .map(|s| ProcessInfo::new(s.name.clone(), s.cmd.clone(), s.cwd.clone()))
-        .collect();
+fn acquire_session(project_dir: &Path) -> miette::Result<SessionLock> {
+    SessionLock::acquire(project_dir).map_err(|e| miette::miette!("{e}"))
+}
+
+/// Binds the HTTP API's port when the config asks for one.
+fn bind_http(cfg: &config::Config) -> miette::Result<Option<HttpListener>> {
+    bind_port(cfg.http, "the HTTP API")
+}
+
+/// Binds the metrics port when the config asks for one.
+fn bind_metrics(cfg: &config::Config) -> miette::Result<Option<HttpListener>> {
+    bind_port(cfg.metrics, "metrics")
+}
+
+fn bind_port(port: Option<u16>, what: &str) -> miette::Result<Option<HttpListener>> {
+    port.map(|port| {
+        HttpListener::bind(port)
+            .map_err(|e| miette::miette!("can't serve {what} on port {port}: {e}"))
+    })
+    .transpose()
+}
+
+/// Asks what to do with the process groups a crashed session left running.
+fn ask_about_leftovers(lock: &SessionLock) -> miette::Result<Leftovers> {
+    let leftovers = lock.leftovers();
+    if leftovers.is_empty() {
+        return Ok(Leftovers::Kill);
+    }
+
+    eprintln!("A previous cdi session left these process groups running:");
+    for group in leftovers {
+        eprintln!("  {} (pgid {}): {}", group.name, group.pgid, group.cmdline);
+    }
+
+    if !io::stdin().is_terminal() {
+        miette::bail!("stop the leftover process groups, then start cdi again");
+    }
+
+    loop {
+        eprint!("[k]ill them, [a]dopt them or [q]uit? ");
+        io::stderr().flush().into_diagnostic()?;
+
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).into_diagnostic()? == 0 {
+            miette::bail!("leftover process groups are still running");
+        }
+
+        match answer.trim() {
+            "k" => return Ok(Leftovers::Kill),
+            "a" => return Ok(Leftovers::Adopt),
+            "q" => miette::bail!("leftover process groups are still running"),
+            _ => {}
+        }
+    }
+}
+
+async fn handle_leftovers(
+    lock: &SessionLock,
+    choice: Leftovers,
+    process_infos: &mut [ProcessInfo],
+) {
+    let leftovers = lock.leftovers();
+    if choice == Leftovers::Kill {
+        return server::session::kill_groups(leftovers).await;
+    }
+
+    let mut unmatched = Vec::new();
+    for group in leftovers {
+        let info = process_infos
+            .iter_mut()
+            .find(|info| info.name == group.name && info.pid.is_none());
+        match info {
+            Some(info) => info.pid = Some(group.pgid as usize),
+            None => unmatched.push(group.clone()),
+        }
+    }
+    server::session::kill_groups(&unmatched).await;
+}
+
+/// Runs the services with the TUI in this process.
+async fn up(cfg: config::Config, project_dir: &Path) -> miette::Result<()> {
+    let lock = acquire_session(project_dir)?;
+    let mut process_infos = process_infos(&cfg);
+    let choice = ask_about_leftovers(&lock)?;
+    handle_leftovers(&lock, choice, &mut process_infos).await;
+    let http = bind_http(&cfg)?;
+    let metrics = bind_metrics(&cfg)?;
+
+    let (conn, store) = server::serve(process_infos, Some(lock)).unwrap();
+
+    let listener = server::client::ClientListener::start(
+        server::client::socket_path(project_dir),
+        store.clone(),
+        conn.sender.clone(),
+    )
+    .map_err(|e| eprintln!("Client socket disabled: {e}"))
+    .ok();
+    let http = http.and_then(|http| {
+        http.start(store.clone(), conn.sender.clone())
+            .map_err(|e| eprintln!("HTTP API disabled: {e}"))
+            .ok()
+    });
+    let metrics = metrics.and_then(|metrics| {
+        metrics
+            .start_metrics(store.clone())
+            .map_err(|e| eprintln!("Metrics disabled: {e}"))
+            .ok()
+    });
+
+    let _ = tui::run(Some(conn), store, cfg, project_dir).await;
+    drop(metrics);
+    drop(http);
+    drop(listener);
+
+    Ok(())
+}
+
+/// Starts `cdi serve` in the background. Leftovers are asked about here,
+/// while there still is a terminal.
+fn up_detached(cfg: config::Config, project_dir: &Path) -> miette::Result<()> {
+    let lock = acquire_session(project_dir)?;
+    let choice = ask_about_leftovers(&lock)?;
+
+    let args: &[&str] = match choice {
+        Leftovers::Kill => &["serve"],
+        Leftovers::Adopt => &["serve", "--adopt"],
+    };
+    // The server holds the lock by the time it listens.
+    let pid =
+        server::daemon::spawn(project_dir, args, &lock).map_err(|e| miette::miette!("{e}"))?;
+    drop(lock);
+    println!("cdi is running in the background (pid {pid}), `cdi attach` to view it");
+    if let Some(port) = cfg.http {
+        println!("HTTP API on http://127.0.0.1:{port}");
+    }
+    if let Some(port) = cfg.metrics {
+        println!("Metrics on http://127.0.0.1:{port}/metrics");
+    }
+
+    Ok(())
+}
+
+/// The background server: runs the services until `cdi down` or a signal.
+async fn serve(
+    cfg: config::Config,
+    project_dir: &Path,
+    adopt: bool,
+    session_fd: Option<RawFd>,
+) -> miette::Result<()> {
+    let lock = match session_fd {
+        Some(fd) => SessionLock::inherit(fd).map_err(|e| miette::miette!("{e}"))?,
+        None => acquire_session(project_dir)?,
+    };
+    let mut process_infos = process_infos(&cfg);
+    let choice = if adopt {
+        Leftovers::Adopt
+    } else {
+        Leftovers::Kill
+    };
+    handle_leftovers(&lock, choice, &mut process_infos).await;
+    let http = bind_http(&cfg)?;
+    let metrics = bind_metrics(&cfg)?;
+
+    let (mut conn, store) = server::serve(process_infos, Some(lock)).unwrap();
+    let listener = server::client::ClientListener::start(
+        server::client::socket_path(project_dir),
+        store.clone(),
+        conn.sender.clone(),
+    )
+    .map_err(|e| miette::miette!("{e}"))?;
+    let metrics = metrics
+        .map(|metrics| metrics.start_metrics(store.clone()))
+        .transpose()
+        .map_err(|e| miette::miette!("{e}"))?;
+    let http = http
+        .map(|http| http.start(store, conn.sender.clone()))
+        .transpose()
+        .map_err(|e| miette::miette!("{e}"))?;
+
+    let mut events = TuiEvent::take();
+    let mut terminate = signal(SignalKind::terminate()).into_diagnostic()?;
+    let mut interrupt = signal(SignalKind::interrupt()).into_diagnostic()?;
+    let mut hangup = signal(SignalKind::hangup()).into_diagnostic()?;
+    loop {
+        select! {
+            Some(event) = events.recv() => {
+                if matches!(event, TuiEvent::Quit) {
+                    break;
+                }
+            }
+            _ = terminate.recv() => break,
+            _ = interrupt.recv() => break,
+            // Left over from the terminal it was started from.
+            _ = hangup.recv() => {}
+        }
+    }
 
-    let conn = server::serve(process_infos.clone()).unwrap();
-    let _ = tui::run(conn, process_infos).await;
+    if !conn.shutdown().await {
+        eprintln!("Some services did not exit");
+    }
+    drop(metrics);
+    drop(http);
+    drop(listener);
 
     Ok(())
 }
+
+async fn attach(cfg: config::Config, project_dir: &Path) -> miette::Result<()> {
+    let store = server::client::attach(&server::client::socket_path(project_dir))
+        .await
+        .map_err(|e| miette::miette!("{e}"))?;
+
+    let _ = tui::run(None, store, cfg, project_dir).await;
+
+    Ok(())
+}
+
+async fn down(project_dir: &Path) -> miette::Result<()> {
+    let socket_path = server::client::socket_path(project_dir);
+    let response = server::client::request(&socket_path, &ClientRequest::Shutdown)
+        .await
+        .map_err(|e| miette::miette!("{e}"))?;
+    if let ClientResponse::Error { message } = response {
+        miette::bail!(message);
+    }
+
+    // Done once the server stops listening.
+    for _ in 0..100 {
+        if !socket_path.exists() {
+            break;
+        }
+        time::sleep(Duration::from_millis(50)).await;
+    }
+
+    Ok(())
+}
+
+async fn logs(
+    project_dir: &Path,
+    process: String,
+    follow: bool,
+    lines: usize,
+) -> miette::Result<()> {
+    let socket_path = server::client::socket_path(project_dir);
+    let request = ClientRequest::GetLogs {
+        process_id: process.clone(),
+        limit: lines,
+        stream: None,
+        search: None,
+        level: None,
+        since: None,
+    };
+    let logs = match server::client::request(&socket_path, &request)
+        .await
+        .map_err(|e| miette::miette!("{e}"))?
+    {
+        ClientResponse::Logs(logs) => logs,
+        ClientResponse::Error { message } => miette::bail!(message),
+        response => miette::bail!("unexpected response: {response:?}"),
+    };
+
+    // Stops quietly once stdout is closed, e.g. piped into `head`.
+    let mut stdout = io::stdout().lock();
+    for line in &logs {
+        if writeln!(stdout, "{}", line.content).is_err() {
+            return Ok(());
+        }
+    }
+    if !follow {
+        return Ok(());
+    }
+    drop(stdout);
+
+    let request = ClientRequest::FollowLogs {
+        process_id: process,
+        after: logs.last().map(|line| line.id),
+        seen_lines: logs.last().map(|line| line.content.split('\n').count()),
+        stream: None,
+    };
+    let mut responses = server::client::open(&socket_path, &request)
+        .await
+        .map_err(|e| miette::miette!("{e}"))?;
+    while let Some(response) = responses.next().await.map_err(|e| miette::miette!("{e}"))? {
+        match response {
+            ClientResponse::Logs(logs) => {
+                let mut stdout = io::stdout().lock();
+                for line in logs {
+                    if writeln!(stdout, "{}", line.content).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            ClientResponse::Lagged { missed } => {
+                eprintln!("cdi: skipped {missed} entries, evicted before they could be printed")
+            }
+            ClientResponse::Error { message } => miette::bail!(message),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn export(
+    project_dir: &Path,
+    process: Option<String>,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+    search: Option<String>,
+    level: Option<Level>,
+) -> miette::Result<()> {
+    let request = ClientRequest::ExportLogs {
+        process_id: process,
+        format,
+        stream: None,
+        search,
+        level,
+        since: None,
+    };
+    let (entries, content) =
+        match server::client::request(&server::client::socket_path(project_dir), &request)
+            .await
+            .map_err(|e| miette::miette!("{e}"))?
+        {
+            ClientResponse::Exported { entries, content } => (entries, content),
+            ClientResponse::Error { message } => miette::bail!(message),
+            response => miette::bail!("unexpected response: {response:?}"),
+        };
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, content)
+                .into_diagnostic()
+                .with_context(|| format!("can't write {}", path.display()))?;
+            eprintln!("Exported {entries} entries to {}", path.display());
+        }
+        // Stops quietly once stdout is closed, e.g. piped into `head`.
+        None => {
+            let _ = io::stdout().lock().write_all(content.as_bytes());
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> miette::Result<()> {
+    let cli = Cli::parse();
+    TuiEvent::init();
+    StoreEvent::init();
+
+    let project_dir = env::current_dir().into_diagnostic()?;
+    match cli.command.unwrap_or(Command::Up { detach: false }) {
+        Command::Up { detach: false } => up(get_config()?, &project_dir).await,
+        Command::Up { detach: true } => up_detached(get_config()?, &project_dir),
+        Command::Attach => attach(get_config()?, &project_dir).await,
+        Command::Down => down(&project_dir).await,
+        Command::Logs {
+            process,
+            follow,
+            lines,
+        } => logs(&project_dir, process, follow, lines).await,
+        Command::Export {
+            process,
+            format,
+            output,
+            search,
+            level,
+        } => export(&project_dir, process, format, output, search, level).await,
+        Command::Mcp => server::mcp::serve_stdio(server::client::socket_path(&project_dir))
+            .await
+            .map_err(|e| miette::miette!("{e}")),
+        Command::Serve { adopt, session_fd } => {
+            serve(get_config()?, &project_dir, adopt, session_fd).await
+        }
+    }
+}